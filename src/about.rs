@@ -0,0 +1,357 @@
+//! Internal `about:` pages showing the state of the browser
+//!
+//! Buttons on them are forms submitting back to the page, like `about:cache?delete=<url>`.
+//! These actions only run when submitted from an `about:` page, never from links of web pages.
+
+use crate::config;
+use crate::data_storage::{self, downloads, escape};
+use crate::history;
+
+use reqwest::Url;
+
+/// the pages listed on `about:`
+const PAGES: [&str; 5] = ["blank", "cache", "config", "downloads", "history"];
+
+/// Html document of an `about:` url, navigated to from the page `referrer`.
+pub fn page(url: &Url, referrer: Option<&Url>) -> String {
+    if url.scheme() != "about" {
+        return format!("Not an about page: {}", url);
+    }
+    let query = url.query_pairs().into_owned().collect::<Vec<_>>();
+    let actions = runs_actions(referrer);
+    let parameter = |name: &str| {
+        query
+            .iter()
+            .find(|(key, _)| key == name && actions)
+            .map(|(_, value)| value.as_str())
+    };
+
+    match url.path() {
+        "blank" => String::from("<html><head></head><body></body></html>"),
+        "" => document(
+            "about:",
+            &PAGES
+                .iter()
+                .map(|page| format!("<p><a href=\"about:{0}\">about:{0}</a></p>", page))
+                .collect::<String>(),
+        ),
+        "cache" => {
+            if let Some(url) = parameter("delete").and_then(|url| Url::parse(url).ok()) {
+                data_storage::remove_from_cache(&url);
+            }
+            if parameter("clear").is_some() {
+                data_storage::clear_cache();
+            }
+            document("Cache", &cache())
+        }
+        "history" => {
+            if parameter("clear").is_some() {
+                if let Err(error) = history::clear() {
+                    eprintln!("Couldn't clear history: {}", error);
+                }
+            }
+            document("History", &visits())
+        }
+        "downloads" => {
+            let id = |name| parameter(name).and_then(|id| id.parse().ok());
+            if let Some(id) = id("pause") {
+                downloads::pause(id);
+            }
+            if let Some(id) = id("resume") {
+                downloads::resume(id);
+            }
+            document("Downloads", &downloads())
+        }
+        "config" => document("Config", &settings(&config::get())),
+        _ => document(
+            "Page Not Found",
+            &format!(
+                "<p>There is no {}, see <a href=\"about:\">about:</a></p>",
+                escape(url.as_str())
+            ),
+        ),
+    }
+}
+
+/// Do the query parameters of a page act, like `about:history?clear`?
+/// Only if sent from an `about:` page, not on reloads or from links and meta refreshes of web pages.
+fn runs_actions(referrer: Option<&Url>) -> bool {
+    referrer.is_some_and(|referrer| referrer.scheme() == "about")
+}
+
+fn document(title: &str, body: &str) -> String {
+    data_storage::open_local_file("assets/about.html")
+        .expect("'about' asset to be present")
+        .replacen("replace_title", title, 2)
+        .replacen("replace_body", body, 1)
+}
+
+/// Html table of cells that are html already
+fn table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let row = |cells: Vec<String>| {
+        let cells = cells
+            .into_iter()
+            .map(|cell| format!("<td>{}</td>", cell))
+            .collect::<String>();
+        format!("<tr>{}</tr>", cells)
+    };
+    let header = header
+        .iter()
+        .map(|cell| format!("<th>{}</th>", cell))
+        .collect::<String>();
+
+    format!(
+        "<table><tr>{}</tr>{}</table>",
+        header,
+        rows.into_iter().map(row).collect::<String>()
+    )
+}
+
+fn link(url: &Url) -> String {
+    format!("<a href=\"{0}\">{0}</a>", escape(url.as_str()))
+}
+
+fn cache() -> String {
+    let entries = data_storage::cache_entries();
+    let total = entries.iter().map(|entry| entry.size).sum();
+
+    let rows = entries
+        .into_iter()
+        .map(|entry| {
+            vec![
+                link(&entry.url),
+                data_storage::format_size(entry.size),
+                escape(&entry.mime_type),
+                data_storage::format_date(entry.stored + entry.lifetime),
+                format!(
+                    "<button name=\"delete\" value=\"{}\">Delete</button>",
+                    escape(entry.url.as_str())
+                ),
+            ]
+        })
+        .collect();
+
+    format!(
+        "<form action=\"about:cache\"><p>{} in total <button name=\"clear\">Delete all</button></p>{}</form>",
+        data_storage::format_size(total),
+        table(&["Url", "Size", "Type", "Expires", ""], rows)
+    )
+}
+
+fn visits() -> String {
+    let rows = history::visits()
+        .into_iter()
+        .map(|visit| {
+            vec![
+                data_storage::format_date(visit.time),
+                escape(&visit.title),
+                link(&visit.url),
+            ]
+        })
+        .collect();
+
+    format!(
+        "<form action=\"about:history\"><p><button name=\"clear\">Clear history</button></p></form>{}",
+        table(&["Visited", "Title", "Url"], rows)
+    )
+}
+
+fn downloads() -> String {
+    let list = downloads::list();
+    let running = list
+        .iter()
+        .any(|download| download.state == downloads::State::Running);
+
+    let rows = list
+        .into_iter()
+        .rev()
+        .map(|download| {
+            let path = download.path.to_string_lossy();
+            let file = match (&download.state, Url::from_file_path(&download.path)) {
+                (downloads::State::Finished, Ok(url)) => {
+                    format!("<a href=\"{}\">{}</a>", escape(url.as_str()), escape(&path))
+                }
+                _ => escape(&path),
+            };
+            let progress = match download.total {
+                Some(total) if total > 0 => format!(
+                    "{} of {} ({}%)",
+                    data_storage::format_size(download.received),
+                    data_storage::format_size(total),
+                    download.received * 100 / total
+                ),
+                _ => data_storage::format_size(download.received),
+            };
+            let (state, button) = match &download.state {
+                downloads::State::Running => {
+                    (String::from("Downloading"), Some(("pause", "Pause")))
+                }
+                downloads::State::Paused => (String::from("Paused"), Some(("resume", "Resume"))),
+                downloads::State::Finished => (String::from("Finished"), None),
+                downloads::State::Failed(error) => (
+                    format!("Failed: {}", escape(error)),
+                    Some(("resume", "Retry")),
+                ),
+            };
+            let button = button
+                .map(|(name, label)| {
+                    format!(
+                        "<button name=\"{}\" value=\"{}\">{}</button>",
+                        name, download.id, label
+                    )
+                })
+                .unwrap_or_default();
+            vec![link(&download.url), file, progress, state, button]
+        })
+        .collect();
+
+    // progress is updated while downloading
+    let refresh = if running {
+        "<meta http-equiv=\"refresh\" content=\"1; url=about:downloads\">"
+    } else {
+        ""
+    };
+    format!(
+        "{}<form action=\"about:downloads\">{}</form>",
+        refresh,
+        table(&["Url", "File", "Progress", "State", ""], rows)
+    )
+}
+
+fn settings(config: &config::Config) -> String {
+    let option = |value: &Option<String>| escape(value.as_deref().unwrap_or("direct"));
+    let general = vec![
+        ("https_only", config.https_only.to_string()),
+        ("refresh_banner", config.refresh_banner.to_string()),
+        (
+            "pause_cross_site_redirects",
+            config.pause_cross_site_redirects.to_string(),
+        ),
+        ("max_redirects", config.max_redirects.to_string()),
+        ("profile", escape(&config.profile)),
+        ("proxy", option(&config.proxy)),
+        ("onion_proxy", option(&config.onion_proxy)),
+        ("search", escape(&config.search)),
+        ("clean_urls", config.clean_urls.to_string()),
+        (
+            "strip_parameters",
+            escape(&config.strip_parameters.join(", ")),
+        ),
+    ]
+    .into_iter()
+    .map(|(key, value)| vec![key.to_string(), value])
+    .collect();
+
+    let mut profiles = config.profiles.iter().collect::<Vec<_>>();
+    profiles.sort_by_key(|(name, _)| name.as_str());
+    let profiles = profiles
+        .into_iter()
+        .map(|(name, profile)| {
+            vec![
+                escape(name),
+                escape(&profile.user_agent),
+                escape(&profile.languages.join(", ")),
+                profile.dnt.to_string(),
+                profile.gpc.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut sites = config.sites.iter().collect::<Vec<_>>();
+    sites.sort_by_key(|(host, _)| host.as_str());
+    let sites = sites
+        .into_iter()
+        .map(|(host, site)| {
+            vec![
+                escape(host),
+                escape(site.profile.as_deref().unwrap_or("")),
+                site.proxy.as_ref().map(option).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    let engines = config
+        .engines
+        .iter()
+        .map(|engine| {
+            vec![
+                escape(&engine.name),
+                escape(engine.keyword.as_deref().unwrap_or("")),
+                escape(&engine.url),
+                escape(engine.post.as_deref().unwrap_or("")),
+            ]
+        })
+        .collect();
+
+    let mut url_rules = config.url_rules.iter().collect::<Vec<_>>();
+    url_rules.sort_by_key(|(domain, _)| domain.as_str());
+    let url_rules = url_rules
+        .into_iter()
+        .map(|(domain, rules)| {
+            let redirects = rules
+                .redirects
+                .iter()
+                .map(|(path, parameter)| format!("{}?{}", path, parameter))
+                .collect::<Vec<_>>();
+            vec![
+                escape(domain),
+                escape(&rules.strip_parameters.join(", ")),
+                escape(&redirects.join(", ")),
+            ]
+        })
+        .collect();
+
+    format!(
+        "<p>Settings are read from data/config.</p>{}<h2>Header profiles</h2>{}<h2>Sites</h2>{}<h2>Search engines</h2>{}<h2>Url cleaning</h2>{}",
+        table(&["Setting", "Value"], general),
+        table(&["Name", "User agent", "Languages", "DNT", "GPC"], profiles),
+        table(&["Site", "Profile", "Proxy"], sites),
+        table(&["Name", "Keyword", "Url", "Post"], engines),
+        table(&["Domain", "Stripped parameters", "Redirects"], url_rules)
+    )
+}
+
+#[cfg(test)]
+mod pages {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn blank() {
+        assert_eq!(
+            page(&url("about:blank"), None),
+            "<html><head></head><body></body></html>"
+        );
+    }
+
+    #[test]
+    fn unknown() {
+        assert!(page(&url("about:nothing"), None).contains("There is no about:nothing"));
+        assert!(page(&url("about:"), None).contains("<a href=\"about:cache\">about:cache</a>"));
+        assert!(page(&url("https://example.com/"), None).starts_with("Not an about page"));
+    }
+
+    #[test]
+    fn actions() {
+        assert!(runs_actions(Some(&url("about:cache"))));
+        assert!(!runs_actions(Some(&url("https://example.com/"))));
+        assert!(!runs_actions(None));
+    }
+
+    #[test]
+    fn config() {
+        let config = config::Config::parse(
+            "proxy = socks5h://127.0.0.1:1080\n[site example.com]\nproxy = direct\n[search wiki]\nurl = https://example.org/?q={searchTerms}&a\nkeyword = w\n",
+        );
+        let settings = settings(&config);
+
+        assert!(settings.contains("<tr><td>proxy</td><td>socks5h://127.0.0.1:1080</td></tr>"));
+        assert!(settings.contains("<tr><td>example.com</td><td></td><td>direct</td></tr>"));
+        assert!(settings.contains(
+            "<td>wiki</td><td>w</td><td>https://example.org/?q={searchTerms}&amp;a</td>"
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! Removing tracking parameters from urls navigated to and unwrapping redirect wrappers,
+//! with the rules of the config
+
+use crate::config::Config;
+
+use reqwest::Url;
+
+/// redirect wrappers unwrapped at most, they can wrap each other
+const MAX_UNWRAPS: usize = 5;
+
+/// Url without tracking parameters, leading to the target of redirect wrappers directly.
+/// Anything but http(s) urls is returned as it is.
+pub fn clean(config: &Config, url: &str) -> String {
+    let mut parsed = match Url::parse(url) {
+        Ok(parsed) if config.clean_urls && is_http(&parsed) => parsed,
+        _ => return url.to_string(),
+    };
+
+    for _ in 0..MAX_UNWRAPS {
+        match unwrap(config, &parsed) {
+            Some(target) => parsed = target,
+            None => break,
+        }
+    }
+    strip(config, &mut parsed);
+
+    parsed.to_string()
+}
+
+/// Target of a redirect wrapper, like `https://www.google.com/url?q=<target>`.
+fn unwrap(config: &Config, url: &Url) -> Option<Url> {
+    let rules = config.url_rules(url.host_str()?);
+    let parameter = rules
+        .iter()
+        .flat_map(|rules| rules.redirects.iter())
+        .find(|(path, _)| matches(path, url.path()))
+        .map(|(_, parameter)| parameter)?;
+
+    let (_, target) = url.query_pairs().find(|(name, _)| name == parameter)?;
+    Url::parse(&target).ok().filter(is_http)
+}
+
+/// Removes the query parameters to strip on the url's host, keeping how the others are encoded.
+fn strip(config: &Config, url: &mut Url) {
+    let query = match url.query() {
+        Some(query) => query.to_string(),
+        None => return,
+    };
+    let rules = config.url_rules(url.host_str().unwrap_or_default());
+    let parameters = config
+        .strip_parameters
+        .iter()
+        .chain(rules.iter().flat_map(|rules| rules.strip_parameters.iter()))
+        .collect::<Vec<_>>();
+
+    let kept = query
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && !parameters.iter().any(|pattern| matches(pattern, name))
+        })
+        .collect::<Vec<_>>();
+
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.set_query(Some(&kept.join("&")));
+    }
+}
+
+/// Does `text` match the case-insensitive `pattern`, whose trailing `*` matches any end?
+fn matches(pattern: &str, text: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => text
+            .get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+        None => pattern.eq_ignore_ascii_case(text),
+    }
+}
+
+fn is_http(url: &Url) -> bool {
+    url.scheme() == "http" || url.scheme() == "https"
+}
+
+#[cfg(test)]
+mod rules {
+    use super::*;
+
+    #[test]
+    fn tracking_parameters() {
+        let config = Config::default();
+
+        assert_eq!(
+            clean(
+                &config,
+                "https://example.com/a?id=1&utm_source=news&UTM_Medium=mail&fbclid=x&q=a%20b#top"
+            ),
+            "https://example.com/a?id=1&q=a%20b#top"
+        );
+        assert_eq!(
+            clean(&config, "https://example.com/?gclid=1"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            clean(&config, "https://www.youtube.com/watch?v=id&si=share"),
+            "https://www.youtube.com/watch?v=id"
+        );
+        // only stripped on youtube
+        assert_eq!(
+            clean(&config, "https://example.com/watch?v=id&si=1"),
+            "https://example.com/watch?v=id&si=1"
+        );
+        assert_eq!(
+            clean(
+                &Config::parse("clean_urls = false"),
+                "https://a.com/?gclid=1"
+            ),
+            "https://a.com/?gclid=1"
+        );
+        assert_eq!(
+            clean(&config, "gopher://example.com/?utm_source=1"),
+            "gopher://example.com/?utm_source=1"
+        );
+        assert_eq!(clean(&config, "rust borrow"), "rust borrow");
+    }
+
+    #[test]
+    fn redirect_wrappers() {
+        let config = Config::parse("[clean example.com]\nredirect = /out?to");
+
+        assert_eq!(
+            clean(
+                &config,
+                "https://www.google.com/url?sa=t&q=https%3A%2F%2Fexample.org%2Fpage%3Fid%3D1%26utm_campaign%3Dx&ved=1"
+            ),
+            "https://example.org/page?id=1"
+        );
+        // nested wrappers
+        assert_eq!(
+            clean(
+                &config,
+                "https://example.com/out?to=https%3A%2F%2Fl.facebook.com%2Fl.php%3Fu%3Dhttps%253A%252F%252Fexample.net%252F"
+            ),
+            "https://example.net/"
+        );
+        assert_eq!(
+            clean(&config, "https://example.com/out?to=javascript:alert(1)"),
+            "https://example.com/out?to=javascript:alert(1)"
+        );
+        assert_eq!(
+            clean(&config, "https://example.com/in?to=https://example.net/"),
+            "https://example.com/in?to=https://example.net/"
+        );
+    }
+}
@@ -0,0 +1,742 @@
+//! User settings, read from `data/config`
+//!
+//! ```text
+//! # comment
+//! https_only = true
+//! # ask before following `<meta http-equiv="refresh">` redirects
+//! refresh_banner = true
+//! # ask before a page redirects to another site
+//! pause_cross_site_redirects = true
+//! max_redirects = 10
+//! profile = uniform
+//! # `http://`, `socks5://` or `socks5h://` (hostnames resolved by the proxy), `direct` for none
+//! proxy = socks5h://127.0.0.1:1080
+//! onion_proxy = socks5h://127.0.0.1:9050
+//! # default search engine
+//! search = wikipedia
+//! # query parameters removed from urls navigated to, added to the built-in ones
+//! strip_parameters = ref, campaign_*
+//!
+//! [profile work]
+//! user_agent = Mozilla/5.0 (X11; Linux x86_64; rv:115.0) Gecko/20100101 Firefox/115.0
+//! languages = de-DE, de, en
+//! dnt = false
+//! gpc = true
+//!
+//! [site example.com]
+//! profile = work
+//! proxy = direct
+//!
+//! # `w rust` searches wikipedia, `{searchTerms}` is replaced by the search
+//! [search wikipedia]
+//! url = https://en.wikipedia.org/w/index.php?search={searchTerms}
+//! keyword = w
+//!
+//! # posted as form data
+//! [search duckduckgo]
+//! url = https://duckduckgo.com/lite/
+//! post = q={searchTerms}&kl=us-en
+//!
+//! # urls of example.com and its subdomains lose `src`, `/out?to=…` leads to its `to` directly
+//! [clean example.com]
+//! strip_parameters = src
+//! redirect = /out?to
+//! ```
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+const CONFIG_FILE: &str = "data/config";
+
+static CONFIG: Lazy<RwLock<Arc<Config>>> =
+    Lazy::new(|| RwLock::new(Arc::new(Config::load(CONFIG_FILE))));
+
+/// The settings of the browser.
+pub fn get() -> Arc<Config> {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Adds `lines` to the end of the config file and reads it again.
+pub fn append(lines: &str) -> Result<(), String> {
+    fs::create_dir_all("data").map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(CONFIG_FILE)
+        .map_err(|e| e.to_string())?;
+    write!(file, "\n{}", lines).map_err(|e| e.to_string())?;
+
+    *CONFIG.write().unwrap() = Arc::new(Config::load(CONFIG_FILE));
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Config {
+    /// upgrade every http request to https
+    pub https_only: bool,
+    /// show a banner instead of following meta refresh redirects
+    pub refresh_banner: bool,
+    /// ask before following a redirect of a page to another site
+    pub pause_cross_site_redirects: bool,
+    /// redirects followed for a request before giving up on a loop
+    pub max_redirects: usize,
+    /// name of the header profile used for sites without an override
+    pub profile: String,
+    /// built-in and configured header profiles by name
+    pub profiles: HashMap<String, HeaderProfile>,
+    /// proxy for all requests, `None` to connect directly
+    pub proxy: Option<String>,
+    /// proxy for `.onion` hosts, the Tor client
+    pub onion_proxy: Option<String>,
+    /// host → overrides for it and its subdomains
+    pub sites: HashMap<String, SiteSettings>,
+    /// name of the search engine used without a keyword
+    pub search: String,
+    /// built-in and configured search engines, in order
+    pub engines: Vec<SearchEngine>,
+    /// remove tracking parameters and unwrap redirect wrappers of urls navigated to
+    pub clean_urls: bool,
+    /// query parameters removed from every url, a trailing `*` matches any end
+    pub strip_parameters: Vec<String>,
+    /// domain → url cleaning rules for it and its subdomains
+    pub url_rules: HashMap<String, UrlRules>,
+}
+
+/// Url cleaning rules of a `[clean example.com]` section
+#[derive(Debug, Default, PartialEq)]
+pub struct UrlRules {
+    /// query parameters removed, like the global `strip_parameters`
+    pub strip_parameters: Vec<String>,
+    /// redirect wrappers: a path and the query parameter holding the target, `/url?q`
+    pub redirects: Vec<(String, String)>,
+}
+
+impl UrlRules {
+    fn new(strip_parameters: &[&str], redirects: &[&str]) -> Self {
+        let mut rules = Self::default();
+        rules.add_parameters(&strip_parameters.join(","));
+        rules.add_redirects(&redirects.join(","));
+        rules
+    }
+
+    fn add_parameters(&mut self, value: &str) {
+        self.strip_parameters.extend(list(value));
+    }
+
+    /// Adds `/path?parameter` redirect wrappers, wrappers without a parameter are ignored.
+    fn add_redirects(&mut self, value: &str) {
+        self.redirects
+            .extend(list(value).into_iter().filter_map(|redirect| {
+                let (path, parameter) = redirect.split_once('?')?;
+                Some((path.to_string(), parameter.to_string()))
+            }));
+    }
+}
+
+/// A `[search name]` section
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchEngine {
+    pub name: String,
+    /// url template, `{searchTerms}` is replaced by the search
+    pub url: String,
+    /// template of form data posted to `url`, `None` for GET requests
+    pub post: Option<String>,
+    /// prefix choosing this engine, `w` for `w rust`
+    pub keyword: Option<String>,
+}
+
+impl SearchEngine {
+    /// Section for the config file.
+    pub fn to_config(&self) -> String {
+        let mut section = format!("[search {}]\nurl = {}\n", self.name, self.url);
+        if let Some(post) = &self.post {
+            section.push_str(&format!("post = {}\n", post));
+        }
+        if let Some(keyword) = &self.keyword {
+            section.push_str(&format!("keyword = {}\n", keyword));
+        }
+        section
+    }
+}
+
+/// Settings of a `[site example.com]` section
+#[derive(Debug, Default, PartialEq)]
+pub struct SiteSettings {
+    /// header profile name
+    pub profile: Option<String>,
+    /// `Some(None)` connects directly
+    pub proxy: Option<Option<String>>,
+}
+
+/// Identifying request headers sent to sites
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderProfile {
+    pub user_agent: String,
+    /// `Accept` of top-level documents
+    pub accept: String,
+    /// preferred languages, first is most preferred
+    pub languages: Vec<String>,
+    /// send `DNT: 1`
+    pub dnt: bool,
+    /// send `Sec-GPC: 1`, <https://globalprivacycontrol.github.io/gpc-spec/>
+    pub gpc: bool,
+}
+
+impl HeaderProfile {
+    /// what this browser always sent
+    fn default_profile() -> Self {
+        Self {
+            user_agent: String::from(
+                "Mozilla/5.0 (Windows NT 10.0; rv:68.0) Gecko/20100101 Firefox/68.0",
+            ),
+            accept: String::from(
+                "text/html, application/xhtml+xml, application/xml;q=0.9, */*;q=0.8",
+            ),
+            languages: vec![String::from("en-us"), String::from("en")],
+            dnt: true,
+            gpc: false,
+        }
+    }
+
+    /// headers of the most common Firefox release on Windows, sent as they are,
+    /// so requests blend in with those of many other users
+    fn uniform() -> Self {
+        Self {
+            user_agent: String::from(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0",
+            ),
+            accept: String::from(
+                "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/png,image/svg+xml,*/*;q=0.8",
+            ),
+            languages: vec![String::from("en-US"), String::from("en")],
+            dnt: false,
+            gpc: false,
+        }
+    }
+
+    /// `Accept-Language` value, `de-DE,de;q=0.7,en;q=0.3`
+    /// Qualities go down evenly, rounded like Firefox does.
+    pub fn accept_language(&self) -> String {
+        let count = self.languages.len();
+        self.languages
+            .iter()
+            .enumerate()
+            .map(|(i, language)| {
+                let quality = 1.0 - i as f32 / count as f32;
+                match i {
+                    0 => language.clone(),
+                    _ if count > 10 => format!("{};q={:.2}", language, quality),
+                    _ => format!("{};q={:.1}", language, quality),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// query parameters of ad and newsletter click tracking, removed everywhere
+const TRACKING_PARAMETERS: [&str; 18] = [
+    "utm_*",
+    "fbclid",
+    "gclid",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "yclid",
+    "twclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "oly_anon_id",
+    "oly_enc_id",
+    "vero_id",
+];
+
+/// Site specific rules, like those of ClearURLs <https://docs.clearurls.xyz/>
+fn default_url_rules() -> HashMap<String, UrlRules> {
+    let mut rules = HashMap::new();
+    let mut add = |domain: &str, strip_parameters: &[&str], redirects: &[&str]| {
+        rules.insert(
+            domain.to_string(),
+            UrlRules::new(strip_parameters, redirects),
+        );
+    };
+
+    add(
+        "google.com",
+        &[
+            "ved", "ei", "sa", "usg", "sclient", "gs_lcp", "aqs", "sourceid", "uact", "oq",
+        ],
+        &["/url?q", "/url?url"],
+    );
+    add(
+        "facebook.com",
+        &["__tn__", "__cft__*", "mibextid"],
+        &["/l.php?u"],
+    );
+    add("instagram.com", &["igsh"], &["/?u"]);
+    add("youtube.com", &["feature", "si", "pp"], &["/redirect?q"]);
+    add("youtu.be", &["feature", "si"], &[]);
+    add(
+        "amazon.com",
+        &[
+            "pd_rd_*",
+            "pf_rd_*",
+            "ref_",
+            "_encoding",
+            "content-id",
+            "crid",
+            "sprefix",
+        ],
+        &[],
+    );
+    add("twitter.com", &["s", "t", "ref_src"], &[]);
+    add("x.com", &["s", "t", "ref_src"], &[]);
+    add("reddit.com", &["share_id", "ref_source"], &[]);
+    add("out.reddit.com", &[], &["/*?url"]);
+    add("steamcommunity.com", &[], &["/linkfilter/?url"]);
+    add("slack-redir.net", &[], &["/link?url"]);
+
+    rules
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let mut profiles = HashMap::new();
+        profiles.insert(String::from("default"), HeaderProfile::default_profile());
+        profiles.insert(String::from("uniform"), HeaderProfile::uniform());
+
+        Self {
+            https_only: false,
+            refresh_banner: false,
+            pause_cross_site_redirects: false,
+            max_redirects: 10,
+            profile: String::from("default"),
+            profiles,
+            proxy: None,
+            // port of the Tor daemon, onion addresses can't be resolved by anyone else
+            onion_proxy: Some(String::from("socks5h://127.0.0.1:9050")),
+            sites: HashMap::new(),
+            search: String::from("duckduckgo"),
+            engines: vec![SearchEngine {
+                name: String::from("duckduckgo"),
+                url: String::from("https://duckduckgo.com/lite/"),
+                post: Some(String::from("q={searchTerms}&kl=us-en")),
+                keyword: Some(String::from("d")),
+            }],
+            clean_urls: true,
+            strip_parameters: TRACKING_PARAMETERS.iter().map(|p| p.to_string()).collect(),
+            url_rules: default_url_rules(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file, defaults if there is none.
+    pub fn load(path: &str) -> Self {
+        fs::read_to_string(path)
+            .map(|source| Self::parse(&source))
+            .unwrap_or_default()
+    }
+
+    /// Parses a config, unknown keys are ignored.
+    /// Profiles start out as the default profile, or the built-in one of the same name.
+    pub fn parse(source: &str) -> Self {
+        let mut config = Self::default();
+        for (section, key, value) in entries(source) {
+            let (kind, name) = section.split_once(' ').unwrap_or((&section, ""));
+            match (kind, key.as_str()) {
+                ("", "https_only") => config.https_only = value == "true",
+                ("", "refresh_banner") => config.refresh_banner = value == "true",
+                ("", "pause_cross_site_redirects") => {
+                    config.pause_cross_site_redirects = value == "true"
+                }
+                ("", "max_redirects") => {
+                    if let Ok(max_redirects) = value.parse() {
+                        config.max_redirects = max_redirects;
+                    }
+                }
+                ("", "profile") => config.profile = value,
+                ("", "proxy") => config.proxy = proxy(value),
+                ("", "onion_proxy") => config.onion_proxy = proxy(value),
+                ("", "search") => config.search = value,
+                ("", "clean_urls") => config.clean_urls = value == "true",
+                ("", "strip_parameters") => config.strip_parameters.extend(list(&value)),
+                ("profile", _) => {
+                    let profile = config
+                        .profiles
+                        .entry(name.trim().to_string())
+                        .or_insert_with(HeaderProfile::default_profile);
+                    match key.as_str() {
+                        "user_agent" => profile.user_agent = value,
+                        "accept" => profile.accept = value,
+                        "languages" => {
+                            profile.languages = value
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|language| !language.is_empty())
+                                .map(str::to_string)
+                                .collect()
+                        }
+                        "dnt" => profile.dnt = value == "true",
+                        "gpc" => profile.gpc = value == "true",
+                        _ => {}
+                    }
+                }
+                ("search", _) => {
+                    let name = name.trim();
+                    let position =
+                        match config.engines.iter().position(|engine| engine.name == name) {
+                            Some(position) => position,
+                            None => {
+                                config.engines.push(SearchEngine {
+                                    name: name.to_string(),
+                                    url: String::new(),
+                                    post: None,
+                                    keyword: None,
+                                });
+                                config.engines.len() - 1
+                            }
+                        };
+                    let engine = &mut config.engines[position];
+                    match key.as_str() {
+                        "url" => engine.url = value,
+                        "post" => engine.post = Some(value).filter(|post| !post.is_empty()),
+                        "keyword" => {
+                            engine.keyword = Some(value).filter(|keyword| !keyword.is_empty())
+                        }
+                        _ => {}
+                    }
+                }
+                ("clean", _) => {
+                    let rules = config
+                        .url_rules
+                        .entry(name.trim().to_ascii_lowercase())
+                        .or_default();
+                    match key.as_str() {
+                        "strip_parameters" => rules.add_parameters(&value),
+                        "redirect" => rules.add_redirects(&value),
+                        _ => {}
+                    }
+                }
+                ("site", _) => {
+                    let site = config
+                        .sites
+                        .entry(name.trim().to_ascii_lowercase())
+                        .or_default();
+                    match key.as_str() {
+                        "profile" => site.profile = Some(value),
+                        "proxy" => site.proxy = Some(proxy(value)),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        config
+    }
+
+    /// Header profile for requests made for a page on `host`.
+    /// Overrides for a domain apply to its subdomains, the most specific one wins.
+    pub fn header_profile(&self, host: &str) -> &HeaderProfile {
+        let name = self
+            .site_setting(host, |site| site.profile.as_ref())
+            .unwrap_or(&self.profile);
+
+        self.profiles
+            .get(name)
+            .or_else(|| self.profiles.get("default"))
+            .expect("default profile to exist")
+    }
+
+    /// Search engine and search terms for text typed into the url bar.
+    /// A leading keyword chooses the engine, the default one is used otherwise.
+    pub fn search_engine<'a>(&self, input: &'a str) -> (&SearchEngine, &'a str) {
+        let engines = self.engines.iter().filter(|engine| !engine.url.is_empty());
+
+        if let Some((keyword, terms)) = input.trim().split_once(' ') {
+            let engine = engines
+                .clone()
+                .find(|engine| engine.keyword.as_deref() == Some(keyword));
+            if let Some(engine) = engine {
+                return (engine, terms.trim());
+            }
+        }
+
+        let engine = engines
+            .clone()
+            .find(|engine| engine.name == self.search)
+            .or_else(|| engines.clone().next())
+            .unwrap_or(&self.engines[0]);
+        (engine, input.trim())
+    }
+
+    /// Proxy to connect to `host` through, `None` to connect directly.
+    /// Errors on proxies that can't be used, those requests must not go out directly.
+    pub fn proxy(&self, host: &str) -> Result<Option<Url>, String> {
+        let host = host.to_ascii_lowercase();
+        let proxy = if host.ends_with(".onion") {
+            match &self.onion_proxy {
+                Some(proxy) => Some(proxy),
+                None => return Err(format!("No proxy for onion address {}", host)),
+            }
+        } else {
+            self.site_setting(&host, |site| site.proxy.as_ref())
+                .unwrap_or(&self.proxy)
+                .as_ref()
+        };
+
+        let proxy = match proxy {
+            Some(proxy) => proxy,
+            None => return Ok(None),
+        };
+        let url = Url::parse(proxy).map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?;
+        match url.scheme() {
+            "http" | "https" | "socks5" | "socks5h" if url.host().is_some() => Ok(Some(url)),
+            _ => Err(format!("Unsupported proxy {}", proxy)),
+        }
+    }
+
+    /// Url cleaning rules of every `[clean]` section for `host`.
+    pub fn url_rules(&self, host: &str) -> Vec<&UrlRules> {
+        let host = host.to_ascii_lowercase();
+        self.url_rules
+            .iter()
+            .filter(|(domain, _)| on_domain(&host, domain))
+            .map(|(_, rules)| rules)
+            .collect()
+    }
+
+    /// Setting of the most specific `[site]` section for `host` that has it.
+    fn site_setting<'a, T>(
+        &'a self,
+        host: &str,
+        setting: impl Fn(&'a SiteSettings) -> Option<&'a T>,
+    ) -> Option<&'a T> {
+        let host = host.to_ascii_lowercase();
+        self.sites
+            .iter()
+            .filter(|(domain, _)| on_domain(&host, domain))
+            .filter_map(|(domain, site)| Some((domain.len(), setting(site)?)))
+            .max_by_key(|(length, _)| *length)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Is the lowercase `host` the domain or one of its subdomains?
+fn on_domain(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// Comma separated values, without empty ones.
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Proxy setting, `direct` and empty values mean no proxy.
+fn proxy(value: String) -> Option<String> {
+    if value.is_empty() || value == "direct" {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// `(section, key, value)` of every `key = value` line,
+/// the section is empty before the first `[section]`.
+fn entries(source: &str) -> Vec<(String, String, String)> {
+    let mut section = String::new();
+    let mut entries = Vec::new();
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            entries.push((
+                section.clone(),
+                key.trim().to_string(),
+                value.trim().to_string(),
+            ));
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod settings {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        assert_eq!(
+            entries("# comment\na = 1\n\n[profile uniform]\nuser_agent = Mozilla/5.0 (X11)\n"),
+            vec![
+                (String::from(""), String::from("a"), String::from("1")),
+                (
+                    String::from("profile uniform"),
+                    String::from("user_agent"),
+                    String::from("Mozilla/5.0 (X11)")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn https_only() {
+        assert!(Config::parse("https_only = true").https_only);
+        assert!(!Config::parse("https_only = false").https_only);
+        assert!(!Config::parse("[other]\nhttps_only = true").https_only);
+        assert_eq!(Config::parse("unknown = 1"), Config::default());
+    }
+
+    #[test]
+    fn refresh_banner() {
+        assert!(Config::parse("refresh_banner = true").refresh_banner);
+        assert!(!Config::default().refresh_banner);
+    }
+
+    #[test]
+    fn max_redirects() {
+        assert_eq!(Config::parse("max_redirects = 3").max_redirects, 3);
+        assert_eq!(Config::parse("max_redirects = many").max_redirects, 10);
+    }
+
+    #[test]
+    fn header_profiles() {
+        let config = Config::parse(
+            "profile = uniform\n[profile work]\nlanguages = de-DE, de, en\ngpc = true\n[site example.com]\nprofile = work\n[site other.org]\nprofile = missing",
+        );
+
+        assert_eq!(
+            config.header_profile("unknown.net"),
+            &HeaderProfile::uniform()
+        );
+        let work = config.header_profile("www.Example.com");
+        assert_eq!(work.user_agent, HeaderProfile::default_profile().user_agent);
+        assert_eq!(work.accept_language(), "de-DE,de;q=0.7,en;q=0.3");
+        assert!(work.dnt && work.gpc);
+        assert_eq!(
+            config.header_profile("notexample.com"),
+            &HeaderProfile::uniform()
+        );
+        assert_eq!(
+            config.header_profile("other.org"),
+            &HeaderProfile::default_profile()
+        );
+    }
+
+    #[test]
+    fn proxies() {
+        let config = Config::parse(
+            "proxy = socks5h://127.0.0.1:1080\n[site example.com]\nproxy = direct\n[site work.example.com]\nproxy = http://proxy.corp:3128\n[site bad.org]\nproxy = ftp://127.0.0.1",
+        );
+
+        let proxy = |host| config.proxy(host).map(|proxy| proxy.map(String::from));
+        assert_eq!(
+            proxy("other.net"),
+            Ok(Some(String::from("socks5h://127.0.0.1:1080")))
+        );
+        assert_eq!(proxy("www.example.com"), Ok(None));
+        assert_eq!(
+            proxy("a.work.example.com"),
+            Ok(Some(String::from("http://proxy.corp:3128/")))
+        );
+        assert!(proxy("bad.org").is_err());
+        assert_eq!(
+            proxy("abcdefghijklmnop.onion"),
+            Ok(Some(String::from("socks5h://127.0.0.1:9050")))
+        );
+
+        let config = Config::parse("onion_proxy = direct");
+        assert!(config.proxy("abcdefghijklmnop.onion").is_err());
+        assert_eq!(config.proxy("example.com"), Ok(None));
+    }
+
+    #[test]
+    fn search_engines() {
+        let config = Config::parse(
+            "search = wikipedia\n[search wikipedia]\nurl = https://en.wikipedia.org/w/index.php?search={searchTerms}\nkeyword = w\n[search broken]\nkeyword = b",
+        );
+
+        let (engine, terms) = config.search_engine("rust borrow checker");
+        assert_eq!(
+            (engine.name.as_str(), terms),
+            ("wikipedia", "rust borrow checker")
+        );
+        let (engine, terms) = config.search_engine("d  rust");
+        assert_eq!((engine.name.as_str(), terms), ("duckduckgo", "rust"));
+        let (engine, terms) = config.search_engine("b rust");
+        assert_eq!((engine.name.as_str(), terms), ("wikipedia", "b rust"));
+
+        let config = Config::parse("search = missing");
+        assert_eq!(config.search_engine("w rust").0.name, "duckduckgo");
+    }
+
+    #[test]
+    fn search_engine_section() {
+        let engine = SearchEngine {
+            name: String::from("example"),
+            url: String::from("https://example.com/search"),
+            post: Some(String::from("q={searchTerms}")),
+            keyword: Some(String::from("e")),
+        };
+        assert_eq!(Config::parse(&engine.to_config()).engines[1], engine);
+    }
+
+    #[test]
+    fn url_cleaning() {
+        let config = Config::parse(
+            "strip_parameters = ref, campaign_*
+[clean example.com]
+strip_parameters = src
+redirect = /out?to, /broken
+[clean google.com]
+redirect = /goto?target",
+        );
+
+        assert!(config.clean_urls);
+        assert!(!Config::parse("clean_urls = false").clean_urls);
+        assert_eq!(
+            config.strip_parameters[TRACKING_PARAMETERS.len()..],
+            ["ref", "campaign_*"]
+        );
+        assert_eq!(
+            config.url_rules("www.Example.com"),
+            vec![&UrlRules {
+                strip_parameters: vec![String::from("src")],
+                redirects: vec![(String::from("/out"), String::from("to"))],
+            }]
+        );
+        let google = config.url_rules("www.google.com")[0];
+        assert_eq!(google.redirects.len(), 3);
+        assert!(google.strip_parameters.contains(&String::from("ved")));
+        assert!(config.url_rules("notgoogle.com").is_empty());
+    }
+
+    #[test]
+    fn accept_language() {
+        assert_eq!(
+            HeaderProfile::default_profile().accept_language(),
+            "en-us,en;q=0.5"
+        );
+        assert_eq!(HeaderProfile::uniform().accept_language(), "en-US,en;q=0.5");
+    }
+}
@@ -3,6 +3,8 @@ pub mod parser;
 
 use crate::layout;
 
+use reqwest::Url;
+
 #[derive(Debug)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
@@ -155,7 +157,7 @@ impl Clamp for f32 {
 }
 
 /// Parse a whole CSS stylesheet.
-pub fn parse(source: String, url: String, dimensions: (u32, u32)) -> Stylesheet {
+pub fn parse(source: String, url: Option<Url>, dimensions: (u32, u32)) -> Stylesheet {
     let mut parser = parser::Parser {
         pos: 0,
         input: source,
@@ -180,7 +180,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("#17977623"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(
@@ -199,7 +199,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("#afb033"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(
@@ -218,7 +218,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("#8c84"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(
@@ -237,7 +237,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("#c77"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(
@@ -256,13 +256,13 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("#0f3"),
-            url: String::new(),
+            url: None,
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("#00ff33ff"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser1.parse_hex_color(), parser2.parse_hex_color(),);
@@ -273,13 +273,13 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("#2f08"),
-            url: String::new(),
+            url: None,
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("#22ff0088"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser1.parse_hex_color(), parser2.parse_hex_color(),);
@@ -290,13 +290,13 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("#904"),
-            url: String::new(),
+            url: None,
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("#990044"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser1.parse_hex_color(), parser2.parse_hex_color(),);
@@ -307,7 +307,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("hallo"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser.parse_hex_color(), None);
@@ -318,7 +318,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("(0,0%,93.3%"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser.parse_hsl(), (238, 238, 238));
@@ -177,7 +177,9 @@ impl Parser {
                         }
                         self.consume_char(); // )
 
-                        Some(Value::Url(logic::absolute_path(&self.url, &url)))
+                        let url = logic::resolve(self.url.as_ref(), &url)
+                            .map_or(url, |url| url.to_string());
+                        Some(Value::Url(url))
                     }
                     "var" => {
                         // FIXME: reads backup value, but not actual variable
@@ -344,7 +346,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("    	       /*  ad as d */    	a    "),
-            url: String::new(),
+            url: None,
         };
 
         parser.consume_blank();
@@ -357,7 +359,7 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("hallo"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser.parse_unit(), Unit::Zero);
@@ -368,13 +370,13 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("px"),
-            url: String::new(),
+            url: None,
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("rEM"),
-            url: String::new(),
+            url: None,
         };
 
         assert_eq!(parser1.parse_unit(), Unit::Px);
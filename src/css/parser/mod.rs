@@ -5,10 +5,13 @@ use crate::css::{
     media_query, valid_identifier_char, ChainedSelector, Declaration, Rule, SimpleSelector, Value,
 };
 
+use reqwest::Url;
+
 pub struct Parser {
     pub input: String,
     pub pos: usize,
-    pub url: String,
+    /// url of the stylesheet, relative urls are resolved against it
+    pub url: Option<Url>,
 }
 
 impl Parser {
@@ -469,7 +472,7 @@ mod rules {
         let mut parser = Parser {
             pos: 0,
             input: String::from("@import url('bluish.css') speech;"),
-            url: String::new(),
+            url: None,
         };
         assert_eq!(parser.parse_rules((0, 0)).len(), 0);
         assert_eq!(parser.pos, 33);
@@ -480,14 +483,14 @@ mod rules {
         let mut parser = Parser {
             pos: 0,
             input: String::from("@media screen {.b {color:red}}.a{color: blue}"),
-            url: String::new(),
+            url: None,
         };
         assert_eq!(parser.parse_rules((0, 0)).len(), 2);
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("@media print {.b {color:red}}.a{color: blue}"),
-            url: String::new(),
+            url: None,
         };
         assert_eq!(parser2.parse_rules((0, 0)).len(), 1);
     }
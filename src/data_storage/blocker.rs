@@ -0,0 +1,675 @@
+//! Content blocker for Adblock Plus / EasyList filter lists
+//! <https://help.eyeo.com/en/adblockplus/how-to-write-filters>
+
+use super::cookies::site;
+
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fs;
+use std::iter;
+
+/// What a request is for, type options like `$image` are matched against it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Document,
+    Stylesheet,
+    Image,
+    Other,
+}
+
+/// Network rules and element hiding rules of all loaded filter lists
+#[derive(Debug, Default)]
+pub struct Blocker {
+    blocking: NetworkRules,
+    /// `@@` rules
+    exceptions: NetworkRules,
+    hiding: Vec<HidingRule>,
+    /// `#@#` rules
+    hiding_exceptions: Vec<HidingRule>,
+}
+
+/// Network rules, `||` rules indexed by the domain they are limited to
+#[derive(Debug, Default)]
+struct NetworkRules {
+    by_domain: HashMap<String, Vec<NetworkRule>>,
+    other: Vec<NetworkRule>,
+}
+
+/// A request as network rules see it, lowercased once for all of them
+struct Request<'a> {
+    url: &'a Url,
+    /// `url` in lowercase
+    text: String,
+    host: String,
+    top_level: Option<&'a Url>,
+    /// lowercase host of `top_level`
+    page_host: String,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq)]
+struct NetworkRule {
+    /// pattern without anchors, `*` wildcards and `^` separators
+    pattern: String,
+    anchor: Anchor,
+    /// `|` at the end: the pattern has to match up to the end of the url
+    end_anchor: bool,
+    /// `$third-party` → `Some(true)`, `$~third-party` → `Some(false)`
+    third_party: Option<bool>,
+    /// matched types, `None` for all
+    kinds: Option<Vec<Kind>>,
+    /// `$~image` and similar
+    excluded_kinds: Vec<Kind>,
+    /// `$domain=` of the page, excluded ones start with `~`
+    domains: Vec<String>,
+    match_case: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Anchor {
+    None,
+    /// `|`: start of the url
+    Start,
+    /// `||`: start of the host or of one of its subdomains
+    Domain,
+}
+
+#[derive(Debug, PartialEq)]
+struct HidingRule {
+    /// empty for generic rules, excluded ones start with `~`
+    domains: Vec<String>,
+    selector: String,
+}
+
+impl Blocker {
+    /// Loads all filter lists in a directory.
+    pub fn load(dir: &str) -> Self {
+        let mut blocker = Self::default();
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.filter_map(Result::ok) {
+                if let Ok(list) = fs::read_to_string(entry.path()) {
+                    blocker.add_list(&list);
+                }
+            }
+        }
+        blocker
+    }
+
+    /// Adds the rules of a filter list, skipping comments and unsupported rules.
+    pub fn add_list(&mut self, list: &str) {
+        for line in list.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+                continue;
+            }
+
+            if let Some(position) = line.find("#@#") {
+                self.hiding_exceptions
+                    .push(HidingRule::parse(&line[..position], &line[position + 3..]));
+            } else if let Some(position) = line.find("##") {
+                self.hiding
+                    .push(HidingRule::parse(&line[..position], &line[position + 2..]));
+            } else if line.contains("#?#") || line.contains("#$#") {
+                // extended css and snippets
+                continue;
+            } else if let Some(rule) = line.strip_prefix("@@") {
+                self.exceptions.extend(NetworkRule::parse(rule));
+            } else {
+                self.blocking.extend(NetworkRule::parse(line));
+            }
+        }
+    }
+
+    /// Should a request for `url`, made by the page `top_level`, be blocked?
+    pub fn blocks(&self, url: &Url, top_level: Option<&Url>, kind: Kind) -> bool {
+        let request = Request {
+            url,
+            text: url.as_str().to_ascii_lowercase(),
+            host: url.host_str().unwrap_or_default().to_ascii_lowercase(),
+            top_level,
+            page_host: top_level
+                .and_then(Url::host_str)
+                .unwrap_or_default()
+                .to_ascii_lowercase(),
+            kind,
+        };
+        self.blocking.any_matches(&request) && !self.exceptions.any_matches(&request)
+    }
+
+    /// Css hiding the elements matched by element hiding rules for the page `url`.
+    pub fn hiding_css(&self, url: &Url) -> String {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+
+        self.hiding
+            .iter()
+            .filter(|rule| rule.applies(&host))
+            .filter(|rule| {
+                !self
+                    .hiding_exceptions
+                    .iter()
+                    .any(|exception| exception.selector == rule.selector && exception.applies(&host))
+            })
+            // one rule per selector, selectors the css parser doesn't understand only drop their own rule
+            .map(|rule| format!("{} {{ display: none }}\n", rule.selector))
+            .collect()
+    }
+}
+
+impl NetworkRules {
+    fn extend(&mut self, rules: impl IntoIterator<Item = NetworkRule>) {
+        for rule in rules {
+            match rule.domain() {
+                Some(domain) => self
+                    .by_domain
+                    .entry(domain.to_ascii_lowercase())
+                    .or_default()
+                    .push(rule),
+                None => self.other.push(rule),
+            }
+        }
+    }
+
+    /// Only the `||` rules of the host and its parent domains can match.
+    fn any_matches(&self, request: &Request) -> bool {
+        iter::successors(Some(request.host.as_str()), |host| {
+            host.split_once('.').map(|(_, parent)| parent)
+        })
+        .filter_map(|domain| self.by_domain.get(domain))
+        .flatten()
+        .chain(&self.other)
+        .any(|rule| rule.matches(request))
+    }
+
+    #[cfg(test)]
+    fn is_empty(&self) -> bool {
+        self.by_domain.is_empty() && self.other.is_empty()
+    }
+}
+
+impl NetworkRule {
+    /// `||ads.example.com^$third-party,image`
+    /// Returns `None` for regular expressions and unsupported options.
+    fn parse(rule: &str) -> Option<Self> {
+        let (mut pattern, options) = match rule.rfind('$') {
+            Some(position) => (&rule[..position], &rule[position + 1..]),
+            None => (rule, ""),
+        };
+        if pattern.starts_with('/') && pattern.ends_with('/') && pattern.len() > 1 {
+            return None;
+        }
+
+        let anchor = if let Some(rest) = pattern.strip_prefix("||") {
+            pattern = rest;
+            Anchor::Domain
+        } else if let Some(rest) = pattern.strip_prefix('|') {
+            pattern = rest;
+            Anchor::Start
+        } else {
+            Anchor::None
+        };
+        let end_anchor = pattern.ends_with('|');
+        let pattern = pattern.trim_end_matches('|');
+
+        let mut parsed = Self {
+            pattern: pattern.to_string(),
+            anchor,
+            end_anchor,
+            third_party: None,
+            kinds: None,
+            excluded_kinds: Vec::new(),
+            domains: Vec::new(),
+            match_case: false,
+        };
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (negated, name) = match option.strip_prefix('~') {
+                Some(name) => (true, name),
+                None => (false, option),
+            };
+            match name {
+                "third-party" | "3p" => parsed.third_party = Some(!negated),
+                "first-party" | "1p" => parsed.third_party = Some(negated),
+                "match-case" => parsed.match_case = true,
+                _ if name.starts_with("domain=") => {
+                    parsed.domains = name[7..].split('|').map(str::to_ascii_lowercase).collect();
+                }
+                _ => {
+                    // type options, types that are never requested match nothing
+                    let kind = match name {
+                        "document" => None,
+                        "stylesheet" => Some(Kind::Stylesheet),
+                        "image" => Some(Kind::Image),
+                        "other" => Some(Kind::Other),
+                        "script" | "xmlhttprequest" | "subdocument" | "object" | "media"
+                        | "font" | "websocket" | "ping" | "popup" => None,
+                        // options changing what happens on a match, like `redirect=`
+                        _ => return None,
+                    };
+                    // documents aren't blocked
+                    let kind = kind.filter(|_| name != "document");
+                    if negated {
+                        parsed.excluded_kinds.extend(kind);
+                    } else {
+                        parsed.kinds.get_or_insert_with(Vec::new).extend(kind);
+                    }
+                }
+            }
+        }
+
+        if !parsed.match_case {
+            parsed.pattern = parsed.pattern.to_ascii_lowercase();
+        }
+        // only types this browser never requests
+        if parsed.kinds.as_ref().is_some_and(Vec::is_empty) {
+            return None;
+        }
+
+        Some(parsed)
+    }
+
+    /// Domain of a `||` rule whose pattern ends the host with a separator,
+    /// it only matches requests to the domain and its subdomains.
+    fn domain(&self) -> Option<&str> {
+        if self.anchor != Anchor::Domain {
+            return None;
+        }
+        let end = self
+            .pattern
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '.'))?;
+        let domain = &self.pattern[..end];
+        (!domain.is_empty()
+            && !domain.ends_with('.')
+            && matches!(self.pattern.as_bytes()[end], b'^' | b'/' | b':'))
+        .then_some(domain)
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        let kind = request.kind;
+        if kind == Kind::Document
+            || self
+                .kinds
+                .as_ref()
+                .is_some_and(|kinds| !kinds.contains(&kind))
+            || self.excluded_kinds.contains(&kind)
+        {
+            return false;
+        }
+
+        if let Some(third_party) = self.third_party {
+            match request.top_level {
+                Some(top_level) if (site(request.url) != site(top_level)) == third_party => {}
+                _ => return false,
+            }
+        }
+
+        if !self.domains.is_empty() && !domains_match(&self.domains, &request.page_host) {
+            return false;
+        }
+
+        let text = if self.match_case {
+            request.url.as_str()
+        } else {
+            &request.text
+        };
+        let text = text.as_bytes();
+        let pattern = self.pattern.as_bytes();
+
+        match self.anchor {
+            Anchor::Start => glob(pattern, text, self.end_anchor),
+            Anchor::Domain => {
+                let host = request.host.as_bytes();
+                let host_start = match text
+                    .windows(host.len().max(1))
+                    .position(|window| window == host)
+                {
+                    Some(start) => start,
+                    None => return false,
+                };
+                // the host itself and every subdomain boundary
+                (host_start..host_start + host.len())
+                    .filter(|&i| i == host_start || text[i - 1] == b'.')
+                    .any(|i| glob(pattern, &text[i..], self.end_anchor))
+            }
+            Anchor::None => (0..=text.len()).any(|i| glob(pattern, &text[i..], self.end_anchor)),
+        }
+    }
+}
+
+impl HidingRule {
+    /// `example.com,~ads.example.com` and `.banner`
+    fn parse(domains: &str, selector: &str) -> Self {
+        Self {
+            domains: domains
+                .split(',')
+                .filter(|domain| !domain.is_empty())
+                .map(str::to_ascii_lowercase)
+                .collect(),
+            selector: selector.trim().to_string(),
+        }
+    }
+
+    fn applies(&self, host: &str) -> bool {
+        self.domains.is_empty() || domains_match(&self.domains, host)
+    }
+}
+
+/// Matches a `domain=` list: the most specific listed domain decides,
+/// a list with only excluded domains includes every other domain.
+fn domains_match(domains: &[String], host: &str) -> bool {
+    let mut best: Option<(usize, bool)> = None;
+    for domain in domains {
+        let (included, domain) = match domain.strip_prefix('~') {
+            Some(domain) => (false, domain),
+            None => (true, domain.as_str()),
+        };
+        if is_subdomain(host, domain) && best.is_none_or(|(length, _)| domain.len() > length) {
+            best = Some((domain.len(), included));
+        }
+    }
+
+    match best {
+        Some((_, included)) => included,
+        None => domains.iter().all(|domain| domain.starts_with('~')),
+    }
+}
+
+/// Is `host` the `domain` or one of its subdomains?
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(domain) && host[..host.len() - domain.len()].ends_with('.'))
+}
+
+/// Matches `text` from its start against a pattern with `*` and `^`.
+fn glob(pattern: &[u8], text: &[u8], end_anchor: bool) -> bool {
+    match pattern.split_first() {
+        None => !end_anchor || text.is_empty(),
+        Some((b'*', rest)) => {
+            let rest = &rest[rest.iter().take_while(|&&c| c == b'*').count()..];
+            (0..=text.len()).any(|i| glob(rest, &text[i..], end_anchor))
+        }
+        // a separator or the end of the url
+        Some((b'^', rest)) => match text.split_first() {
+            None => glob(rest, text, end_anchor),
+            Some((c, text)) => is_separator(*c) && glob(rest, text, end_anchor),
+        },
+        Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..], end_anchor),
+    }
+}
+
+/// Anything but letters, digits and `_-.%`
+fn is_separator(c: u8) -> bool {
+    !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'%'))
+}
+
+#[cfg(test)]
+mod filters {
+    use super::*;
+
+    fn blocker(list: &str) -> Blocker {
+        let mut blocker = Blocker::default();
+        blocker.add_list(list);
+        blocker
+    }
+
+    const PAGE: Option<&str> = Some("https://news.example.com/article");
+
+    /// `Blocker::blocks` with the urls parsed
+    fn blocks(blocker: &Blocker, url: &str, top_level: Option<&str>, kind: Kind) -> bool {
+        let top_level = top_level.map(|page| Url::parse(page).unwrap());
+        blocker.blocks(&Url::parse(url).unwrap(), top_level.as_ref(), kind)
+    }
+
+    #[test]
+    fn domain_anchor() {
+        let blocker = blocker("||ads.tracker.net^");
+
+        assert!(blocks(
+            &blocker,
+            "https://ads.tracker.net/banner.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "http://cdn.ads.tracker.net/a.css",
+            PAGE,
+            Kind::Stylesheet
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://badads.tracker.net/",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://ads.tracker.network/",
+            PAGE,
+            Kind::Image
+        ));
+    }
+
+    #[test]
+    fn indexed_domains() {
+        let blocker =
+            blocker("||tracker.net/pixel\n||ads.trac*^\n||cdn.example\n||example.org:8080^");
+        assert_eq!(blocker.blocking.by_domain.len(), 2);
+
+        assert!(blocks(
+            &blocker,
+            "https://a.b.tracker.net/pixel.gif",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://tracker.network/pixel.gif",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://ads.tracking.org/",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://cdn.example.com/a.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "http://EXAMPLE.org:8080/",
+            PAGE,
+            Kind::Other
+        ));
+    }
+
+    #[test]
+    fn wildcards_and_anchors() {
+        let blocker = blocker("/banner/*/img^\n|https://start.example/\nswf|");
+
+        assert!(blocks(
+            &blocker,
+            "https://a.example/banner/foo/img?x=1",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://a.example/banner/foo/img",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://a.example/banner/foo/imgs",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://start.example/x",
+            PAGE,
+            Kind::Other
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://other.example/https://start.example/",
+            PAGE,
+            Kind::Other
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://a.example/movie.swf",
+            PAGE,
+            Kind::Other
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://a.example/movie.swf?x",
+            PAGE,
+            Kind::Other
+        ));
+    }
+
+    #[test]
+    fn third_party() {
+        let blocker = blocker("||cdn.example.org^$third-party");
+
+        assert!(blocks(
+            &blocker,
+            "https://cdn.example.org/a.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://cdn.example.org/a.png",
+            Some("https://www.example.org/"),
+            Kind::Image
+        ));
+    }
+
+    #[test]
+    fn types() {
+        let blocker =
+            blocker("/ads/*$image\n/style/*$stylesheet\n/other/*$~image\n/scripts/*$script");
+
+        assert!(blocks(
+            &blocker,
+            "https://a.example/ads/1.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://a.example/ads/1.css",
+            PAGE,
+            Kind::Stylesheet
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://a.example/style/1.css",
+            PAGE,
+            Kind::Stylesheet
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://a.example/other/1.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(blocks(
+            &blocker,
+            "https://a.example/other/1.css",
+            PAGE,
+            Kind::Stylesheet
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://a.example/scripts/1.png",
+            PAGE,
+            Kind::Image
+        ));
+    }
+
+    #[test]
+    fn exceptions_and_documents() {
+        let blocker = blocker("||ads.example.com^\n@@||ads.example.com/allowed/");
+
+        assert!(blocks(
+            &blocker,
+            "https://ads.example.com/x.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://ads.example.com/allowed/x.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://ads.example.com/",
+            None,
+            Kind::Document
+        ));
+    }
+
+    #[test]
+    fn page_domains() {
+        let blocker = blocker("/promo.$domain=example.com|~shop.example.com");
+
+        assert!(blocks(
+            &blocker,
+            "https://cdn.net/promo.png",
+            PAGE,
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://cdn.net/promo.png",
+            Some("https://shop.example.com/"),
+            Kind::Image
+        ));
+        assert!(!blocks(
+            &blocker,
+            "https://cdn.net/promo.png",
+            Some("https://a.org/"),
+            Kind::Image
+        ));
+    }
+
+    #[test]
+    fn unsupported_rules_skipped() {
+        let blocker = blocker(
+            "! comment\n[Adblock Plus 2.0]\n/^https?:\\/\\/ads/\n||a.example^$redirect=noop.js\nexample.com#?#div:has(> .ad)",
+        );
+
+        assert!(blocker.blocking.is_empty());
+        assert!(blocker.hiding.is_empty());
+    }
+
+    #[test]
+    fn element_hiding() {
+        let blocker = blocker(
+            "##.ad-banner\nexample.com##.sponsored\n~shop.example.com##.promo\nnews.example.com#@#.ad-banner",
+        );
+
+        assert_eq!(
+            blocker.hiding_css(&Url::parse("https://www.example.com/").unwrap()),
+            ".ad-banner { display: none }\n.sponsored { display: none }\n.promo { display: none }\n"
+        );
+        assert_eq!(
+            blocker.hiding_css(&Url::parse("https://news.example.com/").unwrap()),
+            ".sponsored { display: none }\n.promo { display: none }\n"
+        );
+        assert_eq!(
+            blocker.hiding_css(&Url::parse("https://shop.example.com/").unwrap()),
+            ".ad-banner { display: none }\n.sponsored { display: none }\n"
+        );
+    }
+}
@@ -0,0 +1,1207 @@
+//! HTTP cache
+//! <https://tools.ietf.org/html/rfc7234>
+
+use super::date;
+use super::download;
+use super::downloads;
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// upper bound for heuristic freshness: a week
+const MAX_HEURISTIC_LIFETIME: u64 = 604_800;
+const INDEX_FILE: &str = "index";
+/// first line of the index, to recognise its format
+const INDEX_HEADER: &str = "prowser cache index v1";
+/// numbers temporary files, so concurrent writes don't share one
+static TEMPORARY: AtomicUsize = AtomicUsize::new(0);
+
+/// A cached response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// url of the response, the end of `redirects`
+    pub url: Url,
+    /// relative file system path of the body
+    pub path: String,
+    /// body size in bytes
+    pub size: u64,
+    pub mime_type: String,
+    /// time the response was generated, seconds since unix epoch
+    pub stored: u64,
+    /// freshness lifetime in seconds
+    pub lifetime: u64,
+    /// `must-revalidate`: never use the response when stale
+    pub must_revalidate: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// last lookup, seconds since unix epoch
+    pub last_used: u64,
+    /// redirects followed by the request, not kept in the index
+    pub redirects: Vec<download::Redirect>,
+    /// the body is at a `temporary_path` as it may not be cached, the caller removes it once used
+    pub temporary: bool,
+}
+
+impl Entry {
+    /// Tab separated index line, with tabs, newlines and backslashes escaped.
+    fn to_line(&self) -> String {
+        [
+            file_name(&self.path),
+            self.url.as_str(),
+            &self.mime_type,
+            &self.size.to_string(),
+            &self.stored.to_string(),
+            &self.lifetime.to_string(),
+            if self.must_revalidate { "1" } else { "0" },
+            self.etag.as_deref().unwrap_or(""),
+            self.last_modified.as_deref().unwrap_or(""),
+            &self.last_used.to_string(),
+        ]
+        .iter()
+        .map(|cell| escape(cell))
+        .collect::<Vec<_>>()
+        .join("\t")
+    }
+
+    fn from_line(dir: &Path, line: &str) -> Option<Self> {
+        let cells = line.split('\t').map(unescape).collect::<Vec<String>>();
+        if cells.len() != 10 {
+            return None;
+        }
+
+        Some(Self {
+            path: dir.join(&cells[0]).to_string_lossy().into_owned(),
+            url: Url::parse(&cells[1]).ok()?,
+            mime_type: cells[2].clone(),
+            size: cells[3].parse().ok()?,
+            stored: cells[4].parse().ok()?,
+            lifetime: cells[5].parse().ok()?,
+            must_revalidate: cells[6] == "1",
+            etag: Some(cells[7].clone()).filter(|v| !v.is_empty()),
+            last_modified: Some(cells[8].clone()).filter(|v| !v.is_empty()),
+            last_used: cells[9].parse().ok()?,
+            redirects: Vec::new(),
+            temporary: false,
+        })
+    }
+
+    /// Is the entry still fresh at `now`?
+    pub fn is_fresh(&self, now: u64) -> bool {
+        self.stored + self.lifetime > now
+    }
+
+    /// Conditional request headers to revalidate a stale entry.
+    fn validators(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self
+            .etag
+            .as_ref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(modified) = self
+            .last_modified
+            .as_ref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(header::IF_MODIFIED_SINCE, modified);
+        }
+        headers
+    }
+}
+
+/// Parsed `Cache-Control` directives relevant for a private cache
+/// <https://tools.ietf.org/html/rfc7234#section-5.2.2>
+#[derive(Debug, Default, PartialEq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    must_revalidate: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn parse(value: &str) -> Self {
+        let mut cache_control = Self::default();
+
+        for directive in value.split(',') {
+            let mut parts = directive.splitn(2, '=');
+            let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+            let argument = parts.next().map(|v| v.trim().trim_matches('"'));
+
+            match &*name {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "must-revalidate" | "proxy-revalidate" => cache_control.must_revalidate = true,
+                "max-age" => {
+                    // an invalid max-age is treated as stale
+                    cache_control.max_age =
+                        Some(argument.and_then(|v| v.parse::<u64>().ok()).unwrap_or(0));
+                }
+                _ => {}
+            }
+        }
+
+        cache_control
+    }
+
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+
+        for value in headers.get_all(header::CACHE_CONTROL) {
+            if let Ok(value) = value.to_str() {
+                let directives = Self::parse(value);
+                cache_control.no_store |= directives.no_store;
+                cache_control.no_cache |= directives.no_cache;
+                cache_control.must_revalidate |= directives.must_revalidate;
+                cache_control.max_age = cache_control.max_age.or(directives.max_age);
+            }
+        }
+
+        // `Pragma: no-cache` is only honoured without `Cache-Control`
+        if !headers.contains_key(header::CACHE_CONTROL) {
+            if let Some(pragma) = header_str(headers, header::PRAGMA) {
+                cache_control.no_cache = pragma.to_ascii_lowercase().contains("no-cache");
+            }
+        }
+
+        cache_control
+    }
+}
+
+/// Index of all cached responses, by key
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    /// sum of all body sizes
+    size: u64,
+}
+
+/// A size-bounded cache storing responses in a directory.
+/// Least recently used responses are evicted first.
+pub struct Cache {
+    dir: PathBuf,
+    /// upper bound of the total body size in bytes
+    max_size: u64,
+    index: Mutex<Index>,
+    /// serve only cached responses, regardless of expiry
+    offline: AtomicBool,
+    /// offline mode started because the network was down, not by the user:
+    /// stale responses that must be revalidated are still requested
+    offline_automatically: AtomicBool,
+}
+
+impl Cache {
+    /// Opens the cache in `dir`.
+    /// Index entries whose body vanished and files not in the index are removed.
+    pub fn new<P: Into<PathBuf>>(dir: P, max_size: u64) -> Self {
+        let cache = Self {
+            dir: dir.into(),
+            max_size,
+            index: Mutex::new(Index::default()),
+            offline: AtomicBool::new(false),
+            offline_automatically: AtomicBool::new(false),
+        };
+
+        let mut index = Index::default();
+        if let Ok(content) = fs::read_to_string(cache.index_path()) {
+            let mut lines = content.lines();
+            // an index of another format is discarded, its bodies are removed below
+            if lines.next() != Some(INDEX_HEADER) {
+                lines = "".lines();
+            }
+            for line in lines {
+                if let Some(entry) = Entry::from_line(&cache.dir, line) {
+                    if Path::new(&entry.path).is_file() {
+                        index.size += entry.size;
+                        index
+                            .entries
+                            .insert(file_name(&entry.path).to_string(), entry);
+                    }
+                }
+            }
+        }
+
+        // bodies without an index entry, unstored responses or left over by a crash
+        if let Ok(files) = fs::read_dir(&cache.dir) {
+            for file in files.flatten() {
+                let name = file.file_name().to_string_lossy().into_owned();
+                if name != INDEX_FILE && !index.entries.contains_key(&name) {
+                    let _ = fs::remove_file(file.path());
+                }
+            }
+        }
+
+        cache.write_index(&index);
+        *cache.index.lock().unwrap() = index;
+
+        cache
+    }
+
+    /// In offline mode, only cached responses are used and nothing is requested.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+        self.offline_automatically.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_FILE)
+    }
+
+    /// File system path a url is cached at.
+    /// Named after the SHA-256 hash of the url.
+    pub fn path_for(&self, url: &Url) -> String {
+        self.dir.join(key(url)).to_string_lossy().into_owned()
+    }
+
+    /// Unique file system path for a response that isn't cached, like the answer to a POST.
+    /// It is never indexed, if left behind it is removed on the next start.
+    pub fn temporary_path(&self) -> String {
+        let name = format!("{}.tmp", TEMPORARY.fetch_add(1, Ordering::SeqCst));
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+
+    /// Returns the index entry of an url, if its body is still on disk.
+    /// Marks the entry as recently used.
+    pub fn lookup(&self, url: &Url) -> Option<Entry> {
+        let key = key(url);
+        let mut index = self.index.lock().unwrap();
+
+        let entry = index.entries.get_mut(&key)?;
+        if Path::new(&entry.path).is_file() {
+            entry.last_used = now();
+            return Some(entry.clone());
+        }
+
+        // the body vanished
+        if let Some(entry) = index.entries.remove(&key) {
+            index.size -= entry.size;
+        }
+        self.write_index(&index);
+        None
+    }
+
+    /// Adds or replaces an entry and evicts the least recently used entries above `max_size`.
+    fn insert(&self, entry: Entry) {
+        let key = file_name(&entry.path).to_string();
+        let mut index = self.index.lock().unwrap();
+
+        index.size += entry.size;
+        if let Some(old) = index.entries.insert(key.clone(), entry) {
+            index.size -= old.size;
+        }
+
+        while index.size > self.max_size {
+            let oldest = index
+                .entries
+                .iter()
+                .filter(|(k, _)| **k != key)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.clone());
+
+            match oldest.and_then(|oldest| index.entries.remove(&oldest)) {
+                Some(evicted) => {
+                    index.size -= evicted.size;
+                    let _ = fs::remove_file(&evicted.path);
+                }
+                // the new entry alone is too big
+                None => {
+                    if let Some(evicted) = index.entries.remove(&key) {
+                        index.size -= evicted.size;
+                    }
+                    break;
+                }
+            }
+        }
+
+        self.write_index(&index);
+    }
+
+    /// All entries, most recently used first.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = self
+            .index
+            .lock()
+            .unwrap()
+            .entries
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.last_used.cmp(&a.last_used).then(a.url.cmp(&b.url)));
+        entries
+    }
+
+    /// Deletes the entry of an url and its body.
+    pub fn remove(&self, url: &Url) {
+        let mut index = self.index.lock().unwrap();
+        if let Some(entry) = index.entries.remove(&key(url)) {
+            index.size -= entry.size;
+            let _ = fs::remove_file(&entry.path);
+            self.write_index(&index);
+        }
+    }
+
+    /// Deletes every entry and its body.
+    pub fn clear(&self) {
+        let mut index = self.index.lock().unwrap();
+        for entry in index.entries.values() {
+            let _ = fs::remove_file(&entry.path);
+        }
+        *index = Index::default();
+        self.write_index(&index);
+    }
+
+    /// Removes the entry of an url from the index, its body stays on disk until the next start.
+    fn forget(&self, url: &Url) {
+        let mut index = self.index.lock().unwrap();
+        if let Some(entry) = index.entries.remove(&key(url)) {
+            index.size -= entry.size;
+            self.write_index(&index);
+        }
+    }
+
+    /// Writes the index to a temporary file and moves it in place,
+    /// so a crash never leaves a half written index behind.
+    fn write_index(&self, index: &Index) {
+        let mut content = String::from(INDEX_HEADER);
+        content.push('\n');
+        for entry in index.entries.values() {
+            content.push_str(&entry.to_line());
+            content.push('\n');
+        }
+
+        let temporary = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        if fs::write(&temporary, content).is_ok() {
+            let _ = fs::rename(&temporary, self.index_path());
+        }
+    }
+
+    /// Writes a response body next to its final path and moves it in place.
+    fn write_body<R: Read>(&self, url: &Url, body: &mut R) -> Result<(String, u64), String> {
+        let path = self.path_for(url);
+        let temporary = format!("{}.{}.part", path, TEMPORARY.fetch_add(1, Ordering::SeqCst));
+
+        let mut out = fs::File::create(&temporary).map_err(|e| e.to_string())?;
+        let size = io::copy(body, &mut out).map_err(|e| e.to_string())?;
+        fs::rename(&temporary, &path).map_err(|e| e.to_string())?;
+
+        Ok((path, size))
+    }
+
+    /// Returns a fresh cache entry of the url.
+    /// Stale entries are revalidated with a conditional request, a `304 Not Modified` reuses the cached body.
+    /// A redirected response is cached under the url it came from.
+    pub fn fetch(&self, url: &Url, top_level: Option<&Url>) -> Result<Entry, String> {
+        self.fetch_as(url, top_level, None)
+    }
+
+    /// Returns a fresh cache entry of a page navigated to.
+    /// Files that aren't pages are streamed to the download directory instead of the cache,
+    /// the error then names the path they are saved to.
+    /// No download is started once `cancelled` is set.
+    pub fn navigate(&self, url: &Url, cancelled: &AtomicBool) -> Result<Entry, String> {
+        self.fetch_as(url, None, Some(cancelled))
+    }
+
+    /// `navigation` is the cancel flag of a page navigated to, `None` for subresources.
+    fn fetch_as(
+        &self,
+        url: &Url,
+        top_level: Option<&Url>,
+        navigation: Option<&AtomicBool>,
+    ) -> Result<Entry, String> {
+        let now = now();
+        let cached = self.lookup(url);
+
+        let validators = match &cached {
+            Some(entry) if entry.is_fresh(now) => return Ok(entry.clone()),
+            // stale responses that must be revalidated are only used if the user went offline
+            // https://tools.ietf.org/html/rfc7234#section-4.2.4
+            Some(entry)
+                if entry.must_revalidate && self.offline_automatically.load(Ordering::SeqCst) =>
+            {
+                entry.validators()
+            }
+            Some(entry) if self.is_offline() => return Ok(entry.clone()),
+            None if self.is_offline() => return Err(download::not_requested(url)),
+            Some(entry) => entry.validators(),
+            None => HeaderMap::new(),
+        };
+
+        let (mut response, redirects) = match download::request(url, top_level, validators) {
+            Ok(response) => response,
+            // the network is down, stay with the cache until the user goes online again
+            Err(error)
+                if download::lookup_failed(&error)
+                    && cached.as_ref().is_some_and(|entry| !entry.must_revalidate) =>
+            {
+                self.set_offline(true);
+                self.offline_automatically.store(true, Ordering::SeqCst);
+                return Ok(cached.expect("entry to be cached"));
+            }
+            // a stale response may be used while the server can't be reached, unless it must be revalidated
+            // https://tools.ietf.org/html/rfc7234#section-4.2.4
+            Err(error) if download::unreachable(&error) => {
+                return match cached {
+                    Some(entry) if !entry.must_revalidate => Ok(entry),
+                    _ => Err(error),
+                };
+            }
+            Err(error) => return Err(error),
+        };
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        if let (StatusCode::NOT_MODIFIED, Some(mut entry)) = (status, cached) {
+            let cache_control = CacheControl::from_headers(&headers);
+            // headers of a 304 update the stored response
+            if cache_control.max_age.is_some() || headers.contains_key(header::EXPIRES) {
+                entry.lifetime = freshness_lifetime(&headers, &cache_control, now);
+            }
+            entry.stored = response_time(&headers, now);
+            entry.must_revalidate |= cache_control.must_revalidate;
+            if let Some(etag) = header_str(&headers, header::ETAG) {
+                entry.etag = Some(etag.to_string());
+            }
+            if let Some(modified) = header_str(&headers, header::LAST_MODIFIED) {
+                entry.last_modified = Some(modified.to_string());
+            }
+
+            entry.last_used = now;
+
+            self.insert(entry.clone());
+            return Ok(entry);
+        }
+
+        let final_url = redirects.last().map_or(url, |redirect| &redirect.to);
+        if let (Some(cancelled), true) = (
+            navigation,
+            status.is_success() && downloads::is_download(&headers),
+        ) {
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(String::from("Cancelled"));
+            }
+            let path = downloads::start_response(final_url, response)?;
+            return Err(downloads::started_error(&path));
+        }
+
+        let cache_control = CacheControl::from_headers(&headers);
+        let storable = status == StatusCode::OK && !cache_control.no_store;
+        // never replaces the cached body of the url
+        let (path, size) = if storable {
+            self.write_body(final_url, &mut response)?
+        } else {
+            let path = self.temporary_path();
+            let mut out = fs::File::create(&path).map_err(|e| e.to_string())?;
+            let size = io::copy(&mut response, &mut out).map_err(|e| e.to_string())?;
+            (path, size)
+        };
+
+        let entry = Entry {
+            url: final_url.clone(),
+            path,
+            size,
+            mime_type: header_str(&headers, header::CONTENT_TYPE)
+                .unwrap_or("")
+                .to_string(),
+            stored: response_time(&headers, now),
+            lifetime: if cache_control.no_cache {
+                0
+            } else {
+                freshness_lifetime(&headers, &cache_control, now)
+            },
+            must_revalidate: cache_control.must_revalidate,
+            etag: header_str(&headers, header::ETAG).map(str::to_string),
+            last_modified: header_str(&headers, header::LAST_MODIFIED).map(str::to_string),
+            last_used: now,
+            redirects: redirects.clone(),
+            temporary: !storable,
+        };
+
+        if storable {
+            self.insert(entry.clone());
+        } else if cache_control.no_store {
+            // a copy stored before mustn't outlive the site asking not to store it
+            self.forget(final_url);
+        }
+        // the requested url redirects now
+        if !redirects.is_empty() {
+            self.forget(url);
+        }
+
+        Ok(entry)
+    }
+}
+
+/// <https://tools.ietf.org/html/rfc7234#section-4.2.1>
+fn freshness_lifetime(headers: &HeaderMap, cache_control: &CacheControl, now: u64) -> u64 {
+    if let Some(max_age) = cache_control.max_age {
+        return max_age;
+    }
+
+    let date = header_str(headers, header::DATE)
+        .and_then(date::parse_http_date)
+        .unwrap_or(now);
+
+    if let Some(expires) = header_str(headers, header::EXPIRES) {
+        // invalid dates, like `0`, mean already expired
+        return date::parse_http_date(expires)
+            .map(|expires| expires.saturating_sub(date))
+            .unwrap_or(0);
+    }
+
+    // heuristic freshness https://tools.ietf.org/html/rfc7234#section-4.2.2
+    if let Some(modified) =
+        header_str(headers, header::LAST_MODIFIED).and_then(date::parse_http_date)
+    {
+        return (date.saturating_sub(modified) / 10).min(MAX_HEURISTIC_LIFETIME);
+    }
+
+    0
+}
+
+/// Time the response was generated, corrected by its `Age`.
+fn response_time(headers: &HeaderMap, now: u64) -> u64 {
+    let age = header_str(headers, header::AGE)
+        .and_then(|age| age.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    now.saturating_sub(age)
+}
+
+/// Cache key of an url: hex encoded SHA-256 hash
+fn key(url: &Url) -> String {
+    Sha256::digest(url.as_str().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn file_name(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(path)
+}
+
+fn escape(cell: &str) -> String {
+    cell.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(cell: &str) -> String {
+    let mut result = String::new();
+    let mut chars = cell.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => result.push('\t'),
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|n| n.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod cache_control {
+    use super::*;
+
+    #[test]
+    fn directives() {
+        assert_eq!(
+            CacheControl::parse("no-cache, Must-Revalidate, max-age=\"60\""),
+            CacheControl {
+                no_store: false,
+                no_cache: true,
+                must_revalidate: true,
+                max_age: Some(60),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_max_age() {
+        assert_eq!(CacheControl::parse("max-age=soon").max_age, Some(0));
+    }
+
+    #[test]
+    fn lifetime_max_age_over_expires() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("max-age=10"),
+        );
+        headers.insert(
+            header::EXPIRES,
+            HeaderValue::from_static("Thu, 01 Jan 2099 00:00:00 GMT"),
+        );
+        let cache_control = CacheControl::from_headers(&headers);
+
+        assert_eq!(freshness_lifetime(&headers, &cache_control, 0), 10);
+    }
+
+    #[test]
+    fn lifetime_expires() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::DATE,
+            HeaderValue::from_static("Sun, 06 Nov 1994 08:49:37 GMT"),
+        );
+        headers.insert(
+            header::EXPIRES,
+            HeaderValue::from_static("Sun, 06 Nov 1994 09:49:37 GMT"),
+        );
+
+        assert_eq!(
+            freshness_lifetime(&headers, &CacheControl::default(), 0),
+            3600
+        );
+
+        headers.insert(header::EXPIRES, HeaderValue::from_static("0"));
+        assert_eq!(freshness_lifetime(&headers, &CacheControl::default(), 0), 0);
+    }
+
+    #[test]
+    fn lifetime_heuristic() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::DATE,
+            HeaderValue::from_static("Sun, 06 Nov 1994 10:00:00 GMT"),
+        );
+        headers.insert(
+            header::LAST_MODIFIED,
+            HeaderValue::from_static("Sun, 06 Nov 1994 00:00:00 GMT"),
+        );
+
+        assert_eq!(
+            freshness_lifetime(&headers, &CacheControl::default(), 0),
+            3600
+        );
+    }
+
+    #[test]
+    fn pragma() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::PRAGMA, HeaderValue::from_static("no-cache"));
+
+        assert!(CacheControl::from_headers(&headers).no_cache);
+    }
+}
+
+#[cfg(test)]
+mod fetch {
+    use super::*;
+    use crate::data_storage::test_server::TestServer;
+
+    fn cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("prowser-cache-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Cache::new(dir, u64::MAX)
+    }
+
+    fn body(entry: &Entry) -> String {
+        fs::read_to_string(&entry.path).unwrap()
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn max_age() {
+        let server = TestServer::start(|_| {
+            String::from("HTTP/1.1 200 OK\r\nCache-Control: max-age=3600\r\nContent-Type: text/css\r\n\r\nbody {}")
+        });
+        let cache = cache("max-age");
+
+        let first = cache.fetch(&server.url("/style.css"), None).unwrap();
+        let second = cache.fetch(&server.url("/style.css"), None).unwrap();
+
+        assert_eq!(first.path, second.path);
+        assert_eq!(second.mime_type, "text/css");
+        assert_eq!(body(&second), "body {}");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn concurrent_bodies() {
+        let cache = cache("concurrent");
+        let url = url("https://example.com/");
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| cache.write_body(&url, &mut "body".as_bytes()).unwrap());
+            }
+        });
+
+        assert_eq!(fs::read_to_string(cache.path_for(&url)).unwrap(), "body");
+    }
+
+    #[test]
+    fn offline() {
+        let server = TestServer::start(|request| {
+            if request.starts_with("GET /strict ") {
+                String::from(
+                    "HTTP/1.1 200 OK\r\nCache-Control: max-age=0, must-revalidate\r\n\r\nstrict",
+                )
+            } else {
+                String::from("HTTP/1.1 200 OK\r\nCache-Control: max-age=0\r\n\r\nsaved")
+            }
+        });
+        let cache = cache("offline");
+        cache.fetch(&server.url("/page"), None).unwrap();
+        cache.fetch(&server.url("/strict"), None).unwrap();
+        cache.set_offline(true);
+
+        let stale = cache.fetch(&server.url("/page"), None).unwrap();
+        assert_eq!(body(&stale), "saved");
+        let error = cache.fetch(&server.url("/other"), None).unwrap_err();
+        assert_eq!(
+            download::offline_error(&error),
+            Some(server.url("/other").as_str())
+        );
+        let strict = cache.fetch(&server.url("/strict"), None).unwrap();
+        assert_eq!(body(&strict), "strict");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn offline_on_failed_lookup() {
+        let cache = cache("failed-lookup");
+        let stale = |url: Url, must_revalidate| {
+            fs::write(cache.path_for(&url), "saved").unwrap();
+            cache.insert(Entry {
+                path: cache.path_for(&url),
+                url,
+                size: 5,
+                mime_type: String::from("text/html"),
+                stored: 0,
+                lifetime: 0,
+                must_revalidate,
+                etag: None,
+                last_modified: None,
+                last_used: 0,
+                redirects: Vec::new(),
+                temporary: false,
+            });
+        };
+        stale(url("http://prowser.invalid/strict"), true);
+        stale(url("http://prowser.invalid/page"), false);
+
+        assert!(cache
+            .fetch(&url("http://prowser.invalid/strict"), None)
+            .is_err());
+        assert!(!cache.is_offline());
+        assert_eq!(
+            body(
+                &cache
+                    .fetch(&url("http://prowser.invalid/page"), None)
+                    .unwrap()
+            ),
+            "saved"
+        );
+        assert!(cache.is_offline());
+        let error = cache
+            .fetch(&url("http://prowser.invalid/strict"), None)
+            .unwrap_err();
+        assert_eq!(download::offline_error(&error), None);
+
+        cache.set_offline(true);
+        assert_eq!(
+            body(
+                &cache
+                    .fetch(&url("http://prowser.invalid/strict"), None)
+                    .unwrap()
+            ),
+            "saved"
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let server = TestServer::start(|request| {
+            if request.starts_with("GET /strict ") {
+                String::from(
+                    "HTTP/1.1 200 OK\r\nCache-Control: max-age=0, must-revalidate\r\n\r\nstrict",
+                )
+            } else {
+                String::from("HTTP/1.1 200 OK\r\nCache-Control: max-age=0\r\n\r\nlenient")
+            }
+        });
+        let cache = cache("unreachable");
+        let (lenient, strict) = (server.url("/lenient"), server.url("/strict"));
+        cache.fetch(&lenient, None).unwrap();
+        cache.fetch(&strict, None).unwrap();
+        server.stop();
+
+        assert_eq!(body(&cache.fetch(&lenient, None).unwrap()), "lenient");
+        assert!(download::unreachable(
+            &cache.fetch(&strict, None).unwrap_err()
+        ));
+    }
+
+    #[test]
+    fn no_store() {
+        let server = TestServer::start(|_| {
+            String::from("HTTP/1.1 200 OK\r\nCache-Control: no-store, max-age=3600\r\n\r\nsecret")
+        });
+        let cache = cache("no-store");
+
+        let entry = cache.fetch(&server.url("/"), None).unwrap();
+        assert!(entry.temporary);
+        assert_eq!(body(&entry), "secret");
+        assert_eq!(cache.lookup(&server.url("/")), None);
+        let again = cache.fetch(&server.url("/"), None).unwrap();
+
+        // removed by the callers once used
+        fs::remove_file(&entry.path).unwrap();
+        fs::remove_file(&again.path).unwrap();
+        let left = fs::read_dir(&cache.dir)
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|file| file.file_name() != INDEX_FILE)
+            .count();
+        assert_eq!(left, 0);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn server_error() {
+        let requests = AtomicUsize::new(0);
+        let server = TestServer::start(move |_| {
+            if requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                String::from("HTTP/1.1 200 OK\r\nCache-Control: max-age=0\r\n\r\ngood")
+            } else {
+                String::from("HTTP/1.1 500 Internal Server Error\r\n\r\nbroken")
+            }
+        });
+        let cache = cache("server-error");
+        let url = server.url("/");
+        let good = cache.fetch(&url, None).unwrap();
+
+        let broken = cache.fetch(&url, None).unwrap();
+        assert!(broken.temporary);
+        assert_eq!(body(&broken), "broken");
+        fs::remove_file(&broken.path).unwrap();
+
+        let cached = cache.lookup(&url).unwrap();
+        assert_eq!(cached.path, good.path);
+        assert_eq!(body(&cached), "good");
+    }
+
+    #[test]
+    fn etag_revalidation() {
+        let server = TestServer::start(|request| {
+            if request.contains("if-none-match: \"v1\"") {
+                String::from("HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n")
+            } else {
+                String::from(
+                    "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\n\r\noriginal",
+                )
+            }
+        });
+        let cache = cache("etag");
+
+        cache.fetch(&server.url("/"), None).unwrap();
+        let revalidated = cache.fetch(&server.url("/"), None).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+        assert_eq!(body(&revalidated), "original");
+    }
+
+    #[test]
+    fn last_modified_revalidation() {
+        let server = TestServer::start(|request| {
+            if request.contains("if-modified-since: Sun, 06 Nov 1994 08:49:37 GMT") {
+                String::from("HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=60\r\n\r\n")
+            } else {
+                String::from("HTTP/1.1 200 OK\r\nExpires: 0\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\noriginal")
+            }
+        });
+        let cache = cache("last-modified");
+
+        cache.fetch(&server.url("/"), None).unwrap();
+        let revalidated = cache.fetch(&server.url("/"), None).unwrap();
+        // fresh again after the 304 updated max-age
+        cache.fetch(&server.url("/"), None).unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(revalidated.lifetime, 60);
+        assert_eq!(body(&revalidated), "original");
+    }
+
+    #[test]
+    fn changed_on_revalidation() {
+        let server = TestServer::start(|request| {
+            if request.contains("if-none-match") {
+                String::from("HTTP/1.1 200 OK\r\nETag: \"v2\"\r\n\r\nnew")
+            } else {
+                String::from("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\n\r\nold")
+            }
+        });
+        let cache = cache("changed");
+
+        cache.fetch(&server.url("/"), None).unwrap();
+        let updated = cache.fetch(&server.url("/"), None).unwrap();
+
+        assert_eq!(updated.etag, Some(String::from("\"v2\"")));
+        assert_eq!(body(&updated), "new");
+    }
+
+    #[test]
+    fn redirected() {
+        let server = TestServer::start(|request| {
+            if request.starts_with("GET /old ") {
+                String::from("HTTP/1.1 301 Moved Permanently\r\nLocation: /new\r\n\r\n")
+            } else {
+                String::from("HTTP/1.1 200 OK\r\nCache-Control: max-age=3600\r\n\r\nnew")
+            }
+        });
+        let cache = cache("redirected");
+
+        let entry = cache.fetch(&server.url("/old"), None).unwrap();
+
+        assert_eq!(entry.url, server.url("/new"));
+        assert_eq!(
+            entry.redirects,
+            vec![download::Redirect {
+                status: 301,
+                from: server.url("/old"),
+                to: server.url("/new"),
+            }]
+        );
+        assert_eq!(body(&entry), "new");
+        assert!(cache.lookup(&server.url("/new")).is_some());
+        assert_eq!(cache.lookup(&server.url("/old")), None);
+    }
+
+    #[test]
+    fn redirect_loop() {
+        let server =
+            TestServer::start(|_| String::from("HTTP/1.1 302 Found\r\nLocation: /again\r\n\r\n"));
+        let cache = cache("redirect-loop");
+
+        let error = cache.fetch(&server.url("/"), None).unwrap_err();
+
+        assert!(error.starts_with("Redirect loop"));
+        assert_eq!(server.requests().len(), 11);
+    }
+}
+
+#[cfg(test)]
+mod index {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("prowser-index-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    /// stores a body of `size` bytes for `url`, last used at `last_used`
+    fn add(cache: &Cache, url: &str, size: usize, last_used: u64) {
+        let url = self::url(url);
+        let path = cache.path_for(&url);
+        fs::write(&path, vec![b'a'; size]).unwrap();
+        cache.insert(Entry {
+            url,
+            path,
+            size: size as u64,
+            mime_type: String::from("text/html"),
+            stored: now(),
+            lifetime: 3600,
+            must_revalidate: false,
+            etag: Some(String::from("\"a|b\tc\"")),
+            last_modified: None,
+            last_used,
+            redirects: Vec::new(),
+            temporary: false,
+        });
+    }
+
+    #[test]
+    fn escaping() {
+        let cell = "a|b\tc\\d\ne";
+        assert_eq!(unescape(&escape(cell)), cell);
+    }
+
+    #[test]
+    fn key_is_sha256() {
+        assert_eq!(
+            key(&url("https://example.com/")),
+            "0f115db062b7c0dd030b16878c99dea5c354b49dc37b38eb8846179c7783e9d7"
+        );
+    }
+
+    #[test]
+    fn persisted() {
+        let dir = dir("persisted");
+        let cache = Cache::new(&dir, u64::MAX);
+        add(&cache, "https://example.com/a|b", 10, 1);
+
+        let reopened = Cache::new(&dir, u64::MAX);
+        let entry = reopened.lookup(&url("https://example.com/a|b")).unwrap();
+
+        assert_eq!(entry.url.as_str(), "https://example.com/a|b");
+        assert_eq!(entry.etag, Some(String::from("\"a|b\tc\"")));
+        assert_eq!(entry.size, 10);
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let dir = dir("lru");
+        let cache = Cache::new(&dir, 25);
+        add(&cache, "https://example.com/old", 10, 1);
+        add(&cache, "https://example.com/used", 10, 2);
+        // lookup marks it as recently used
+        cache.lookup(&url("https://example.com/old")).unwrap();
+        add(&cache, "https://example.com/new", 10, now());
+
+        assert!(cache.lookup(&url("https://example.com/used")).is_none());
+        assert!(!Path::new(&cache.path_for(&url("https://example.com/used"))).exists());
+        assert!(cache.lookup(&url("https://example.com/old")).is_some());
+        assert!(cache.lookup(&url("https://example.com/new")).is_some());
+    }
+
+    #[test]
+    fn too_big() {
+        let dir = dir("too-big");
+        let cache = Cache::new(&dir, 5);
+        add(&cache, "https://example.com/big", 10, 1);
+
+        assert!(cache.lookup(&url("https://example.com/big")).is_none());
+    }
+
+    #[test]
+    fn vanished_body() {
+        let dir = dir("vanished");
+        let cache = Cache::new(&dir, u64::MAX);
+        add(&cache, "https://example.com/", 10, 1);
+        fs::remove_file(cache.path_for(&url("https://example.com/"))).unwrap();
+
+        assert!(cache.lookup(&url("https://example.com/")).is_none());
+        assert!(Cache::new(&dir, u64::MAX)
+            .index
+            .lock()
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn removed() {
+        let dir = dir("removed");
+        let cache = Cache::new(&dir, u64::MAX);
+        add(&cache, "https://example.com/a", 10, 1);
+        add(&cache, "https://example.com/b", 20, 2);
+        add(&cache, "https://example.com/c", 30, 3);
+
+        let urls = |cache: &Cache| {
+            cache
+                .entries()
+                .into_iter()
+                .map(|entry| entry.url.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            urls(&cache),
+            vec![
+                "https://example.com/c",
+                "https://example.com/b",
+                "https://example.com/a"
+            ]
+        );
+
+        cache.remove(&url("https://example.com/b"));
+        assert_eq!(
+            urls(&Cache::new(&dir, u64::MAX)),
+            vec!["https://example.com/c", "https://example.com/a"]
+        );
+        assert!(!Path::new(&cache.path_for(&url("https://example.com/b"))).exists());
+        assert_eq!(cache.index.lock().unwrap().size, 40);
+
+        cache.clear();
+        assert!(cache.entries().is_empty());
+        assert!(!Path::new(&cache.path_for(&url("https://example.com/a"))).exists());
+    }
+
+    #[test]
+    fn other_format() {
+        let dir = dir("other-format");
+        let cache = Cache::new(&dir, u64::MAX);
+        add(&cache, "https://example.com/", 10, 1);
+        let index = fs::read_to_string(dir.join(INDEX_FILE)).unwrap();
+        fs::write(
+            dir.join(INDEX_FILE),
+            index.replacen(INDEX_HEADER, "prowser cache index v0", 1),
+        )
+        .unwrap();
+
+        let reopened = Cache::new(&dir, u64::MAX);
+        assert!(reopened.lookup(&url("https://example.com/")).is_none());
+        assert!(!Path::new(&cache.path_for(&url("https://example.com/"))).exists());
+    }
+
+    #[test]
+    fn temporary() {
+        let dir = dir("temporary");
+        let cache = Cache::new(&dir, u64::MAX);
+        let (first, second) = (cache.temporary_path(), cache.temporary_path());
+        assert_ne!(first, second);
+        fs::write(&first, "response").unwrap();
+
+        assert!(cache.entries().is_empty());
+        Cache::new(&dir, u64::MAX);
+        assert!(!Path::new(&first).exists());
+    }
+
+    #[test]
+    fn orphans_removed() {
+        let dir = dir("orphans");
+        fs::write(dir.join("cache.csv"), "old index").unwrap();
+        fs::write(dir.join("1234"), "old body").unwrap();
+        Cache::new(&dir, u64::MAX);
+
+        assert!(!dir.join("cache.csv").exists());
+        assert!(!dir.join("1234").exists());
+        assert!(dir.join(INDEX_FILE).exists());
+    }
+}
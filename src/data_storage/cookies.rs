@@ -0,0 +1,416 @@
+//! Cookie jar, partitioned by top-level site
+//! <https://tools.ietf.org/html/rfc6265>
+//!
+//! Cookies set while visiting one site are never sent while visiting another site,
+//! even if the request goes to the same third party host.
+
+use super::date;
+
+use reqwest::Url;
+use std::collections::HashMap;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// host (`host_only`) or domain the cookie is sent to
+    pub domain: String,
+    pub host_only: bool,
+    pub path: String,
+    /// seconds since unix epoch. `None` for session cookies
+    pub expires: Option<u64>,
+    pub secure: bool,
+}
+
+/// `top-level site` → cookies set while browsing that site
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    partitions: HashMap<String, Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Loads persisted cookies.
+    /// One cookie per line in the netscape `cookies.txt` format, prefixed by the partition:
+    /// `site  domain  include_subdomains  path  secure  expires  name  value`
+    pub fn load(path: &str) -> Self {
+        let mut jar = Self::default();
+        let now = now();
+
+        if let Ok(content) = fs::read_to_string(path) {
+            for line in content.lines() {
+                let cells = line.split('\t').collect::<Vec<&str>>();
+                if cells.len() != 8 {
+                    continue;
+                }
+
+                if let Ok(expires) = cells[5].parse::<u64>() {
+                    if expires > now {
+                        jar.partitions
+                            .entry(cells[0].to_string())
+                            .or_default()
+                            .push(Cookie {
+                                domain: cells[1].to_string(),
+                                host_only: cells[2] != "TRUE",
+                                path: cells[3].to_string(),
+                                secure: cells[4] == "TRUE",
+                                expires: Some(expires),
+                                name: cells[6].to_string(),
+                                value: cells[7].to_string(),
+                            });
+                    }
+                }
+            }
+        }
+
+        jar
+    }
+
+    /// Writes all persistent cookies to disk. Session cookies are not saved.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let now = now();
+        let mut content = String::new();
+
+        for (site, cookies) in &self.partitions {
+            for cookie in cookies {
+                if let Some(expires) = cookie.expires {
+                    if expires > now {
+                        content.push_str(&format!(
+                            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                            site,
+                            cookie.domain,
+                            if cookie.host_only { "FALSE" } else { "TRUE" },
+                            cookie.path,
+                            if cookie.secure { "TRUE" } else { "FALSE" },
+                            expires,
+                            cookie.name,
+                            cookie.value
+                        ));
+                    }
+                }
+            }
+        }
+
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    /// Stores a `Set-Cookie` header received from `url` while browsing `top_level`.
+    /// Returns `true` if a persistent cookie changed.
+    pub fn store(&mut self, top_level: &Url, url: &Url, set_cookie: &str) -> bool {
+        let cookie = match parse_set_cookie(url, set_cookie) {
+            Some(cookie) => cookie,
+            None => return false,
+        };
+        let mut persistent = cookie.expires.is_some();
+
+        let cookies = self.partitions.entry(site(top_level)).or_default();
+        cookies.retain(|c| {
+            let replaced =
+                c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path;
+            persistent |= replaced && c.expires.is_some();
+            !replaced
+        });
+
+        // an expiry date in the past deletes the cookie
+        if cookie.expires.is_none_or(|expires| expires > now()) {
+            cookies.push(cookie);
+        }
+
+        persistent
+    }
+
+    /// Returns the value of the `Cookie` header for a request to `url` while browsing `top_level`.
+    pub fn header(&self, top_level: &Url, url: &Url) -> Option<String> {
+        let host = url.host_str()?;
+        let now = now();
+
+        let cookies = self.partitions.get(&site(top_level))?;
+        let mut matching = cookies
+            .iter()
+            .filter(|c| {
+                (if c.host_only {
+                    c.domain == host
+                } else {
+                    domain_match(host, &c.domain)
+                }) && path_match(url.path(), &c.path)
+                    && (!c.secure || url.scheme() == "https")
+                    && c.expires.is_none_or(|expires| expires > now)
+            })
+            .collect::<Vec<_>>();
+
+        if matching.is_empty() {
+            return None;
+        }
+
+        // cookies with longer paths are listed first
+        matching.sort_by_key(|c| std::cmp::Reverse(c.path.len()));
+
+        Some(
+            matching
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Removes all cookies of the site `url` belongs to.
+    pub fn clear_site(&mut self, url: &Url) {
+        self.partitions.remove(&site(url));
+    }
+
+    /// Removes all cookies.
+    pub fn clear(&mut self) {
+        self.partitions.clear();
+    }
+}
+
+/// Parses a `Set-Cookie` header value.
+/// <https://tools.ietf.org/html/rfc6265#section-5.2>
+fn parse_set_cookie(url: &Url, header: &str) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut parts = header.split(';');
+
+    let pair = parts.next()?;
+    let equals = pair.find('=')?;
+    let name = pair[..equals].trim();
+    let value = pair[equals + 1..].trim();
+    if name.is_empty() || value.contains('\t') {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url.path()),
+        expires: None,
+        secure: false,
+    };
+    let mut max_age = None;
+
+    for attribute in parts {
+        let (key, value) = match attribute.find('=') {
+            Some(equals) => (&attribute[..equals], attribute[equals + 1..].trim()),
+            None => (attribute, ""),
+        };
+
+        match &*key.trim().to_ascii_lowercase() {
+            "expires" if cookie.expires.is_none() => {
+                cookie.expires = date::parse_http_date(value);
+            }
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    max_age = Some(if seconds <= 0 {
+                        0
+                    } else {
+                        now() + seconds as u64
+                    });
+                }
+            }
+            "domain" => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain.is_empty() {
+                    // a single label (`com`) would share the cookie with a whole tld
+                    if !domain.contains('.') || !domain_match(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+            }
+            "path" if value.starts_with('/') => {
+                cookie.path = value.to_string();
+            }
+            "secure" => cookie.secure = true,
+            _ => {}
+        }
+    }
+
+    // `Max-Age` has precedence over `Expires`
+    if max_age.is_some() {
+        cookie.expires = max_age;
+    }
+
+    Some(cookie)
+}
+
+/// <https://tools.ietf.org/html/rfc6265#section-5.1.3>
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// <https://tools.ietf.org/html/rfc6265#section-5.1.4>
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// Directory of the request path.
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => String::from("/"),
+        Some(slash) => path[..slash].to_string(),
+    }
+}
+
+/// Approximates the registrable domain ("site") of an url.
+/// Without a public suffix list, two letter country tlds with a common second level
+/// (`example.co.uk`) keep three labels, everything else keeps two.
+pub fn site(url: &Url) -> String {
+    let host = match url.host_str() {
+        Some(host) => host.to_ascii_lowercase(),
+        None => return url.scheme().to_string(),
+    };
+
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+
+    let labels = host.split('.').collect::<Vec<&str>>();
+    let keep = match labels.as_slice() {
+        [.., second, tld]
+            if tld.len() == 2
+                && ["co", "com", "org", "net", "ac", "gov", "edu"].contains(second) =>
+        {
+            3
+        }
+        _ => 2,
+    };
+
+    labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|n| n.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod jar {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn send_back() {
+        let mut jar = CookieJar::default();
+        let page = url("https://example.com/login");
+        jar.store(&page, &page, "session=abc; Path=/; Secure");
+
+        assert_eq!(
+            jar.header(&page, &url("https://example.com/account")),
+            Some(String::from("session=abc"))
+        );
+        // secure cookies are not sent over http
+        assert_eq!(jar.header(&page, &url("http://example.com/account")), None);
+    }
+
+    #[test]
+    fn partitioned_by_site() {
+        let mut jar = CookieJar::default();
+        let tracker = url("https://tracker.net/pixel.gif");
+        jar.store(&url("https://a.org/"), &tracker, "id=1");
+
+        assert_eq!(
+            jar.header(&url("https://www.a.org/page"), &tracker),
+            Some(String::from("id=1"))
+        );
+        assert_eq!(jar.header(&url("https://b.org/"), &tracker), None);
+    }
+
+    #[test]
+    fn domain_attribute() {
+        let mut jar = CookieJar::default();
+        let page = url("https://login.example.com/");
+        jar.store(&page, &page, "a=1; Domain=.example.com");
+        // not allowed to set for other domains or whole tlds
+        jar.store(&page, &page, "b=2; Domain=other.com");
+        jar.store(&page, &page, "c=3; Domain=com");
+
+        assert_eq!(
+            jar.header(&page, &url("https://www.example.com/")),
+            Some(String::from("a=1"))
+        );
+    }
+
+    #[test]
+    fn host_only() {
+        let mut jar = CookieJar::default();
+        let page = url("https://login.example.com/");
+        jar.store(&page, &page, "a=1");
+
+        assert_eq!(jar.header(&page, &url("https://www.example.com/")), None);
+    }
+
+    #[test]
+    fn path() {
+        let mut jar = CookieJar::default();
+        let page = url("https://example.com/shop/cart");
+        jar.store(&page, &page, "a=1");
+
+        assert_eq!(
+            jar.header(&page, &url("https://example.com/shop/item")),
+            Some(String::from("a=1"))
+        );
+        assert_eq!(
+            jar.header(&page, &url("https://example.com/shopping")),
+            None
+        );
+    }
+
+    #[test]
+    fn delete_by_max_age() {
+        let mut jar = CookieJar::default();
+        let page = url("https://example.com/");
+        jar.store(&page, &page, "a=1");
+        jar.store(&page, &page, "a=1; Max-Age=0");
+
+        assert_eq!(jar.header(&page, &page), None);
+    }
+
+    #[test]
+    fn clear() {
+        let mut jar = CookieJar::default();
+        let page = url("https://example.com/");
+        jar.store(&page, &page, "a=1");
+        jar.clear_site(&url("https://www.example.com/"));
+
+        assert_eq!(jar.header(&page, &page), None);
+    }
+
+    #[test]
+    fn persist() {
+        let path = std::env::temp_dir().join("prowser-cookie-test");
+        let path = path.to_str().unwrap();
+        let page = url("https://example.com/");
+
+        let mut jar = CookieJar::default();
+        jar.store(&page, &page, "session=1");
+        jar.store(&page, &page, "login=2; Max-Age=3600");
+        jar.save(path).unwrap();
+
+        let loaded = CookieJar::load(path);
+        let _ = fs::remove_file(path);
+
+        assert_eq!(loaded.header(&page, &page), Some(String::from("login=2")));
+    }
+
+    #[test]
+    fn sites() {
+        assert_eq!(site(&url("https://www.example.com/")), "example.com");
+        assert_eq!(site(&url("https://a.b.example.co.uk/")), "example.co.uk");
+        assert_eq!(site(&url("http://127.0.0.1:8080/")), "127.0.0.1");
+        assert_eq!(site(&url("http://localhost/")), "localhost");
+    }
+}
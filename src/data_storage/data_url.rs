@@ -0,0 +1,209 @@
+//! Inline resources of `data:` urls
+//! <https://fetch.spec.whatwg.org/#data-urls>
+
+/// Body and mime type of a `data:` url
+#[derive(Debug, PartialEq)]
+pub struct DataUrl {
+    /// `text/plain;charset=US-ASCII` if none is given
+    pub mime_type: String,
+    pub body: Vec<u8>,
+}
+
+/// Whether `url` is a `data:` url.
+pub fn is_data_url(url: &str) -> bool {
+    url.get(..5)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:"))
+}
+
+/// Decodes a `data:` url, `data:[<mime type>][;base64],<data>`.
+pub fn parse(url: &str) -> Result<DataUrl, String> {
+    if !is_data_url(url) {
+        return Err(format!("Not a data url: {}", url));
+    }
+    // the fragment isn't part of the data
+    let url = url[5..].split('#').next().unwrap_or_default();
+    let (mime_type, data) = url
+        .split_once(',')
+        .ok_or_else(|| format!("No data in data url: data:{}", url))?;
+
+    let mut mime_type = mime_type.trim();
+    let mut base64 = false;
+    if let Some(position) = mime_type.rfind(';') {
+        if mime_type[position + 1..]
+            .trim()
+            .eq_ignore_ascii_case("base64")
+        {
+            mime_type = mime_type[..position].trim_end();
+            base64 = true;
+        }
+    }
+
+    let mime_type = if mime_type.is_empty() {
+        String::from("text/plain;charset=US-ASCII")
+    } else if mime_type.starts_with(';') {
+        format!("text/plain{}", mime_type)
+    } else {
+        mime_type.to_string()
+    };
+
+    let data = percent_decode(data);
+    let body = if base64 {
+        forgiving_base64_decode(&data).ok_or_else(|| String::from("Invalid base64 in data url"))?
+    } else {
+        data
+    };
+
+    Ok(DataUrl { mime_type, body })
+}
+
+/// `data:` url of `body`, base64 encoded.
+pub fn encode(mime_type: &str, body: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut url = format!("data:{};base64,", mime_type.replace([' ', ','], ""));
+    for chunk in body.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                url.push(char::from(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize]));
+            } else {
+                url.push('=');
+            }
+        }
+    }
+    url
+}
+
+pub fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = text
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+/// <https://infra.spec.whatwg.org/#forgiving-base64-decode>
+/// White space is ignored and padding is optional.
+fn forgiving_base64_decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut data = data
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect::<Vec<_>>();
+
+    if data.len() % 4 == 0 {
+        if data.ends_with(b"==") {
+            data.truncate(data.len() - 2);
+        } else if data.ends_with(b"=") {
+            data.truncate(data.len() - 1);
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod decode {
+    use super::*;
+
+    #[test]
+    fn percent_encoded() {
+        assert_eq!(
+            parse("data:,Hello%2C%20World%21"),
+            Ok(DataUrl {
+                mime_type: String::from("text/plain;charset=US-ASCII"),
+                body: b"Hello, World!".to_vec(),
+            })
+        );
+        assert_eq!(
+            parse("DATA:text/html;charset=utf-8,<p>%C3%BC</p>#fragment"),
+            Ok(DataUrl {
+                mime_type: String::from("text/html;charset=utf-8"),
+                body: "<p>ü</p>".as_bytes().to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(
+            parse("data:image/png;base64,iVBORw0KGgo="),
+            Ok(DataUrl {
+                mime_type: String::from("image/png"),
+                body: b"\x89PNG\r\n\x1a\n".to_vec(),
+            })
+        );
+        // white space and missing padding are forgiven
+        assert_eq!(
+            parse("data:;charset=utf-8;BASE64,SGVs\n bG8").unwrap(),
+            DataUrl {
+                mime_type: String::from("text/plain;charset=utf-8"),
+                body: b"Hello".to_vec(),
+            }
+        );
+        assert_eq!(parse("data:text/plain;base64,YQ%3D%3D").unwrap().body, b"a");
+    }
+
+    #[test]
+    fn encoded() {
+        for body in [&b""[..], b"a", b"ab", b"abc", b"\x89PNG\r\n\x1a\n\xff"] {
+            let url = encode("image/png", body);
+            assert_eq!(parse(&url).unwrap().body, body);
+        }
+        assert_eq!(
+            encode("text/css; charset=utf-8", b"Hello"),
+            "data:text/css;charset=utf-8;base64,SGVsbG8="
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(parse("data:text/plain").is_err());
+        assert!(parse("data:;base64,a").is_err());
+        assert!(parse("data:;base64,ab*d").is_err());
+        assert!(parse("https://example.com/,a").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! HTTP date parsing and date formatting
+//! <https://tools.ietf.org/html/rfc7231#section-7.1.1.1>
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// Parses an HTTP date into seconds since the unix epoch.
+/// Accepts the IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`), the obsolete RFC 850 format
+/// (`Sunday, 06-Nov-94 08:49:37 GMT`), asctime (`Sun Nov  6 08:49:37 1994`)
+/// and the dash separated variant browsers accept in cookies (`Sun, 06-Nov-1994 08:49:37 GMT`).
+pub fn parse_http_date(date: &str) -> Option<u64> {
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    let mut time = None;
+
+    for token in date
+        .split([' ', ',', '-'])
+        .filter(|token| !token.is_empty())
+    {
+        if time.is_none() && token.contains(':') {
+            let parts = token
+                .split(':')
+                .map(str::parse::<u64>)
+                .collect::<Result<Vec<_>, _>>()
+                .ok()?;
+            if parts.len() != 3 || parts[0] > 23 || parts[1] > 59 || parts[2] > 60 {
+                return None;
+            }
+            time = Some(parts[0] * 3600 + parts[1] * 60 + parts[2]);
+        } else if let Ok(number) = token.parse::<u64>() {
+            if day.is_none() && token.len() <= 2 {
+                day = Some(number);
+            } else if year.is_none() {
+                year = Some(match (token.len(), number) {
+                    // two digit years, RFC 6265 5.1.1
+                    (2, 70..=99) => number + 1900,
+                    (2, _) => number + 2000,
+                    _ => number,
+                });
+            }
+        } else if month.is_none() && token.len() >= 3 {
+            let lower = token[..3].to_ascii_lowercase();
+            month = MONTHS
+                .iter()
+                .position(|m| *m == lower)
+                .map(|m| m as u64 + 1);
+        }
+    }
+
+    let (day, month, year, time) = (day?, month?, year?, time?);
+    if day == 0 || day > 31 || year < 1970 {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86_400 + time)
+}
+
+/// Formats seconds since the unix epoch as `YYYY-MM-DD HH:MM` (UTC).
+pub fn format_date(seconds: u64) -> String {
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60
+    )
+}
+
+/// Formats seconds since the unix epoch as ISO 8601 `YYYY-MM-DDThh:mm:ssZ`.
+pub fn format_iso_date(seconds: u64) -> String {
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+    let time = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Days since 1970-01-01 of a date in the proleptic gregorian calendar.
+/// <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date in the proleptic gregorian calendar of days since 1970-01-01.
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod parse {
+    use super::*;
+
+    #[test]
+    fn imf_fixdate() {
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+    }
+
+    #[test]
+    fn rfc850() {
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(784_111_777)
+        );
+    }
+
+    #[test]
+    fn asctime() {
+        assert_eq!(
+            parse_http_date("Sun Nov  6 08:49:37 1994"),
+            Some(784_111_777)
+        );
+    }
+
+    #[test]
+    fn cookie_dash_format() {
+        assert_eq!(
+            parse_http_date("Wed, 21-Oct-2015 07:28:00 GMT"),
+            Some(1_445_412_480)
+        );
+    }
+
+    #[test]
+    fn epoch() {
+        assert_eq!(parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"), Some(0));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(parse_http_date("0"), None);
+        assert_eq!(parse_http_date("-1"), None);
+        assert_eq!(parse_http_date("tomorrow"), None);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_date(0), "1970-01-01 00:00");
+        assert_eq!(format_date(784_111_777), "1994-11-06 08:49");
+        assert_eq!(format_date(951_782_400), "2000-02-29 00:00");
+        assert_eq!(format_iso_date(784_111_777), "1994-11-06T08:49:37Z");
+    }
+}
@@ -1,76 +1,283 @@
+use super::cookies::{self, CookieJar};
+use super::https;
+use super::warc;
+use crate::config::{self, Config, HeaderProfile};
+
+use once_cell::sync::Lazy;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, CONTENT_TYPE, COOKIE, DNT,
+    LOCATION, SET_COOKIE, UPGRADE_INSECURE_REQUESTS, USER_AGENT,
+};
+use reqwest::{Method, StatusCode, Url};
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::sync::Mutex;
+
+/// persisted cookies
+const COOKIE_FILE: &str = "data/cookies";
+/// start of the error of a paused redirect, followed by its target
+const REDIRECT_PAUSED: &str = "Paused redirect to ";
+/// start of the error of a request not sent in offline mode, followed by its url
+const OFFLINE: &str = "Offline, not requesting ";
+
+/// The single http client of the browser.
+/// Reused for every request, so connections are kept alive.
+static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+struct Client {
+    http: reqwest::blocking::Client,
+    cookies: Mutex<CookieJar>,
+}
+
+/// A redirect followed on the way to a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub status: u16,
+    /// url answering with the redirect
+    pub from: Url,
+    /// its `Location`, resolved
+    pub to: Url,
+}
+
+impl Client {
+    fn new() -> Self {
+        Self {
+            http: http_client(|url| proxy_for(url).ok().flatten()),
+            cookies: Mutex::new(CookieJar::load(COOKIE_FILE)),
+        }
+    }
+
+    /// Sends a request and follows redirects, returning the response and the redirects followed.
+    /// Cookies are partitioned by `top_level`, the page the request is made for.
+    /// `None` for top-level navigations, the partition then follows the redirects.
+    fn send(
+        &self,
+        mut method: Method,
+        url: &Url,
+        top_level: Option<&Url>,
+        mut headers: HeaderMap,
+        mut body: Option<(&str, &[u8])>,
+    ) -> Result<(reqwest::blocking::Response, Vec<Redirect>), String> {
+        if super::offline() {
+            return Err(not_requested(url));
+        }
+        if warc::is_replaying() {
+            return Err(warc::not_archived(url));
+        }
+        let mut url = url.clone();
+        let max_redirects = config::get().max_redirects;
+        let mut redirects = Vec::new();
+
+        for _ in 0..=max_redirects {
+            // the http url, if the request is upgraded to https
+            let insecure = https::upgrade(&url).map(|secure| std::mem::replace(&mut url, secure));
+            let site = top_level.unwrap_or(&url);
+            // a request whose proxy can't be used would go out directly
+            proxy_for(&url)?;
+
+            // the profile of the page, all its resources look the same
+            let config = config::get();
+            let profile = config.header_profile(site.host_str().unwrap_or_default());
+            let mut request = self
+                .http
+                .request(method.clone(), url.clone())
+                .headers(get_headers(profile))
+                .headers(headers.clone());
+            if let Some(cookie) = self.cookies.lock().unwrap().header(site, &url) {
+                request = request.header(COOKIE, cookie);
+            }
+            if let Some((content_type, body)) = body {
+                request = request
+                    .header(CONTENT_TYPE, content_type)
+                    .body(body.to_vec());
+            }
+
+            let request = request.build().map_err(|e| e.to_string())?;
+            // sending consumes the request, a copy is kept for the archive
+            let recorded = warc::is_recording().then(|| request.try_clone()).flatten();
+            let mut responce = self.http.execute(request).map_err(|e| match &insecure {
+                Some(insecure) => https::upgrade_error(insecure, &e.to_string()),
+                None => e.to_string(),
+            })?;
+            if let Some(recorded) = &recorded {
+                responce = warc::record(recorded, responce, top_level.is_none())?;
+            }
+
+            {
+                let mut jar = self.cookies.lock().unwrap();
+                let mut changed = false;
+                for set_cookie in responce.headers().get_all(SET_COOKIE) {
+                    if let Ok(set_cookie) = set_cookie.to_str() {
+                        changed |= jar.store(site, &url, set_cookie);
+                    }
+                }
+                if changed {
+                    let _ = jar.save(COOKIE_FILE);
+                }
+            }
+
+            let status = responce.status();
+            let location = responce
+                .headers()
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok());
+
+            match (status.is_redirection(), location) {
+                (true, Some(location)) => {
+                    let next = url.join(location).map_err(|e| e.to_string())?;
+                    // a site redirecting https to http can't be upgraded
+                    if let Some(insecure) = &insecure {
+                        if https::upgrade(&next).as_ref() == Some(&url) {
+                            return Err(https::upgrade_error(insecure, "redirected to http"));
+                        }
+                    }
+                    if top_level.is_none() && pauses(&config, &url, &next) {
+                        return Err(format!("{}{}\nfrom {}", REDIRECT_PAUSED, next, url));
+                    }
+                    redirects.push(Redirect {
+                        status: status.as_u16(),
+                        from: url.clone(),
+                        to: next.clone(),
+                    });
+                    url = next;
+                    // conditional headers only apply to the originally requested url
+                    headers = HeaderMap::new();
+
+                    // https://tools.ietf.org/html/rfc7231#section-6.4
+                    if status == StatusCode::SEE_OTHER
+                        || (method == Method::POST
+                            && (status == StatusCode::MOVED_PERMANENTLY
+                                || status == StatusCode::FOUND))
+                    {
+                        method = Method::GET;
+                        body = None;
+                    }
+                }
+                _ => return Ok((responce, redirects)),
+            }
+        }
+
+        Err(format!(
+            "Redirect loop: more than {} redirects, the last to {}",
+            max_redirects, url
+        ))
+    }
+}
+
+/// Error of a request that isn't sent in offline mode.
+pub fn not_requested(url: &Url) -> String {
+    format!("{}{}", OFFLINE, url)
+}
+
+/// Url of a request that wasn't sent in offline mode, `None` for other errors.
+pub fn offline_error(error: &str) -> Option<&str> {
+    error.strip_prefix(OFFLINE)
+}
+
+/// Did a request fail because the host name couldn't be resolved, like without a network?
+pub fn lookup_failed(error: &str) -> bool {
+    error.contains("failed to lookup address information")
+}
+
+/// Did a request fail because no connection to the server could be made?
+pub fn unreachable(error: &str) -> bool {
+    error.contains("error trying to connect")
+}
+
+/// Does a top-level navigation wait for the user before following a redirect?
+fn pauses(config: &Config, from: &Url, to: &Url) -> bool {
+    config.pause_cross_site_redirects && cookies::site(from) != cookies::site(to)
+}
+
+/// Target of a redirect paused by `send`.
+pub fn paused_redirect(error: &str) -> Option<&str> {
+    error.strip_prefix(REDIRECT_PAUSED)?.lines().next()
+}
+
+/// Http client connecting through the proxy `proxy` returns for an url.
+fn http_client<F>(proxy: F) -> reqwest::blocking::Client
+where
+    F: Fn(&Url) -> Option<Url> + Send + Sync + 'static,
+{
+    reqwest::blocking::Client::builder()
+        .referer(false)
+        // redirects are followed by hand, to store cookies set on the way
+        .redirect(reqwest::redirect::Policy::none())
+        // replaces proxies from the environment
+        .proxy(reqwest::Proxy::custom(proxy))
+        .build()
+        .expect("http client to build")
+}
+
+/// Configured proxy for an url, checked to be usable.
+fn proxy_for(url: &Url) -> Result<Option<Url>, String> {
+    let proxy = config::get().proxy(url.host_str().unwrap_or_default())?;
+    if let Some(proxy) = &proxy {
+        reqwest::Proxy::all(proxy.as_str())
+            .map_err(|e| format!("Invalid proxy {}: {}", proxy, e))?;
+    }
+    Ok(proxy)
+}
 
-fn get_headers() -> reqwest::header::HeaderMap {
+/// Identifying headers of a header profile.
+/// Values that aren't valid in a header are left out.
+fn get_headers(profile: &HeaderProfile) -> HeaderMap {
     /*
     https://httpbin.org/get
 
     Time Zone : 0 UTC+1
     Referer
     */
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert(
-        reqwest::header::USER_AGENT,
-        reqwest::header::HeaderValue::from_static(
-            "Mozilla/5.0 (Windows NT 10.0; rv:68.0) Gecko/20100101 Firefox/68.0",
-        ),
-    );
-    headers.insert(
-        reqwest::header::ACCEPT,
-        reqwest::header::HeaderValue::from_static(
-            "text/html, application/xhtml+xml, application/xml;q=0.9, */*;q=0.8",
-        ),
-    );
-    headers.insert(
-        reqwest::header::DNT,
-        reqwest::header::HeaderValue::from_static("1"),
-    );
-    headers.insert(
-        reqwest::header::ACCEPT_LANGUAGE,
-        reqwest::header::HeaderValue::from_static("en-us,en;q=0.5"),
-    );
-    headers.insert(
-        reqwest::header::UPGRADE_INSECURE_REQUESTS,
-        reqwest::header::HeaderValue::from_static("1"),
-    );
+    let mut headers = HeaderMap::new();
+    let mut insert = |name: HeaderName, value: &str| {
+        if let Ok(value) = HeaderValue::from_str(value) {
+            headers.insert(name, value);
+        }
+    };
+
+    insert(USER_AGENT, &profile.user_agent);
+    insert(ACCEPT, &profile.accept);
+    if profile.dnt {
+        insert(DNT, "1");
+    }
+    if profile.gpc {
+        insert(HeaderName::from_static("sec-gpc"), "1");
+    }
+    insert(ACCEPT_LANGUAGE, &profile.accept_language());
+    insert(UPGRADE_INSECURE_REQUESTS, "1");
 
     headers
 }
 
+/// Requests an url with the shared client.
+/// `top_level` is the url of the page the resource is loaded for, `None` for a top-level navigation.
+/// `headers` are sent in addition to the headers of the header profile, like conditional request headers.
 // https://www.reddit.com/r/rust/comments/9lrpru/download_file_with_progress_bar/
-pub fn request(url: &str) -> Result<reqwest::blocking::Response, String> {
-    // TODO: only use 1 single client and reuse it.
-
-    let client = reqwest::blocking::Client::builder()
-        //.cookie_store(true) <- currently useless as I create a new `Client` for each request
-        .referer(false)
-        .default_headers(get_headers())
-        .build()
-        .map_err(|e| e.to_string())?;
-    let responce = client.get(url).send().map_err(|e| e.to_string())?;
-
-    // FIXME: may require a single client. Seperate cookie store is needed anyway
-    //dbg!(responce.cookies().collect::<Vec<_>>());
-
-    Ok(responce)
+pub fn request(
+    url: &Url,
+    top_level: Option<&Url>,
+    headers: HeaderMap,
+) -> Result<(reqwest::blocking::Response, Vec<Redirect>), String> {
+    CLIENT.send(Method::GET, url, top_level, headers, None)
 }
 
-/// downloads a file with given parameters
+/// posts `body` of `content_type` and saves the response to `path`
+/// Returns the redirects followed.
 // https://www.reddit.com/r/rust/comments/9lrpru/download_file_with_progress_bar/
-pub fn save_file_post(url: &str, path: &str, params: &[(&str, &str)]) -> Result<(), String> {
-    let client = reqwest::blocking::Client::builder()
-        //.cookie_store(true) <- currently useless as I create a new `Client` for each request
-        .referer(false)
-        .default_headers(get_headers())
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let mut responce = client
-        .post(url)
-        .form(&params)
-        .send()
-        .map_err(|e| e.to_string())?;
+pub fn save_file_post(
+    url: &Url,
+    path: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<Vec<Redirect>, String> {
+    let (mut responce, redirects) = CLIENT.send(
+        Method::POST,
+        url,
+        None,
+        HeaderMap::new(),
+        Some((content_type, body)),
+    )?;
 
     let status = responce.status();
     if !status.is_success() {
@@ -88,5 +295,149 @@ pub fn save_file_post(url: &str, path: &str, params: &[(&str, &str)]) -> Result<
     let mut out = fs::File::create(path).map_err(|e| e.to_string())?;
     io::copy(&mut responce, &mut out).map_err(|e| e.to_string())?;
 
-    Ok(())
+    Ok(redirects)
+}
+
+/// Deletes all cookies, in memory and on disk.
+pub fn clear_cookies() -> Result<(), String> {
+    CLIENT.cookies.lock().unwrap().clear();
+    CLIENT.cookies.lock().unwrap().save(COOKIE_FILE)
+}
+
+/// Deletes the cookies set while browsing the site of `url`.
+pub fn clear_site_cookies(url: &Url) -> Result<(), String> {
+    let mut jar = CLIENT.cookies.lock().unwrap();
+    jar.clear_site(url);
+    jar.save(COOKIE_FILE)
+}
+
+#[cfg(test)]
+mod requests {
+    use super::super::test_server::TestServer;
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn from_profile() {
+        let config =
+            config::Config::parse("[profile private]\ndnt = false\ngpc = true\nlanguages = de, en");
+        let headers = get_headers(config.header_profile("example.com"));
+        assert_eq!(headers[ACCEPT_LANGUAGE], "en-us,en;q=0.5");
+        assert_eq!(headers[DNT], "1");
+        assert!(!headers.contains_key("sec-gpc"));
+
+        let headers = get_headers(&config.profiles["private"]);
+        assert_eq!(headers[ACCEPT_LANGUAGE], "de,en;q=0.5");
+        assert!(!headers.contains_key(DNT));
+        assert_eq!(headers["sec-gpc"], "1");
+    }
+
+    #[test]
+    fn cross_site_redirects() {
+        let url = |url| Url::parse(url).unwrap();
+        let config = config::Config::parse("pause_cross_site_redirects = true");
+
+        assert!(pauses(
+            &config,
+            &url("https://example.com/"),
+            &url("https://tracker.example/?to=a")
+        ));
+        assert!(!pauses(
+            &config,
+            &url("http://example.com/"),
+            &url("https://www.example.com/")
+        ));
+        assert!(!pauses(
+            &config::Config::default(),
+            &url("https://example.com/"),
+            &url("https://tracker.example/")
+        ));
+
+        let error = format!(
+            "{}https://tracker.example/\nfrom https://example.com/",
+            REDIRECT_PAUSED
+        );
+        assert_eq!(paused_redirect(&error), Some("https://tracker.example/"));
+        assert_eq!(paused_redirect("Too many redirects"), None);
+    }
+
+    #[test]
+    fn invalid_value_left_out() {
+        let config = config::Config::parse("[profile broken]\nuser_agent = Mozilla\u{7}");
+        let headers = get_headers(&config.profiles["broken"]);
+        assert!(!headers.contains_key(USER_AGENT));
+        assert!(headers.contains_key(ACCEPT));
+    }
+
+    #[test]
+    fn http_proxy() {
+        let proxy = TestServer::start(|_| String::from("HTTP/1.1 200 OK\r\n\r\nproxied"));
+        let proxy_url = proxy.url("/");
+        let client = http_client(move |_| Some(proxy_url.clone()));
+
+        let body = client
+            .get("http://example.invalid/page")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+
+        assert_eq!(body, "proxied");
+        assert!(proxy.requests()[0].starts_with("GET http://example.invalid/page HTTP/1.1"));
+    }
+
+    /// The hostname is sent to a `socks5h` proxy, never resolved locally.
+    #[test]
+    fn socks5h_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // greeting: version, methods
+            let mut greeting = [0; 2];
+            stream.read_exact(&mut greeting).unwrap();
+            let mut methods = vec![0; greeting[1] as usize];
+            stream.read_exact(&mut methods).unwrap();
+            stream.write_all(&[5, 0]).unwrap();
+
+            // connect: version, command, reserved, address type 3 (domain name)
+            let mut request = [0; 5];
+            stream.read_exact(&mut request).unwrap();
+            let mut host = vec![0; request[4] as usize];
+            stream.read_exact(&mut host).unwrap();
+            let mut port = [0; 2];
+            stream.read_exact(&mut port).unwrap();
+            stream.write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+
+            let mut head = [0; 1024];
+            let _ = stream.read(&mut head).unwrap();
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nonion",
+                )
+                .unwrap();
+            (
+                request[3],
+                String::from_utf8(host).unwrap(),
+                u16::from_be_bytes(port),
+            )
+        });
+
+        let proxy_url = Url::parse(&format!("socks5h://127.0.0.1:{}", port)).unwrap();
+        let client = http_client(move |_| Some(proxy_url.clone()));
+        let body = client
+            .get("http://abcdefghijklmnop.onion/")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+
+        assert_eq!(body, "onion");
+        assert_eq!(
+            server.join().unwrap(),
+            (3, String::from("abcdefghijklmnop.onion"), 80)
+        );
+    }
 }
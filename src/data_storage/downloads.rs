@@ -0,0 +1,590 @@
+//! Downloads streamed to the user's download directory, with progress.
+//! Paused and broken off downloads are resumed with range requests.
+//! <https://tools.ietf.org/html/rfc7233>
+
+use super::data_url::percent_decode;
+use super::download;
+
+use once_cell::sync::Lazy;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use reqwest::{StatusCode, Url};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+
+/// bytes read from the network at once
+const CHUNK_SIZE: usize = 64 * 1024;
+/// start of the error of a navigation that turned into a download
+const DOWNLOAD_STARTED: &str = "Downloading to ";
+
+/// Downloads since the start of the browser, the id is the index
+static DOWNLOADS: Lazy<Mutex<Vec<Download>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// A file saved to the download directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub id: usize,
+    pub url: Url,
+    /// where the file is saved, until it is complete it is `<path>.part`
+    pub path: PathBuf,
+    /// bytes written so far
+    pub received: u64,
+    /// size of the file, if the server told it
+    pub total: Option<u64>,
+    pub state: State,
+    /// `ETag` or `Last-Modified`, a resume only continues the same version of the file
+    validator: Option<String>,
+    /// counts resumes, workers of earlier attempts stop writing
+    attempt: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum State {
+    Running,
+    Paused,
+    Finished,
+    /// can be resumed
+    Failed(String),
+}
+
+/// Downloads since the start of the browser, oldest first.
+pub fn list() -> Vec<Download> {
+    DOWNLOADS.lock().unwrap().clone()
+}
+
+/// Does a response to a navigation download a file instead of showing a page?
+/// Attachments and files of mime types that can't be shown are downloaded.
+pub fn is_download(headers: &HeaderMap) -> bool {
+    let attachment = header_str(headers, header::CONTENT_DISPOSITION).is_some_and(|value| {
+        value
+            .split(';')
+            .next()
+            .is_some_and(|kind| kind.trim().eq_ignore_ascii_case("attachment"))
+    });
+    attachment || !super::displayable(header_str(headers, header::CONTENT_TYPE).unwrap_or(""))
+}
+
+/// Requests `url` and downloads it in the background.
+/// Returns the path it is saved to.
+pub fn start(url: &Url) -> Result<PathBuf, String> {
+    let (response, redirects) = download::request(url, None, HeaderMap::new())?;
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+    let url = redirects.last().map_or(url, |redirect| &redirect.to);
+    start_response(url, response)
+}
+
+/// Downloads the body of a response to `url` in the background.
+/// Returns the path it is saved to.
+pub fn start_response(url: &Url, response: reqwest::blocking::Response) -> Result<PathBuf, String> {
+    let headers = response.headers();
+    let name = file_name(header_str(headers, header::CONTENT_DISPOSITION), url);
+    let validator = header_str(headers, header::ETAG)
+        // `If-Range` needs a strong validator
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header_str(headers, header::LAST_MODIFIED))
+        .map(str::to_string);
+
+    let (path, part) = reserve(&download_dir(), &name)?;
+    let total = response.content_length();
+    let id = add(url, path.clone(), total, validator, State::Running);
+
+    thread::spawn(move || transfer(id, 0, response, part));
+    Ok(path)
+}
+
+/// Saves `content` of `url`, received already, as a finished download.
+/// It is named `name` if given, after the url otherwise.
+/// Returns the path it is saved to.
+pub fn save(url: &Url, name: Option<&str>, content: &[u8]) -> Result<PathBuf, String> {
+    let name = name.map_or_else(|| file_name(None, url), sanitize);
+    let (path, mut part) = reserve(&download_dir(), &name)?;
+    part.write_all(content).map_err(|e| e.to_string())?;
+    fs::rename(part_path(&path), &path).map_err(|e| e.to_string())?;
+
+    let id = add(
+        url,
+        path.clone(),
+        Some(content.len() as u64),
+        None,
+        State::Finished,
+    );
+    DOWNLOADS.lock().unwrap()[id].received = content.len() as u64;
+    Ok(path)
+}
+
+/// Error of a navigation that started a download to `path`.
+pub fn started_error(path: &Path) -> String {
+    format!("{}{}", DOWNLOAD_STARTED, path.display())
+}
+
+/// Path of a download a navigation started, `None` for other errors.
+pub fn started(error: &str) -> Option<&str> {
+    error.strip_prefix(DOWNLOAD_STARTED)
+}
+
+/// Stops a running download, the received part is kept for resuming.
+pub fn pause(id: usize) {
+    if let Some(download) = DOWNLOADS.lock().unwrap().get_mut(id) {
+        if download.state == State::Running {
+            download.state = State::Paused;
+        }
+    }
+}
+
+/// Continues a paused or failed download where it stopped.
+pub fn resume(id: usize) {
+    let attempt = {
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let download = match downloads.get_mut(id) {
+            Some(download) if matches!(download.state, State::Paused | State::Failed(_)) => {
+                download
+            }
+            _ => return,
+        };
+        download.state = State::Running;
+        download.attempt += 1;
+        download.attempt
+    };
+
+    thread::spawn(move || {
+        if let Err(error) = continue_download(id, attempt) {
+            update(id, attempt, |download| {
+                download.state = State::Failed(error)
+            });
+        }
+    });
+}
+
+fn add(
+    url: &Url,
+    path: PathBuf,
+    total: Option<u64>,
+    validator: Option<String>,
+    state: State,
+) -> usize {
+    let mut downloads = DOWNLOADS.lock().unwrap();
+    let id = downloads.len();
+    downloads.push(Download {
+        id,
+        url: url.clone(),
+        path,
+        received: 0,
+        total,
+        state,
+        validator,
+        attempt: 0,
+    });
+    id
+}
+
+/// Changes a download, unless a later attempt took over.
+fn update<F: FnOnce(&mut Download)>(id: usize, attempt: usize, change: F) {
+    let mut downloads = DOWNLOADS.lock().unwrap();
+    if let Some(download) = downloads.get_mut(id).filter(|d| d.attempt == attempt) {
+        change(download);
+    }
+}
+
+/// Requests the rest of a download with `Range`, or all of it again if the file changed.
+fn continue_download(id: usize, attempt: usize) -> Result<(), String> {
+    let Download {
+        url,
+        path,
+        received,
+        validator,
+        ..
+    } = DOWNLOADS.lock().unwrap()[id].clone();
+
+    let mut headers = HeaderMap::new();
+    let range = HeaderValue::from_str(&format!("bytes={}-", received)).expect("valid range");
+    headers.insert(header::RANGE, range);
+    if let Some(validator) = validator.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        headers.insert(header::IF_RANGE, validator);
+    }
+
+    let (response, _) = download::request(&url, None, headers)?;
+    let (offset, total) = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            let total = header_str(response.headers(), header::CONTENT_RANGE)
+                .and_then(|range| range.rsplit('/').next())
+                .and_then(|total| total.parse().ok());
+            (received, total)
+        }
+        // the server doesn't do ranges or the file changed
+        StatusCode::OK => (0, response.content_length()),
+        status => return Err(status.to_string()),
+    };
+
+    let part = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(part_path(&path))
+        .map_err(|e| e.to_string())?;
+    update(id, attempt, |download| {
+        download.received = offset;
+        download.total = total;
+    });
+
+    transfer(id, attempt, response, part);
+    Ok(())
+}
+
+/// Writes `body` to the `.part` file of a download until it ends, is paused or a later attempt took over.
+/// A complete file is moved to its path.
+fn transfer<R: Read>(id: usize, attempt: usize, mut body: R, mut part: File) {
+    let mut buffer = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = body.read(&mut buffer);
+
+        // written while locked, so a resume never sees a stale size
+        let mut downloads = DOWNLOADS.lock().unwrap();
+        let download = &mut downloads[id];
+        if download.attempt != attempt || download.state != State::Running {
+            return;
+        }
+
+        let result = match read {
+            Ok(0)
+                if download
+                    .total
+                    .is_some_and(|total| download.received < total) =>
+            {
+                Err(String::from("Connection closed before the end of the file"))
+            }
+            Ok(0) => {
+                download.state = fs::rename(part_path(&download.path), &download.path)
+                    .map_or_else(|e| State::Failed(e.to_string()), |_| State::Finished);
+                return;
+            }
+            Ok(length) => part
+                .write_all(&buffer[..length])
+                .map(|_| download.received += length as u64)
+                .map_err(|e| e.to_string()),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => Ok(()),
+            Err(error) => Err(error.to_string()),
+        };
+        if let Err(error) = result {
+            download.state = State::Failed(error);
+            return;
+        }
+    }
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+/// Finds a path for the file `name` in `dir` that doesn't overwrite anything,
+/// `name (1).ext` if `name.ext` exists, and creates its `.part` file.
+fn reserve(dir: &Path, name: &str) -> Result<(PathBuf, File), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    let mut number = 0;
+    loop {
+        let path = match number {
+            0 => dir.join(name),
+            _ => dir.join(format!("{} ({}){}", stem, number, extension)),
+        };
+        number += 1;
+        if path.exists() {
+            continue;
+        }
+        // created at once, downloads of the same name at the same time get different paths
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(part_path(&path))
+        {
+            Ok(part) => return Ok((path, part)),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+}
+
+/// The download directory: `$XDG_DOWNLOAD_DIR`, the one set in `user-dirs.dirs`, or `~/Downloads`
+/// <https://www.freedesktop.org/wiki/Software/xdg-user-dirs/>
+pub fn download_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("XDG_DOWNLOAD_DIR").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir);
+    }
+    let home = match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return PathBuf::from("downloads"),
+    };
+
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home.join(".config"), PathBuf::from);
+    fs::read_to_string(config.join("user-dirs.dirs"))
+        .ok()
+        .and_then(|source| user_dirs_download(&source, &home))
+        .unwrap_or_else(|| home.join("Downloads"))
+}
+
+/// `XDG_DOWNLOAD_DIR="$HOME/Downloads"` of a `user-dirs.dirs` file
+fn user_dirs_download(source: &str, home: &Path) -> Option<PathBuf> {
+    let value = source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("XDG_DOWNLOAD_DIR="))?
+        .trim()
+        .trim_matches('"');
+
+    if let Some(path) = value.strip_prefix("$HOME") {
+        Some(home.join(path.trim_start_matches('/')))
+    } else if value.starts_with('/') {
+        Some(PathBuf::from(value))
+    } else {
+        None
+    }
+}
+
+/// Name of a downloaded file, from `Content-Disposition` or the end of the url path.
+fn file_name(content_disposition: Option<&str>, url: &Url) -> String {
+    let name = content_disposition
+        .and_then(disposition_file_name)
+        .or_else(|| {
+            let segment = url.path_segments()?.next_back()?;
+            Some(String::from_utf8_lossy(&percent_decode(segment)).into_owned())
+        })
+        .filter(|name| !name.trim().is_empty())
+        .or_else(|| url.host_str().map(str::to_string))
+        .unwrap_or_default();
+    sanitize(&name)
+}
+
+/// Path separators and leading dots are replaced, the file stays in the download directory.
+fn sanitize(name: &str) -> String {
+    let name = name
+        .trim()
+        .trim_start_matches('.')
+        .replace(|c: char| c == '/' || c == '\\' || c.is_control(), "_");
+    if name.is_empty() {
+        String::from("download")
+    } else {
+        name
+    }
+}
+
+/// `filename` parameter of a `Content-Disposition` header,
+/// `filename*=UTF-8''na%C3%AFve.txt` is preferred over `filename="naive.txt"`
+/// <https://tools.ietf.org/html/rfc6266#section-4.3>
+fn disposition_file_name(value: &str) -> Option<String> {
+    let mut plain = None;
+    let mut extended = None;
+
+    for parameter in parameters(value) {
+        let (name, value) = match parameter.split_once('=') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => continue,
+        };
+        match name.as_str() {
+            "filename*" => {
+                // `charset'language'percent-encoded`
+                let mut parts = value.splitn(3, '\'');
+                let (charset, encoded) = match (parts.next(), parts.nth(1)) {
+                    (Some(charset), Some(encoded)) => (charset, encoded),
+                    _ => continue,
+                };
+                let bytes = percent_decode(encoded);
+                extended = if charset.eq_ignore_ascii_case("utf-8") {
+                    String::from_utf8(bytes).ok()
+                } else {
+                    // ISO-8859-1
+                    Some(bytes.into_iter().map(char::from).collect())
+                };
+            }
+            "filename" => {
+                plain = Some(match value.strip_prefix('"') {
+                    Some(quoted) => quoted
+                        .strip_suffix('"')
+                        .unwrap_or(quoted)
+                        .replace("\\\"", "\"")
+                        .replace("\\\\", "\\"),
+                    None => value.to_string(),
+                })
+            }
+            _ => {}
+        }
+    }
+
+    extended.or(plain)
+}
+
+/// Parameters of a header value, split at `;` outside of quotes
+fn parameters(value: &str) -> Vec<&str> {
+    let mut parameters = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                parameters.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parameters.push(&value[start..]);
+    parameters
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod manager {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(
+            file_name(
+                Some("attachment; filename=\"a;b \\\"c\\\".pdf\""),
+                &url("https://example.com/x")
+            ),
+            "a;b \"c\".pdf"
+        );
+        assert_eq!(
+            file_name(
+                Some("attachment; filename=\"naive.txt\"; filename*=UTF-8''na%C3%AFve.txt"),
+                &url("https://example.com/x")
+            ),
+            "naïve.txt"
+        );
+        assert_eq!(
+            file_name(
+                Some("attachment; filename=../../.bashrc"),
+                &url("https://e.com/")
+            ),
+            "_.._.bashrc"
+        );
+        assert_eq!(
+            file_name(None, &url("https://example.com/files/My%20Report.pdf?v=2")),
+            "My Report.pdf"
+        );
+        assert_eq!(file_name(None, &url("https://example.com/")), "example.com");
+        assert_eq!(file_name(None, &url("data:,")), "download");
+    }
+
+    #[test]
+    fn attachments() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        assert!(!is_download(&headers));
+
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("Attachment; filename=page.html"),
+        );
+        assert!(is_download(&headers));
+
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_static("inline"),
+        );
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/zip"),
+        );
+        assert!(is_download(&headers));
+    }
+
+    #[test]
+    fn user_dirs() {
+        let home = Path::new("/home/user");
+        let source = "# written by xdg-user-dirs-update\nXDG_DESKTOP_DIR=\"$HOME/Desktop\"\nXDG_DOWNLOAD_DIR=\"$HOME/Files/Downloads\"\n";
+
+        assert_eq!(
+            user_dirs_download(source, home),
+            Some(PathBuf::from("/home/user/Files/Downloads"))
+        );
+        assert_eq!(
+            user_dirs_download("XDG_DOWNLOAD_DIR=\"/data/dl\"", home),
+            Some(PathBuf::from("/data/dl"))
+        );
+        assert_eq!(user_dirs_download("XDG_MUSIC_DIR=\"$HOME\"", home), None);
+    }
+
+    #[test]
+    fn not_clobbered() {
+        let dir = env::temp_dir().join("prowser-downloads-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("report.pdf"), "old").unwrap();
+
+        let (first, _) = reserve(&dir, "report.pdf").unwrap();
+        let (second, _) = reserve(&dir, "report.pdf").unwrap();
+        let (plain, _) = reserve(&dir, "README").unwrap();
+
+        assert_eq!(first, dir.join("report (1).pdf"));
+        assert_eq!(second, dir.join("report (2).pdf"));
+        assert_eq!(plain, dir.join("README"));
+        assert!(dir.join("report (1).pdf.part").exists());
+        assert_eq!(fs::read_to_string(dir.join("report.pdf")).unwrap(), "old");
+    }
+
+    #[test]
+    fn paused_and_finished() {
+        let dir = env::temp_dir().join("prowser-downloads-transfer-test");
+        let _ = fs::remove_dir_all(&dir);
+        let (path, part) = reserve(&dir, "file.bin").unwrap();
+        let id = add(
+            &url("https://example.com/file.bin"),
+            path.clone(),
+            Some(10),
+            None,
+            State::Running,
+        );
+
+        // broken off after 4 of 10 bytes
+        transfer(id, 0, &b"abcd"[..], part);
+        assert_eq!(
+            list()[id].state,
+            State::Failed(String::from("Connection closed before the end of the file"))
+        );
+        assert_eq!(list()[id].received, 4);
+
+        // the rest, as a `206 Partial Content` would send it
+        DOWNLOADS.lock().unwrap()[id].state = State::Running;
+        let part = OpenOptions::new()
+            .append(true)
+            .open(part_path(&path))
+            .unwrap();
+        transfer(id, 0, &b"efghij"[..], part);
+
+        assert_eq!(list()[id].state, State::Finished);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abcdefghij");
+        assert!(!part_path(&path).exists());
+
+        // a paused download doesn't write
+        DOWNLOADS.lock().unwrap()[id].state = State::Paused;
+        let part = File::create(part_path(&path)).unwrap();
+        transfer(id, 0, &b"more"[..], part);
+        assert_eq!(list()[id].received, 10);
+    }
+}
@@ -0,0 +1,142 @@
+//! Character encoding detection and decoding
+//! <https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding>
+
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::fs;
+
+/// bytes of a document searched for `<meta charset>`
+const PRESCAN_LENGTH: usize = 1024;
+
+/// Reads a file and decodes it to UTF-8.
+/// `content_type` is the `Content-Type` it was served with, if any.
+pub fn read_text(path: &str, content_type: &str) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    Ok(decode(&bytes, content_type))
+}
+
+/// Decodes `bytes` to UTF-8. The encoding is taken from, in this order:
+/// a byte order mark, the charset of `content_type`, `<meta charset>` of html documents,
+/// and falls back to UTF-8 if the bytes are valid UTF-8 and windows-1252 otherwise.
+pub fn decode(bytes: &[u8], content_type: &str) -> String {
+    let encoding = Encoding::for_bom(bytes)
+        .map(|(encoding, _)| encoding)
+        .or_else(|| charset(content_type))
+        .or_else(|| {
+            if content_type.is_empty() || content_type.starts_with("text/html") {
+                prescan(bytes)
+            } else {
+                None
+            }
+        })
+        .unwrap_or_else(|| {
+            if std::str::from_utf8(bytes).is_ok() {
+                UTF_8
+            } else {
+                WINDOWS_1252
+            }
+        });
+
+    // the BOM is removed
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Encoding of the `charset` parameter, `text/html; charset=ISO-8859-1`
+fn charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|parameter| {
+            let (name, value) = parameter.split_once('=')?;
+            if name.trim().eq_ignore_ascii_case("charset") {
+                Some(value.trim().trim_matches(['"', '\'']))
+            } else {
+                None
+            }
+        })
+        .find_map(|label| Encoding::for_label(label.as_bytes()))
+}
+
+/// Looks for `<meta charset="…">` and `<meta http-equiv="Content-Type" content="…; charset=…">`
+/// at the start of a document.
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(PRESCAN_LENGTH)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+
+    let encoding = head.match_indices("<meta").find_map(|(start, _)| {
+        let tag = &head[start..];
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let value = &tag[tag.find("charset")? + "charset".len()..];
+        let value = value.trim_start().strip_prefix('=')?;
+        let value = value.trim_start().trim_start_matches(['"', '\'']);
+        let label = &value[..value
+            .find(|c: char| c == '"' || c == '\'' || c == ';' || c == '/' || c.is_whitespace())
+            .unwrap_or(value.len())];
+        Encoding::for_label(label.as_bytes())
+    })?;
+
+    // a document that could be read so far is not UTF-16, x-user-defined is windows-1252
+    if encoding == encoding_rs::UTF_16BE || encoding == encoding_rs::UTF_16LE {
+        Some(UTF_8)
+    } else if encoding == encoding_rs::X_USER_DEFINED {
+        Some(WINDOWS_1252)
+    } else {
+        Some(encoding)
+    }
+}
+
+#[cfg(test)]
+mod detect {
+    use super::*;
+
+    #[test]
+    fn content_type_charset() {
+        assert_eq!(decode(b"caf\xe9", "text/html; charset=ISO-8859-1"), "café");
+        assert_eq!(decode(b"caf\xe9", "text/plain;Charset=\"latin1\""), "café");
+    }
+
+    #[test]
+    fn bom() {
+        assert_eq!(
+            decode(b"\xef\xbb\xbfcaf\xc3\xa9", "text/html; charset=ISO-8859-1"),
+            "café"
+        );
+        assert_eq!(decode(b"\xff\xfea\x00", ""), "a");
+    }
+
+    #[test]
+    fn meta_charset() {
+        assert_eq!(
+            decode(
+                b"<html><head><meta charset='shift_jis'>\x93\xfa\x96\x7b",
+                "text/html"
+            ),
+            "<html><head><meta charset='shift_jis'>日本"
+        );
+        assert_eq!(
+            decode(
+                b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=euc-kr\">\xc7\xd1",
+                ""
+            ),
+            "<meta http-equiv=\"Content-Type\" content=\"text/html; charset=euc-kr\">한"
+        );
+    }
+
+    #[test]
+    fn meta_only_in_html() {
+        assert_eq!(
+            decode(b"<meta charset=shift_jis> caf\xc3\xa9", "text/plain"),
+            "<meta charset=shift_jis> café"
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        assert_eq!(decode("café".as_bytes(), "text/html"), "café");
+        assert_eq!(decode(b"caf\xe9 \x80", "text/html"), "café €");
+    }
+
+    #[test]
+    fn iso_8859_2() {
+        assert_eq!(decode(b"\xb1", "text/html; charset=iso-8859-2"), "ą");
+    }
+}
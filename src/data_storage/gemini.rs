@@ -0,0 +1,264 @@
+//! Gemini client
+//! <https://gemini.circumlunar.space/docs/specification.html>
+
+use super::proxy;
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 1965;
+const MAX_REDIRECTS: usize = 5;
+/// pinned certificates
+const KNOWN_HOSTS_FILE: &str = "data/known_hosts";
+
+static KNOWN_HOSTS: Lazy<Mutex<KnownHosts>> =
+    Lazy::new(|| Mutex::new(KnownHosts::load(KNOWN_HOSTS_FILE)));
+
+/// A gemini response
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    /// url of the response, after redirects
+    pub url: Url,
+    /// two digit status code
+    pub status: u8,
+    /// prompt, mime type, redirect target or error message, depending on `status`
+    pub meta: String,
+    pub body: Vec<u8>,
+}
+
+/// Trust on first use: the certificate fingerprint seen on the first visit of a host
+/// is pinned, a different certificate later on is rejected.
+#[derive(Debug, Default)]
+struct KnownHosts {
+    /// `host:port` → hex encoded SHA-256 fingerprint
+    hosts: HashMap<String, String>,
+}
+
+impl KnownHosts {
+    /// One host per line: `host:port fingerprint`
+    fn load(path: &str) -> Self {
+        let mut known_hosts = Self::default();
+        if let Ok(content) = fs::read_to_string(path) {
+            for line in content.lines() {
+                let mut cells = line.split_whitespace();
+                if let (Some(host), Some(fingerprint)) = (cells.next(), cells.next()) {
+                    known_hosts
+                        .hosts
+                        .insert(host.to_string(), fingerprint.to_string());
+                }
+            }
+        }
+        known_hosts
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        let content = self
+            .hosts
+            .iter()
+            .map(|(host, fingerprint)| format!("{} {}\n", host, fingerprint))
+            .collect::<String>();
+        fs::write(path, content).map_err(|e| e.to_string())
+    }
+
+    /// Pins unknown hosts. Returns `false` if the host presented a different certificate before.
+    fn verify(&mut self, host: &str, fingerprint: &str) -> bool {
+        match self.hosts.get(host) {
+            Some(pinned) => pinned == fingerprint,
+            None => {
+                self.hosts.insert(host.to_string(), fingerprint.to_string());
+                true
+            }
+        }
+    }
+}
+
+/// Requests a gemini url, following redirects.
+pub fn request(url: &Url) -> Result<Response, String> {
+    let mut url = url.clone();
+
+    for _ in 0..=MAX_REDIRECTS {
+        let responce = request_once(&url)?;
+
+        if responce.status / 10 != 3 {
+            return Ok(responce);
+        }
+
+        let target = url.join(&responce.meta).map_err(|e| e.to_string())?;
+        if target.scheme() != "gemini" {
+            return Err(format!("Refusing to follow redirect to {}", target));
+        }
+        url = target;
+    }
+
+    Err(String::from("Too many redirects"))
+}
+
+/// Sends a single request over TLS, checking the certificate against the pinned one.
+fn request_once(url: &Url) -> Result<Response, String> {
+    let host = url.host_str().ok_or("No host in url")?;
+    let port = url.port().unwrap_or(DEFAULT_PORT);
+
+    let stream = proxy::connect(host, port)?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .map_err(|e| e.to_string())?;
+
+    // capsules mostly use self-signed certificates, they are checked by pinning instead
+    let connector = native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let stream = connector.connect(host, stream).map_err(|e| e.to_string())?;
+
+    let certificate = stream
+        .peer_certificate()
+        .map_err(|e| e.to_string())?
+        .ok_or("Server sent no certificate")?
+        .to_der()
+        .map_err(|e| e.to_string())?;
+    let fingerprint = hex(&Sha256::digest(&certificate));
+
+    {
+        let mut known_hosts = KNOWN_HOSTS.lock().unwrap();
+        let host_port = format!("{}:{}", host, port);
+        if !known_hosts.verify(&host_port, &fingerprint) {
+            return Err(format!(
+                "The certificate of {} changed since the last visit. \
+                 If this is expected, remove it from {}.",
+                host_port, KNOWN_HOSTS_FILE
+            ));
+        }
+        let _ = known_hosts.save(KNOWN_HOSTS_FILE);
+    }
+
+    exchange(stream, url)
+}
+
+/// Sends the request line and reads the response.
+fn exchange<S: Read + Write>(mut stream: S, url: &Url) -> Result<Response, String> {
+    let request = format!("{}\r\n", url);
+    if request.len() > 1026 {
+        return Err(String::from("Url is longer than 1024 bytes"));
+    }
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut header = String::new();
+    reader.read_line(&mut header).map_err(|e| e.to_string())?;
+    let header = header.trim_end_matches(&['\r', '\n'][..]);
+
+    let status = header
+        .get(..2)
+        .and_then(|status| status.parse::<u8>().ok())
+        .filter(|status| *status >= 10)
+        .ok_or_else(|| format!("Invalid response header: {}", header))?;
+    let meta = header[2..].trim().to_string();
+
+    let mut body = Vec::new();
+    if status / 10 == 2 {
+        reader.read_to_end(&mut body).map_err(|e| e.to_string())?;
+    }
+
+    Ok(Response {
+        url: url.clone(),
+        status,
+        meta,
+        body,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod client {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    /// Plain text stand-in for a gemini server on loopback, the TLS layer is left out
+    fn serve(responce: &'static str) -> (TcpStream, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut request)
+                .unwrap();
+            (&stream).write_all(responce.as_bytes()).unwrap();
+            request
+        });
+
+        (TcpStream::connect(address).unwrap(), server)
+    }
+
+    #[test]
+    fn success() {
+        let (stream, server) = serve("20 text/gemini; charset=utf-8\r\n# Hello\n=> /a Link\n");
+        let url = Url::parse("gemini://localhost/index.gmi").unwrap();
+
+        let responce = exchange(stream, &url).unwrap();
+
+        assert_eq!(server.join().unwrap(), "gemini://localhost/index.gmi\r\n");
+        assert_eq!(responce.status, 20);
+        assert_eq!(responce.meta, "text/gemini; charset=utf-8");
+        assert_eq!(responce.body, b"# Hello\n=> /a Link\n");
+    }
+
+    #[test]
+    fn input() {
+        let (stream, _) = serve("10 Your name?\r\n");
+        let url = Url::parse("gemini://localhost/greet").unwrap();
+
+        let responce = exchange(stream, &url).unwrap();
+
+        assert_eq!(responce.status, 10);
+        assert_eq!(responce.meta, "Your name?");
+        assert!(responce.body.is_empty());
+    }
+
+    #[test]
+    fn invalid_header() {
+        let (stream, _) = serve("hello\r\n");
+        let url = Url::parse("gemini://localhost/").unwrap();
+
+        assert!(exchange(stream, &url).is_err());
+    }
+
+    #[test]
+    fn tofu() {
+        let mut known_hosts = KnownHosts::default();
+
+        assert!(known_hosts.verify("localhost:1965", "aa"));
+        assert!(known_hosts.verify("localhost:1965", "aa"));
+        assert!(!known_hosts.verify("localhost:1965", "bb"));
+        assert!(known_hosts.verify("localhost:1966", "bb"));
+    }
+
+    #[test]
+    fn known_hosts_persisted() {
+        let path = std::env::temp_dir().join("prowser-known-hosts-test");
+        let path = path.to_str().unwrap();
+
+        let mut known_hosts = KnownHosts::default();
+        known_hosts.verify("localhost:1965", "aa");
+        known_hosts.save(path).unwrap();
+
+        let mut loaded = KnownHosts::load(path);
+        let _ = fs::remove_file(path);
+
+        assert!(!loaded.verify("localhost:1965", "bb"));
+    }
+}
@@ -0,0 +1,312 @@
+//! Gopher client
+//! <https://tools.ietf.org/html/rfc1436>, urls: <https://tools.ietf.org/html/rfc4266>
+
+use super::proxy;
+
+use reqwest::Url;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 70;
+
+/// Where a gopher url points to
+#[derive(Debug, PartialEq)]
+pub struct Target {
+    pub host: String,
+    pub port: u16,
+    /// item type, `1` (menu) if the url has no path
+    pub kind: char,
+    pub selector: String,
+    /// search string of type `7` items
+    pub query: Option<String>,
+}
+
+impl Target {
+    /// `gopher://host:port/<type><selector>%09<query>`
+    pub fn parse(url: &Url) -> Result<Self, String> {
+        if url.scheme() != "gopher" {
+            return Err(format!("Not a gopher url: {}", url));
+        }
+        let host = url.host_str().ok_or("No host in url")?.to_string();
+        let port = url.port().unwrap_or(DEFAULT_PORT);
+
+        let path = percent_decode(url.path().get(1..).unwrap_or_default());
+        let mut chars = path.chars();
+        let kind = chars.next().unwrap_or('1');
+        let rest = chars.as_str();
+        let (selector, query) = match rest.find('\t') {
+            Some(tab) => (&rest[..tab], Some(rest[tab + 1..].to_string())),
+            None => (rest, None),
+        };
+
+        Ok(Self {
+            host,
+            port,
+            kind,
+            selector: selector.to_string(),
+            query,
+        })
+    }
+}
+
+/// A line of a gophermap
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    pub kind: char,
+    pub display: String,
+    pub selector: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Item {
+    /// Url of the item, `None` for informational lines and errors.
+    pub fn url(&self) -> Option<Url> {
+        let url = match self.kind {
+            'i' | '3' => return None,
+            // telnet sessions aren't supported, but still links
+            '8' | 'T' => format!("telnet://{}:{}", self.host, self.port),
+            'h' if self.selector.starts_with("URL:") => self.selector[4..].to_string(),
+            _ => format!(
+                "gopher://{}:{}/{}{}",
+                self.host,
+                self.port,
+                self.kind,
+                percent_encode(&self.selector)
+            ),
+        };
+        Url::parse(&url).ok()
+    }
+}
+
+/// Parses a menu (type `1`), skipping malformed lines.
+pub fn parse_menu(source: &str) -> Vec<Item> {
+    let mut items = Vec::new();
+
+    for line in source.lines() {
+        if line == "." {
+            break;
+        }
+        let mut chars = line.chars();
+        let kind = match chars.next() {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let mut cells = chars.as_str().split('\t');
+        let display = cells.next().unwrap_or_default().to_string();
+        let selector = cells.next().unwrap_or_default().to_string();
+        let host = cells.next().unwrap_or_default().to_string();
+        let port = cells.next().and_then(|port| port.trim().parse().ok());
+
+        match port {
+            Some(port) => items.push(Item {
+                kind,
+                display,
+                selector,
+                host,
+                port,
+            }),
+            // lots of servers send info lines without the other fields
+            None if kind == 'i' => items.push(Item {
+                kind,
+                display,
+                selector,
+                host,
+                port: DEFAULT_PORT,
+            }),
+            None => {}
+        }
+    }
+
+    items
+}
+
+/// Fetches the selector of `target`.
+/// Text items (menus, search results and files) have the terminating `.` line removed.
+pub fn fetch(target: &Target) -> Result<Vec<u8>, String> {
+    let mut stream = proxy::connect(&target.host, target.port)?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .map_err(|e| e.to_string())?;
+
+    let request = match &target.query {
+        Some(query) => format!("{}\t{}\r\n", target.selector, query),
+        None => format!("{}\r\n", target.selector),
+    };
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut body = Vec::new();
+    stream.read_to_end(&mut body).map_err(|e| e.to_string())?;
+
+    if matches!(target.kind, '0' | '1' | '7') {
+        for terminator in &[&b".\r\n"[..], b".\n"] {
+            if body.ends_with(terminator) {
+                body.truncate(body.len() - terminator.len());
+                break;
+            }
+        }
+    }
+
+    Ok(body)
+}
+
+/// Url with `query` as search string of a type `7` item.
+pub fn with_query(url: &Url, query: &str) -> Url {
+    let mut url = url.clone();
+    url.set_path(&format!("{}%09{}", url.path(), percent_encode(query)));
+    url
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = text
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod client {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Gopher server on loopback answering a single request.
+    /// Returns the port and the received request line.
+    fn serve(responce: &'static str) -> (u16, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut request)
+                .unwrap();
+            (&stream).write_all(responce.as_bytes()).unwrap();
+            request
+        });
+
+        (port, server)
+    }
+
+    #[test]
+    fn target() {
+        assert_eq!(
+            Target::parse(&Url::parse("gopher://example.com").unwrap()).unwrap(),
+            Target {
+                host: String::from("example.com"),
+                port: 70,
+                kind: '1',
+                selector: String::new(),
+                query: None,
+            }
+        );
+        assert_eq!(
+            Target::parse(&Url::parse("gopher://example.com:7070/7/search%09rust%20lang").unwrap())
+                .unwrap(),
+            Target {
+                host: String::from("example.com"),
+                port: 7070,
+                kind: '7',
+                selector: String::from("/search"),
+                query: Some(String::from("rust lang")),
+            }
+        );
+    }
+
+    #[test]
+    fn menu() {
+        let items = parse_menu(
+            "iWelcome\tfake\t(NULL)\t0\r\n\
+             1Docs\t/docs\texample.com\t70\r\n\
+             0About\t/about.txt\texample.com\t70\r\n\
+             broken line\r\n\
+             iNo fields\r\n\
+             .\r\n",
+        );
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].url(), None);
+        assert_eq!(
+            items[1].url(),
+            Some(Url::parse("gopher://example.com:70/1/docs").unwrap())
+        );
+        assert_eq!(items[2].display, "About");
+        assert_eq!(items[3].display, "No fields");
+    }
+
+    #[test]
+    fn html_link() {
+        let items = parse_menu("hWeb\tURL:https://example.com/\texample.com\t70\r\n");
+
+        assert_eq!(
+            items[0].url(),
+            Some(Url::parse("https://example.com/").unwrap())
+        );
+    }
+
+    #[test]
+    fn fetch_menu() {
+        let (port, server) = serve("1Docs\t/docs\tlocalhost\t70\r\n.\r\n");
+        let url = Url::parse(&format!("gopher://127.0.0.1:{}/1/", port)).unwrap();
+        let target = Target::parse(&url).unwrap();
+
+        let body = fetch(&target).unwrap();
+
+        assert_eq!(server.join().unwrap(), "/\r\n");
+        assert_eq!(body, b"1Docs\t/docs\tlocalhost\t70\r\n");
+    }
+
+    #[test]
+    fn fetch_search() {
+        let (port, server) = serve("iNo results\t\t\t0\r\n.\r\n");
+        let url = Url::parse(&format!("gopher://127.0.0.1:{}/7/search", port)).unwrap();
+        let url = with_query(&url, "a b");
+        let target = Target::parse(&url).unwrap();
+
+        fetch(&target).unwrap();
+
+        assert_eq!(server.join().unwrap(), "/search\ta b\r\n");
+    }
+
+    #[test]
+    fn fetch_binary() {
+        let (port, _) = serve("GIF89a.\r\n");
+        let url = Url::parse(&format!("gopher://127.0.0.1:{}/g/a.gif", port)).unwrap();
+        let target = Target::parse(&url).unwrap();
+
+        assert_eq!(fetch(&target).unwrap(), b"GIF89a.\r\n");
+    }
+}
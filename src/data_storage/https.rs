@@ -0,0 +1,128 @@
+//! HTTPS-only mode: plain http requests are upgraded to https,
+//! unless the user chose to continue over http for a site.
+
+use super::cookies::site;
+use crate::config;
+
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// sites allowed over plain http, one per line
+const ALLOWED_FILE: &str = "data/http_allowed";
+/// start of the error of a failed upgrade, followed by the http url
+const UPGRADE_FAILED: &str = "HTTPS not available for ";
+
+static ALLOWED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(load(ALLOWED_FILE)));
+
+fn load(path: &str) -> HashSet<String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Https url to request instead of `url`, `None` if it stays as it is.
+pub fn upgrade(url: &Url) -> Option<Url> {
+    if !config::get().https_only || url.scheme() != "http" || is_local(url) {
+        return None;
+    }
+    if ALLOWED.lock().unwrap().contains(&site(url)) {
+        return None;
+    }
+    upgraded(url)
+}
+
+/// `url` with the https scheme, an explicit port stays.
+fn upgraded(url: &Url) -> Option<Url> {
+    let mut upgraded = url.clone();
+    upgraded.set_scheme("https").ok()?;
+    Some(upgraded)
+}
+
+/// Local hosts are left alone, like other browsers do.
+fn is_local(url: &Url) -> bool {
+    match url.host_str() {
+        Some("localhost") => true,
+        Some(host) => match host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+        {
+            Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+            Ok(IpAddr::V6(ip)) => ip.is_loopback(),
+            Err(_) => host.ends_with(".localhost") || host.ends_with(".local"),
+        },
+        None => true,
+    }
+}
+
+/// Remembers to load the site of `url` over plain http.
+pub fn allow_http(url: &Url) -> Result<(), String> {
+    let mut allowed = ALLOWED.lock().unwrap();
+    if allowed.insert(site(url)) {
+        let mut sites = allowed.iter().cloned().collect::<Vec<_>>();
+        sites.sort();
+        fs::create_dir_all("data").map_err(|e| e.to_string())?;
+        fs::write(ALLOWED_FILE, sites.join("\n") + "\n").map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Error for an upgraded request of the http `url` that failed.
+pub fn upgrade_error(url: &Url, error: &str) -> String {
+    format!("{}{}\n{}", UPGRADE_FAILED, url, error)
+}
+
+/// Http url of an error returned by `upgrade_error`.
+pub fn failed_upgrade(error: &str) -> Option<&str> {
+    error.strip_prefix(UPGRADE_FAILED)?.lines().next()
+}
+
+#[cfg(test)]
+mod upgrades {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn https_url() {
+        assert_eq!(
+            upgraded(&url("http://example.com/a?b#c")).unwrap().as_str(),
+            "https://example.com/a?b#c"
+        );
+        assert_eq!(
+            upgraded(&url("http://example.com:80/")).unwrap().as_str(),
+            "https://example.com/"
+        );
+        assert_eq!(
+            upgraded(&url("http://example.com:8080/")).unwrap().as_str(),
+            "https://example.com:8080/"
+        );
+    }
+
+    #[test]
+    fn local_hosts() {
+        assert!(is_local(&url("http://localhost:8000/")));
+        assert!(is_local(&url("http://127.0.0.1/")));
+        assert!(is_local(&url("http://192.168.1.1/")));
+        assert!(is_local(&url("http://[::1]/")));
+        assert!(is_local(&url("http://printer.local/")));
+        assert!(!is_local(&url("http://example.com/")));
+        assert!(!is_local(&url("http://93.184.216.34/")));
+    }
+
+    #[test]
+    fn error_roundtrip() {
+        let error = upgrade_error(&url("http://example.com/page"), "connection refused");
+        assert_eq!(failed_upgrade(&error), Some("http://example.com/page"));
+        assert_eq!(failed_upgrade("404 Not Found"), None);
+    }
+}
@@ -0,0 +1,261 @@
+//! Concurrent fetching of subresources: stylesheets, images and favicons
+
+use super::{Kind, Resource};
+
+use reqwest::Url;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// open connections per host at the same time, like other browsers
+const MAX_PER_HOST: usize = 6;
+/// open connections overall
+const MAX_CONNECTIONS: usize = 16;
+
+/// Subresources of a page, fetched ahead of styling and painting.
+/// Uncached responses are removed along with them.
+#[derive(Debug, Default)]
+pub struct Resources {
+    /// url of the page the resources are loaded for
+    top_level: Option<Url>,
+    fetched: HashMap<Url, Result<Resource, String>>,
+    /// uncached responses of resources that weren't fetched ahead
+    temporary: Mutex<Vec<Resource>>,
+    /// set when loading the page is cancelled, nothing is fetched anymore
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Resources {
+    pub fn new(top_level: Option<&Url>, cancelled: Arc<AtomicBool>) -> Self {
+        Self {
+            top_level: top_level.cloned(),
+            fetched: HashMap::new(),
+            temporary: Mutex::new(Vec::new()),
+            cancelled,
+        }
+    }
+
+    /// Fetches `urls` of the given kind concurrently, skipping already fetched ones.
+    pub fn fetch(&mut self, urls: Vec<Url>, kind: Kind) {
+        let urls = urls
+            .into_iter()
+            .filter(|url| !self.fetched.contains_key(url))
+            .collect();
+        let top_level = self.top_level.as_ref();
+
+        self.fetched
+            .extend(fetch_parallel(urls, &self.cancelled, |url| {
+                super::locate(url, top_level, kind)
+            }));
+    }
+
+    /// Path of a resource.
+    /// Resources that weren't fetched ahead are downloaded now.
+    pub fn path(&self, url: &Url, accepted_mime_types: Vec<&str>) -> Result<String, String> {
+        self.get(url, accepted_mime_types)
+            .map(|resource| resource.path)
+    }
+
+    /// Decoded content of a resource.
+    pub fn content(&self, url: &Url, accepted_mime_types: Vec<&str>) -> Result<String, String> {
+        let resource = self.get(url, accepted_mime_types)?;
+        super::encoding::read_text(&resource.path, &resource.mime_type)
+    }
+
+    /// Resource as a `data:` url, to be inlined into a saved page.
+    pub fn data_url(&self, url: &Url, accepted_mime_types: Vec<&str>) -> Result<String, String> {
+        let resource = self.get(url, accepted_mime_types)?;
+        let body = std::fs::read(&resource.path).map_err(|e| e.to_string())?;
+        Ok(super::data_url::encode(&resource.mime_type, &body))
+    }
+
+    /// Path and mime type of a resource.
+    /// On wrong mime type, returns error with path to file.
+    fn get(&self, url: &Url, accepted_mime_types: Vec<&str>) -> Result<Resource, String> {
+        let resource = match self.fetched.get(url) {
+            Some(resource) => resource.clone()?,
+            None if self.cancelled.load(Ordering::SeqCst) => return Err(String::from("Cancelled")),
+            None => {
+                let resource =
+                    super::locate(url, self.top_level.as_ref(), kind(&accepted_mime_types))?;
+                if resource.temporary {
+                    self.temporary.lock().unwrap().push(resource.clone());
+                }
+                resource
+            }
+        };
+
+        if super::check_mimetype(&resource.mime_type, accepted_mime_types) {
+            Ok(resource)
+        } else {
+            Err(resource.path)
+        }
+    }
+}
+
+impl Drop for Resources {
+    fn drop(&mut self) {
+        let fetched = self
+            .fetched
+            .values()
+            .filter_map(|resource| resource.as_ref().ok());
+        for resource in fetched.chain(self.temporary.get_mut().unwrap().iter()) {
+            resource.used();
+        }
+    }
+}
+
+/// Runs `fetch` for every url on a pool of threads,
+/// with at most `MAX_PER_HOST` running for the same host.
+/// Once `cancelled` is set, the urls left aren't fetched.
+fn fetch_parallel<T, F>(
+    urls: Vec<Url>,
+    cancelled: &AtomicBool,
+    fetch: F,
+) -> HashMap<Url, Result<T, String>>
+where
+    T: Send,
+    F: Fn(&Url) -> Result<T, String> + Sync,
+{
+    let mut seen = HashSet::new();
+    let queue = urls
+        .into_iter()
+        .filter(|url| seen.insert(url.clone()))
+        .collect::<VecDeque<_>>();
+    let threads = queue.len().min(MAX_CONNECTIONS);
+
+    // queued urls, running fetches per host
+    let state = Mutex::new((queue, HashMap::<String, usize>::new()));
+    let finished = Condvar::new();
+    let results = Mutex::new(HashMap::new());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let (url, url_host) = {
+                    let mut state = state.lock().unwrap();
+                    loop {
+                        if state.0.is_empty() || cancelled.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        let (queue, running) = &mut *state;
+                        let next = queue.iter().position(|url| {
+                            running.get(&host(url)).copied().unwrap_or(0) < MAX_PER_HOST
+                        });
+                        if let Some(next) = next {
+                            let url = queue.remove(next).expect("index to be in queue");
+                            let url_host = host(&url);
+                            *running.entry(url_host.clone()).or_default() += 1;
+                            break (url, url_host);
+                        }
+                        // every queued host is busy
+                        state = finished.wait(state).unwrap();
+                    }
+                };
+
+                let result = fetch(&url);
+                results.lock().unwrap().insert(url, result);
+
+                if let Some(count) = state.lock().unwrap().1.get_mut(&url_host) {
+                    *count -= 1;
+                }
+                finished.notify_all();
+            });
+        }
+    });
+
+    results.into_inner().unwrap()
+}
+
+/// Kind of a resource for the content blocker, from the mime types it is accepted as
+fn kind(accepted_mime_types: &[&str]) -> Kind {
+    if accepted_mime_types.contains(&"text/css") {
+        Kind::Stylesheet
+    } else if accepted_mime_types
+        .iter()
+        .any(|mime_type| mime_type.starts_with("image/"))
+    {
+        Kind::Image
+    } else {
+        Kind::Other
+    }
+}
+
+fn host(url: &Url) -> String {
+    url.host_str().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod parallel {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[test]
+    fn all_fetched() {
+        let url = |url| Url::parse(url).unwrap();
+        let urls = vec![
+            url("https://a.example/1"),
+            url("https://b.example/2"),
+            url("https://a.example/1"),
+        ];
+
+        let results = fetch_parallel(urls, &AtomicBool::new(false), |url| {
+            if url.path() == "/2" {
+                Err(String::from("404 Not Found"))
+            } else {
+                Ok(url.as_str().len())
+            }
+        });
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[&url("https://a.example/1")], Ok(19));
+        assert!(results[&url("https://b.example/2")].is_err());
+    }
+
+    #[test]
+    fn concurrent_with_host_limit() {
+        let urls = (0..20)
+            .map(|i| format!("https://same.example/{}.png", i))
+            .chain((0..4).map(|i| format!("https://other.example/{}.png", i)))
+            .map(|url| Url::parse(&url).unwrap())
+            .collect();
+        let running = Mutex::new(HashMap::<String, usize>::new());
+        let most_same_host = AtomicUsize::new(0);
+        let most_overall = AtomicUsize::new(0);
+
+        let results = fetch_parallel(urls, &AtomicBool::new(false), |url| {
+            {
+                let mut running = running.lock().unwrap();
+                *running.entry(host(url)).or_default() += 1;
+                let same_host = running.get("same.example").copied().unwrap_or(0);
+                most_same_host.fetch_max(same_host, Ordering::SeqCst);
+                most_overall.fetch_max(running.values().sum(), Ordering::SeqCst);
+            }
+            thread::sleep(Duration::from_millis(20));
+            *running.lock().unwrap().get_mut(&host(url)).unwrap() -= 1;
+            Ok(())
+        });
+
+        assert_eq!(results.len(), 24);
+        assert!(most_same_host.load(Ordering::SeqCst) <= MAX_PER_HOST);
+        assert!(most_overall.load(Ordering::SeqCst) > MAX_PER_HOST);
+    }
+
+    #[test]
+    fn cancelled() {
+        let urls = (0..40)
+            .map(|i| Url::parse(&format!("https://same.example/{}.png", i)).unwrap())
+            .collect();
+        let cancelled = AtomicBool::new(false);
+
+        let results = fetch_parallel(urls, &cancelled, |_| {
+            cancelled.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+
+        // only the fetches started before
+        assert!(results.len() <= MAX_PER_HOST);
+    }
+}
@@ -0,0 +1,208 @@
+//! Local files and directories (`file://` urls)
+
+use super::date;
+
+use reqwest::Url;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// bytes looked at to guess the mime type of files without a known extension
+const SNIFF_LENGTH: usize = 512;
+
+/// File system path of a `file://` url.
+pub fn path(url: &Url) -> Result<PathBuf, String> {
+    url.to_file_path()
+        .map_err(|_| format!("Not a local file: {}", url))
+}
+
+/// Guesses the mime type of a file from its extension, then from its first bytes.
+pub fn mime_type(path: &Path) -> String {
+    // `mime_guess` knows markdown as `text/x-markdown`
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("md") | Some("markdown") => return String::from("text/markdown"),
+        Some(_) => {
+            if let Some(mime_type) = mime_guess::from_path(path).first() {
+                return mime_type.to_string();
+            }
+        }
+        None => {}
+    }
+
+    let mut head = Vec::with_capacity(SNIFF_LENGTH);
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(SNIFF_LENGTH as u64).read_to_end(&mut head);
+    }
+    sniff(&head).to_string()
+}
+
+/// Mime type from magic numbers, <https://mimesniff.spec.whatwg.org/>
+fn sniff(head: &[u8]) -> &'static str {
+    let text = String::from_utf8_lossy(head);
+    let start = text.trim_start().to_ascii_lowercase();
+
+    if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if head.starts_with(b"\xff\xd8\xff") {
+        "image/jpeg"
+    } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if head.starts_with(b"RIFF") && head.get(8..12) == Some(b"WEBP") {
+        "image/webp"
+    } else if start.starts_with("<!doctype html") || start.starts_with("<html") {
+        "text/html"
+    } else if !head.contains(&0) && std::str::from_utf8(head).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Html listing of a directory: parent, subdirectories and files with size and modification time.
+pub fn directory_listing(url: &Url, path: &Path) -> Result<String, String> {
+    let mut entries = fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| date::format_date(modified.as_secs()))
+                .unwrap_or_default();
+            Some((
+                entry.file_name().to_string_lossy().to_string(),
+                metadata.is_dir(),
+                metadata.len(),
+                modified,
+            ))
+        })
+        .collect::<Vec<_>>();
+    // directories first
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut rows = String::new();
+    if let Some(parent) = path.parent() {
+        if let Ok(parent) = Url::from_directory_path(parent) {
+            rows.push_str(&format!(
+                "<tr><td><a href=\"{}\">../</a></td><td></td><td></td></tr>",
+                parent
+            ));
+        }
+    }
+    for (name, is_dir, size, modified) in entries {
+        let (href, name, size) = if is_dir {
+            (
+                Url::from_directory_path(path.join(&name)),
+                format!("{}/", name),
+                String::new(),
+            )
+        } else {
+            (
+                Url::from_file_path(path.join(&name)),
+                name,
+                format_size(size),
+            )
+        };
+        let href = href.map_err(|_| format!("Not a local file: {}", url))?;
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+            href,
+            escape(&name),
+            size,
+            modified
+        ));
+    }
+
+    let title = escape(&path.to_string_lossy());
+    let template =
+        super::open_local_file("assets/directory.html").expect("'directory' asset to be present");
+    Ok(template
+        .replacen("replace_title", &title, 2)
+        .replacen("replace_body", &rows, 1))
+}
+
+/// Human readable size, `1.5 KiB`
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Escapes text for html.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod files {
+    use super::*;
+
+    #[test]
+    fn file_url() {
+        let url = |url| Url::parse(url).unwrap();
+        assert_eq!(
+            path(&url("file:///tmp/My%20File.html")).unwrap(),
+            PathBuf::from("/tmp/My File.html")
+        );
+        assert!(path(&url("https://example.com/")).is_err());
+    }
+
+    #[test]
+    fn mime_from_extension() {
+        assert_eq!(mime_type(Path::new("/a/index.html")), "text/html");
+        assert_eq!(mime_type(Path::new("/a/README.md")), "text/markdown");
+        assert_eq!(mime_type(Path::new("/a/photo.JPG")), "image/jpeg");
+    }
+
+    #[test]
+    fn mime_from_content() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n...."), "image/png");
+        assert_eq!(sniff(b"  <!DOCTYPE html><html>"), "text/html");
+        assert_eq!(sniff(b"just some notes\n"), "text/plain");
+        assert_eq!(
+            sniff(b"\x7fELF\x02\x01\x01\x00"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(10), "10 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn listing() {
+        let dir = std::env::temp_dir().join("prowser-listing-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(dir.join("a<b>.txt"), "hello").unwrap();
+
+        let url = Url::from_directory_path(&dir).unwrap();
+        let listing = directory_listing(&url, &dir).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        let sub = listing.find("sub%20dir/\">sub dir/</a>").unwrap();
+        let file = listing
+            .find("a%3Cb%3E.txt\">a&lt;b&gt;.txt</a></td><td>5 B")
+            .unwrap();
+        assert!(sub < file);
+        assert!(listing.contains(">../</a>"));
+    }
+}
@@ -1,14 +1,77 @@
+mod blocker;
+pub mod cache;
+mod cookies;
+mod data_url;
+mod date;
 mod download;
+pub mod downloads;
+mod encoding;
+pub mod gemini;
+pub mod gopher;
+mod https;
+mod loader;
+mod local;
+mod proxy;
+#[cfg(test)]
+mod test_server;
+pub mod warc;
+
+pub use blocker::Kind;
+pub use data_url::{encode as to_data_url, is_data_url};
+pub use date::format_date;
+pub use download::{clear_cookies, clear_site_cookies, Redirect};
+pub use encoding::decode;
+pub use https::allow_http;
+pub use loader::Resources;
+pub use local::{escape, format_size};
 
 use crate::markdown;
 
-use std::collections::hash_map::DefaultHasher;
-use std::fs::{self, File, OpenOptions};
-use std::hash::{Hash, Hasher};
-use std::io::{self, Read, Write};
-use std::path::Path;
-use std::str;
-use std::time::{SystemTime, UNIX_EPOCH};
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use std::fs::{self, File};
+use std::io::Read;
+use std::sync::atomic::AtomicBool;
+
+/// upper bound of the http cache size: 512 MiB
+const CACHE_SIZE: u64 = 512 * 1024 * 1024;
+
+/// the browser's http cache
+static CACHE: Lazy<cache::Cache> = Lazy::new(|| cache::Cache::new("cache", CACHE_SIZE));
+
+/// Filter lists in `data/filters`
+static BLOCKER: Lazy<blocker::Blocker> = Lazy::new(|| blocker::Blocker::load("data/filters"));
+
+/// Html document to show in a tab
+#[derive(Debug, Default, PartialEq)]
+pub struct TabDocument {
+    pub html: String,
+    /// redirects followed, the last one leads to the url of the document
+    pub redirects: Vec<Redirect>,
+    /// the document is a page of the browser, shown instead of the one navigated to
+    pub interstitial: Option<Interstitial>,
+}
+
+impl TabDocument {
+    pub fn new(html: String) -> Self {
+        Self {
+            html,
+            ..Self::default()
+        }
+    }
+}
+
+/// Page of the browser whose links change its settings,
+/// they are followed only from the page itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interstitial {
+    /// `allow-http:` links
+    HttpsOnly,
+    /// `go-online:` links
+    Offline,
+    /// `stop-replay:` links of the pages listing an archive or missing from it
+    Archive,
+}
 
 /// Returns content of a local file.
 pub fn open_local_file(path: &str) -> Result<String, String> {
@@ -20,257 +83,289 @@ pub fn open_local_file(path: &str) -> Result<String, String> {
     Ok(content)
 }
 
-/// Downloads file (if not cached).
-/// Returns relative file system path.
-pub fn download_cache_path(url: &str, accepted_mime_types: Vec<&str>) -> Result<String, String> {
-    let mut s = DefaultHasher::new();
-    url.hash(&mut s);
-
-    let path = format!("cache/{}", s.finish());
-
-    let mut mime_type = String::new();
+/// Downloads file (if not cached) and returns content.
+/// On wrong mime type, return error with path to file.
+pub fn download_and_get(
+    url: &Url,
+    top_level: Option<&Url>,
+    accepted_mime_types: Vec<&str>,
+) -> Result<String, String> {
+    let resource = locate(url, top_level, Kind::Document)?;
 
-    if let Some(mime) = file_cached(&path) {
-        mime_type = mime;
+    // check mime type
+    let content = if check_mimetype(&resource.mime_type, accepted_mime_types) {
+        encoding::read_text(&resource.path, &resource.mime_type)
     } else {
-        let mut responce = download::request(url)?;
-
-        let mut out = fs::File::create(&path).map_err(|e| e.to_string())?;
-        io::copy(&mut responce, &mut out).map_err(|e| e.to_string())?;
-
-        let headers = responce.headers();
-        let content_type = headers
-            .get("content-type")
-            .and_then(|value| value.to_str().ok());
-
-        if let Some(responce_mime_type) = content_type {
-            let cache_control = headers
-                .get("cache-control")
-                .and_then(|value| value.to_str().ok());
+        Err(resource.path.clone())
+    };
+    resource.used();
+    content
+}
 
-            if let Some(responce_cache_control) = cache_control {
-                mime_type.push_str(responce_mime_type);
+/// A resource on disk
+#[derive(Debug, Clone)]
+struct Resource {
+    path: String,
+    mime_type: String,
+    /// an uncached response, removed once used
+    temporary: bool,
+}
 
-                add_to_cache(responce_mime_type, &path, responce_cache_control);
-            }
+impl Resource {
+    fn new(path: String, mime_type: String) -> Self {
+        Self {
+            path,
+            mime_type,
+            temporary: false,
         }
     }
 
-    // check mime type
-    if check_mimetype(&mime_type, accepted_mime_types) {
-        Ok(path)
-    } else {
-        Err(path)
+    /// Removes the body of an uncached response.
+    fn used(&self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.path);
+        }
     }
 }
 
-/// Downloads file (if not cached) and returns content.
-/// On wrong mime type, return error with path to file.
-pub fn download_and_get(url: &str, accepted_mime_types: Vec<&str>) -> Result<String, String> {
-    let mut s = DefaultHasher::new();
-    url.hash(&mut s);
-
-    let path = format!("cache/{}", s.finish());
-
-    let mut mime_type = String::new();
-
-    if let Some(mime) = file_cached(&path) {
-        mime_type = mime;
-    } else {
-        let mut responce = download::request(url)?;
-
-        let mut out = fs::File::create(&path).map_err(|e| e.to_string())?;
-        io::copy(&mut responce, &mut out).map_err(|e| e.to_string())?;
-
-        let headers = responce.headers();
-        let content_type = headers
-            .get("content-type")
-            .and_then(|value| value.to_str().ok());
-
-        if let Some(responce_mime_type) = content_type {
-            let cache_control = headers
-                .get("cache-control")
-                .and_then(|value| value.to_str().ok());
-
-            if let Some(responce_cache_control) = cache_control {
-                mime_type.push_str(responce_mime_type);
+/// Returns path and mime type of a resource.
+/// Local files are used in place, `data:` urls are decoded next to the http cache,
+/// a replayed archive answers with its captures and everything else goes through the http cache.
+/// Subresources matched by the content blocker aren't requested.
+fn locate(url: &Url, top_level: Option<&Url>, kind: Kind) -> Result<Resource, String> {
+    if url.scheme() == "data" {
+        // not indexed, so removed from the cache directory on the next start
+        let data = data_url::parse(url.as_str())?;
+        let path = CACHE.path_for(url);
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        fs::write(&path, data.body).map_err(|e| e.to_string())?;
+        return Ok(Resource::new(path, data.mime_type));
+    }
 
-                add_to_cache(responce_mime_type, &path, responce_cache_control);
-            }
+    if url.scheme() == "file" {
+        // web pages must not read local files
+        if top_level.is_some_and(|page| page.scheme() != "file") {
+            return Err(format!("Blocked local file {}", url));
         }
+        let path = local::path(url)?;
+        let mime_type = local::mime_type(&path);
+        return Ok(Resource::new(path.to_string_lossy().to_string(), mime_type));
     }
 
-    // check mime type
-    if check_mimetype(&mime_type, accepted_mime_types) {
-        Ok(open_local_file(&path).expect("File to be freshly downloaded or already cached"))
-    } else {
-        Err(path)
+    if BLOCKER.blocks(url, top_level, kind) {
+        return Err(format!("Blocked by content blocker: {}", url));
+    }
+
+    if let Some(replayed) = warc::replayed(url) {
+        let (capture, _) = replayed?;
+        return Ok(Resource::new(capture.path, capture.mime_type));
     }
+
+    let entry = CACHE.fetch(url, top_level)?;
+    Ok(Resource {
+        path: entry.path,
+        mime_type: entry.mime_type,
+        temporary: entry.temporary,
+    })
 }
 
-/// Downloads file (if not cached) and returns content.
-/// On wrong mime type, return error with path to file.
-pub fn download(url: &str) -> Result<(reqwest::blocking::Response, String), String> {
-    let mut s = DefaultHasher::new();
-    url.hash(&mut s);
+/// Is the browser offline, using only cached responses?
+pub fn offline() -> bool {
+    CACHE.is_offline()
+}
 
-    let path = format!("cache/{}", s.finish());
+/// Goes offline or online again.
+pub fn set_offline(offline: bool) {
+    CACHE.set_offline(offline);
+}
 
-    // download -> responce
-    let mut responce = download::request(url)?;
+/// Css hiding elements of the page `url` matched by element hiding rules.
+pub fn hiding_css(url: &Url) -> String {
+    BLOCKER.hiding_css(url)
+}
 
-    let mut out = fs::File::create(&path).map_err(|e| e.to_string())?;
-    io::copy(&mut responce, &mut out).map_err(|e| e.to_string())?;
+/// Entries of the http cache, most recently used first.
+pub fn cache_entries() -> Vec<cache::Entry> {
+    CACHE.entries()
+}
 
-    // after saving responce to file, text() is empty
-    Ok((responce, path))
+/// Deletes the cached response of an url.
+pub fn remove_from_cache(url: &Url) {
+    CACHE.remove(url);
 }
 
-/// Downloads file (if not cached) with given parameters and returns content.
-pub fn download_and_get_post(url: &str, params: &[(&str, &str)]) -> String {
-    let mut s = DefaultHasher::new();
-    url.hash(&mut s);
+/// Deletes every cached response.
+pub fn clear_cache() {
+    CACHE.clear();
+}
 
-    let path = format!("cache/{}", s.finish());
-    match download::save_file_post(url, &path, params) {
-        Ok(()) => open_local_file(&path).unwrap(),
-        Err(error) => open_error_document(error),
-    }
+/// Posts `body` of `content_type`, like form data, and returns the response.
+pub fn download_and_get_post(url: &Url, content_type: &str, body: &[u8]) -> TabDocument {
+    // responses to a POST aren't cached, the one of a GET to the url stays
+    let path = CACHE.temporary_path();
+    let document = match download::save_file_post(url, &path, content_type, body) {
+        Ok(redirects) => TabDocument {
+            redirects,
+            ..TabDocument::new(encoding::read_text(&path, "").unwrap())
+        },
+        Err(error) => error_document(error),
+    };
+    let _ = fs::remove_file(&path);
+    document
 }
 
 /// downloads
 /// return html
 /// either directly, text, converted md or image
-pub fn for_tab(url: &str) -> String {
-    let download = download(url);
-
-    match download {
-        Ok((responce, path)) => {
-            let headers = responce.headers();
-            let content_type = headers
-                .get("content-type")
-                .and_then(|value| value.to_str().ok());
-
-            if let Some(mime_type) = content_type {
-                if mime_type.starts_with("text/html") {
-                    open_local_file(&path).unwrap()
-                } else if mime_type.starts_with("text/plain")
-                    || mime_type.starts_with("text/css")
-                    || mime_type.starts_with("text/javascript")
-                    || mime_type.starts_with("application/javascript")
-                {
-                    let mut content = open_local_file(&path).unwrap();
-                    let template =
-                        open_local_file("assets/text.html").expect("'text' asset to be present");
-
-                    content = content.replace("\n", "<br>");
-
-                    // FIXME: escape content for possible html elements
-                    template.replacen("replace_body", &content, 1)
-                } else if mime_type.starts_with("text/markdown") {
-                    let content = open_local_file(&path).unwrap();
-                    let template = open_local_file("assets/markdown.html")
-                        .expect("'markdown' asset to be present");
-
-                    // FIXME: probably should give real url
-                    let markdown = markdown::parse(content, String::new());
-
-                    // FIXME: use selected stylesheets from config
-                    template.replacen("replace_body", &markdown, 1)
-                } else if mime_type.starts_with("image/jpeg")
-                    || mime_type.starts_with("image/gif")
-                    || mime_type.starts_with("image/png")
-                    || mime_type.starts_with("image/webp")
-                {
-                    let template =
-                        open_local_file("assets/image.html").expect("'image' asset to be present");
-
-                    template.replacen("replace_image", url, 3)
-                } else {
-                    save_to_downloads(&path);
-                    format!("Unsuported Mime Type: {}. Saved to downloads", mime_type)
+/// no download is started once `cancelled` is set
+pub fn for_tab(url: &Url, cancelled: &AtomicBool) -> TabDocument {
+    if url.scheme() == "file" {
+        return local_for_tab(url);
+    }
+    if url.scheme() == "data" {
+        let html = match locate(url, None, Kind::Document) {
+            Ok(Resource {
+                path, mime_type, ..
+            }) => document_for_mime(url, &path, &mime_type).unwrap_or_else(|error| error),
+            Err(error) => error,
+        };
+        return TabDocument::new(html);
+    }
+
+    if let Some(replayed) = warc::replayed(url) {
+        return match replayed {
+            Ok((capture, redirects)) => {
+                let html = document_for_mime(&capture.url, &capture.path, &capture.mime_type)
+                    .unwrap_or_else(|error| error);
+                TabDocument {
+                    redirects,
+                    ..TabDocument::new(html)
                 }
+            }
+            Err(error) => error_document(error),
+        };
+    }
+
+    match CACHE.navigate(url, cancelled) {
+        Ok(cache::Entry {
+            url,
+            path,
+            mime_type,
+            redirects,
+            temporary,
+            ..
+        }) => {
+            // cached before as something else than a document
+            let html = if displayable(&mime_type) {
+                document_for_mime(&url, &path, &mime_type).unwrap_or_else(|error| error)
             } else {
-                save_to_downloads(&path);
-                String::from("No Mime Type specified. Saved to downloads")
+                open_error_document(match downloads::start(&url) {
+                    Ok(path) => downloads::started_error(&path),
+                    Err(error) => error,
+                })
+            };
+            if temporary {
+                let _ = fs::remove_file(&path);
+            }
+            TabDocument {
+                redirects,
+                ..TabDocument::new(html)
             }
         }
         // download falsch
-        Err(error) => open_error_document(error),
+        Err(error) => error_document(error),
     }
 }
 
-fn save_to_downloads(cache_path: &str) {
-    let vec = cache_path.split('/').collect::<Vec<&str>>()[1];
-    // FIXME: fix for windows and change to real filename
-    let username = std::process::Command::new("whoami")
-        .output()
-        .expect("wohami command failed to start")
-        .stdout;
-    let mut username = str::from_utf8(&username).unwrap().to_string();
-    username.truncate(username.len() - 1); // remove '\n'
-
-    let mut destination = format!("/home/{}/Downloads/", username);
-    destination.push_str(vec);
-    fs::copy(cache_path, &destination).expect("Error when copying in downloads directory");
-}
-
-// FIXME: files that are not valid or do not exist anymore should be deleted and removed from cache file
-/// checks if a file is cached and still valid
-fn file_cached(name: &str) -> Option<String> {
-    let mut content = String::new();
-    File::open("cache/cache.csv")
-        .map_err(|e| e.to_string())
-        .expect("'cache' asset to be present")
-        .read_to_string(&mut content)
-        .map_err(|e| e.to_string())
-        .unwrap();
-
-    let lines = content.split('\n').collect::<Vec<&str>>();
-
-    for line in lines {
-        // path/to/cache|mime_type|cache_control|download_time
-        let cells = line.split('|').collect::<Vec<&str>>();
-
-        if cells[0] == name {
-            // file was cached some time ago
-
-            if let Ok(n) = SystemTime::now().duration_since(UNIX_EPOCH) {
-                // FIXME: read cache_control max-age. ↓ Don't assume a year
-                if cells[3].parse::<u64>().unwrap() + 31_536_000 > n.as_secs() {
-                    // file is still valid
-
-                    if Path::new(cells[0]).exists() {
-                        // file on disk still exists
-                        return Some(cells[1].to_string());
-                    } else {
-                        return None;
-                    }
-                } else {
-                    return None;
-                }
-            }
-        }
+/// Opens a local file, replays an archive or lists a directory.
+fn local_for_tab(url: &Url) -> TabDocument {
+    let path = match local::path(url) {
+        Ok(path) => path,
+        Err(error) => return TabDocument::new(error),
+    };
+
+    if path.is_file() && warc::is_archive(&path) {
+        return match warc::replay(&path) {
+            Ok(html) => TabDocument {
+                interstitial: Some(Interstitial::Archive),
+                ..TabDocument::new(html)
+            },
+            Err(error) => TabDocument::new(error),
+        };
     }
 
-    None
+    let html = if path.is_dir() {
+        local::directory_listing(url, &path).unwrap_or_else(|error| error)
+    } else if path.is_file() {
+        let mime_type = local::mime_type(&path);
+        document_for_mime(url, &path.to_string_lossy(), &mime_type).unwrap_or_else(|error| error)
+    } else {
+        format!("File not found: {}", path.display())
+    };
+    TabDocument::new(html)
 }
 
-fn add_to_cache(hash: &str, mime_type: &str, cache_control: &str) {
-    // add file entry to cache
-    // with hashed path
-    // mime type
-    // ...
-
-    let mut file = OpenOptions::new()
-        .append(true)
-        .open("cache/cache.csv")
-        .expect("'cache' asset to be present");
-
-    if let Ok(n) = SystemTime::now().duration_since(UNIX_EPOCH) {
-        let line = format!("{}|{}|{}|{}\n", mime_type, hash, cache_control, n.as_secs());
-        // FIXME: never add "must revalidate" or similar
-        let _ = file.write_all(line.as_bytes());
+/// Can `document_for_mime` show files of the mime type?
+fn displayable(mime_type: &str) -> bool {
+    [
+        "text/html",
+        "text/plain",
+        "text/css",
+        "text/javascript",
+        "application/javascript",
+        "text/markdown",
+        "image/jpeg",
+        "image/gif",
+        "image/png",
+        "image/webp",
+    ]
+    .iter()
+    .any(|displayable| mime_type.starts_with(displayable))
+}
+
+/// Html document showing the file at `path`.
+/// Fails if the mime type can't be displayed or the file can't be read.
+fn document_for_mime(url: &Url, path: &str, mime_type: &str) -> Result<String, String> {
+    let read_text = || {
+        encoding::read_text(path, mime_type).map_err(|e| format!("Couldn't read {}: {}", path, e))
+    };
+
+    if mime_type.starts_with("text/html") {
+        read_text()
+    } else if mime_type.starts_with("text/plain")
+        || mime_type.starts_with("text/css")
+        || mime_type.starts_with("text/javascript")
+        || mime_type.starts_with("application/javascript")
+    {
+        let mut content = read_text()?;
+        let template = open_local_file("assets/text.html").expect("'text' asset to be present");
+
+        content = content.replace("\n", "<br>");
+
+        // FIXME: escape content for possible html elements
+        Ok(template.replacen("replace_body", &content, 1))
+    } else if mime_type.starts_with("text/markdown") {
+        let content = read_text()?;
+        let template =
+            open_local_file("assets/markdown.html").expect("'markdown' asset to be present");
+
+        // FIXME: probably should give real url
+        let markdown = markdown::parse(content, String::new());
+
+        // FIXME: use selected stylesheets from config
+        Ok(template.replacen("replace_body", &markdown, 1))
+    } else if mime_type.starts_with("image/jpeg")
+        || mime_type.starts_with("image/gif")
+        || mime_type.starts_with("image/png")
+        || mime_type.starts_with("image/webp")
+    {
+        let template = open_local_file("assets/image.html").expect("'image' asset to be present");
+
+        Ok(template.replacen("replace_image", url.as_str(), 3))
+    } else {
+        Err(format!("Unsuported Mime Type: {}", mime_type))
     }
 }
 
@@ -285,9 +380,54 @@ fn check_mimetype(mime_type: &str, accepted_mime_types: Vec<&str>) -> bool {
     false
 }
 
+/// Error document of a navigation, knowing which interstitial it is.
+fn error_document(error: String) -> TabDocument {
+    TabDocument {
+        interstitial: interstitial(&error),
+        ..TabDocument::new(open_error_document(error))
+    }
+}
+
+/// Interstitial `open_error_document` shows for an error, if any.
+fn interstitial(error: &str) -> Option<Interstitial> {
+    if https::failed_upgrade(error).is_some() {
+        Some(Interstitial::HttpsOnly)
+    } else if download::offline_error(error).is_some() {
+        Some(Interstitial::Offline)
+    } else if warc::not_archived_error(error).is_some() {
+        Some(Interstitial::Archive)
+    } else {
+        None
+    }
+}
+
 /// Return error document on given http error
 fn open_error_document(error: String) -> String {
-    if error.contains("lookup address information: Name or service not known") {
+    if let Some(url) = https::failed_upgrade(&error) {
+        let url = local::escape(url);
+        open_local_file("assets/https-only.html")
+            .expect("'https only' asset to be present")
+            .replace("replace_url", &url)
+    } else if let Some(url) = download::offline_error(&error) {
+        let url = local::escape(url);
+        open_local_file("assets/offline.html")
+            .expect("'offline' asset to be present")
+            .replace("replace_url", &url)
+    } else if let Some(url) = warc::not_archived_error(&error) {
+        let url = local::escape(url);
+        open_local_file("assets/not-archived.html")
+            .expect("'not archived' asset to be present")
+            .replace("replace_url", &url)
+    } else if let Some(path) = downloads::started(&error) {
+        open_local_file("assets/download-started.html")
+            .expect("'download started' asset to be present")
+            .replace("replace_path", &local::escape(path))
+    } else if let Some(url) = download::paused_redirect(&error) {
+        let url = local::escape(url);
+        open_local_file("assets/redirect-paused.html")
+            .expect("'redirect paused' asset to be present")
+            .replace("replace_url", &url)
+    } else if error.contains("lookup address information: Name or service not known") {
         open_local_file("assets/server-not-found.html")
             .expect("'server not found' asset to be present")
     } else if error == "404 Not Found" {
@@ -309,11 +449,61 @@ mod open {
         );
     }
 
+    #[test]
+    fn error_document_https() {
+        let document = open_error_document(String::from(
+            "HTTPS not available for http://example.com/?a&b\nconnection refused",
+        ));
+        assert!(document.contains("href=\"allow-http:http://example.com/?a&amp;b\""));
+        assert_eq!(
+            super::error_document(String::from("HTTPS not available for http://example.com/"))
+                .interstitial,
+            Some(Interstitial::HttpsOnly)
+        );
+    }
+
+    #[test]
+    fn error_document_offline() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let document = super::error_document(download::not_requested(&url));
+        assert!(document
+            .html
+            .contains("href=\"go-online:https://example.com/\""));
+        assert_eq!(document.interstitial, Some(Interstitial::Offline));
+    }
+
+    #[test]
+    fn error_document_not_archived() {
+        let url = Url::parse("https://example.com/").unwrap();
+        let document = super::error_document(warc::not_archived(&url));
+        assert!(document
+            .html
+            .contains("href=\"stop-replay:https://example.com/\""));
+        assert_eq!(document.interstitial, Some(Interstitial::Archive));
+    }
+
     #[test]
     fn error_document_no_match() {
         assert_eq!(
             open_error_document(String::from("unknown error")),
             String::from("unknown error")
         );
+        assert_eq!(
+            super::error_document(String::from("404 Not Found")).interstitial,
+            None
+        );
+    }
+
+    #[test]
+    fn unreadable_file() {
+        let url = Url::parse("file:///missing.txt").unwrap();
+        let path = std::env::temp_dir().join("prowser-missing-document.txt");
+
+        let error = document_for_mime(&url, &path.to_string_lossy(), "text/plain").unwrap_err();
+        assert!(error.starts_with("Couldn't read "));
+        assert_eq!(
+            document_for_mime(&url, &path.to_string_lossy(), "video/mp4"),
+            Err(String::from("Unsuported Mime Type: video/mp4"))
+        );
     }
 }
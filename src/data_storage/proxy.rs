@@ -0,0 +1,207 @@
+//! Connections for gemini and gopher through the configured proxy,
+//! http requests are proxied by the http client.
+
+use crate::config;
+
+use reqwest::Url;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Opens a connection to `host`, through a proxy if one is configured for it.
+pub fn connect(host: &str, port: u16) -> Result<TcpStream, String> {
+    match config::get().proxy(host)? {
+        Some(proxy) => connect_through(&proxy, host, port),
+        None => connect_directly(host, port),
+    }
+}
+
+fn connect_directly(host: &str, port: u16) -> Result<TcpStream, String> {
+    let address = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("failed to lookup address information: Name or service not known")?;
+    TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).map_err(|e| e.to_string())
+}
+
+/// Tunnels a connection to `host` through `proxy`.
+fn connect_through(proxy: &Url, host: &str, port: u16) -> Result<TcpStream, String> {
+    let proxy_host = proxy.host_str().ok_or("No host in proxy url")?;
+    // socks proxies listen on 1080
+    let proxy_port = proxy.port_or_known_default().unwrap_or(1080);
+    let mut stream = connect_directly(proxy_host, proxy_port)?;
+
+    match proxy.scheme() {
+        "socks5h" => socks5(&mut stream, Address::Name(host), port)?,
+        "socks5" => {
+            let address = (host, port)
+                .to_socket_addrs()
+                .map_err(|e| e.to_string())?
+                .next()
+                .ok_or("failed to lookup address information: Name or service not known")?;
+            socks5(&mut stream, Address::Ip(address.ip()), port)?;
+        }
+        "http" => http_connect(&mut stream, host, port)?,
+        scheme => return Err(format!("Unsupported proxy for this protocol: {}", scheme)),
+    }
+    Ok(stream)
+}
+
+enum Address<'a> {
+    Name(&'a str),
+    Ip(std::net::IpAddr),
+}
+
+/// SOCKS5 `CONNECT` without authentication, <https://tools.ietf.org/html/rfc1928>
+fn socks5<S: Read + Write>(stream: &mut S, address: Address, port: u16) -> Result<(), String> {
+    let error = |e: std::io::Error| e.to_string();
+
+    stream.write_all(&[5, 1, 0]).map_err(error)?;
+    let mut choice = [0; 2];
+    stream.read_exact(&mut choice).map_err(error)?;
+    if choice != [5, 0] {
+        return Err(String::from("SOCKS proxy requires authentication"));
+    }
+
+    let mut request = vec![5, 1, 0];
+    match address {
+        Address::Name(name) => {
+            if name.len() > 255 {
+                return Err(format!("Host name too long: {}", name));
+            }
+            request.push(3);
+            request.push(name.len() as u8);
+            request.extend(name.as_bytes());
+        }
+        Address::Ip(std::net::IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend(&ip.octets());
+        }
+        Address::Ip(std::net::IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend(&ip.octets());
+        }
+    }
+    request.extend(&port.to_be_bytes());
+    stream.write_all(&request).map_err(error)?;
+
+    // version, reply, reserved, address type, then the bound address and port
+    let mut reply = [0; 4];
+    stream.read_exact(&mut reply).map_err(error)?;
+    if reply[1] != 0 {
+        return Err(format!("SOCKS proxy refused the connection ({})", reply[1]));
+    }
+    let bound_length = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => {
+            let mut length = [0];
+            stream.read_exact(&mut length).map_err(error)?;
+            length[0] as usize
+        }
+        _ => return Err(String::from("Invalid SOCKS reply")),
+    };
+    let mut bound = vec![0; bound_length + 2];
+    stream.read_exact(&mut bound).map_err(error)
+}
+
+/// Http `CONNECT` tunnel, <https://tools.ietf.org/html/rfc7231#section-4.3.6>
+fn http_connect<S: Read + Write>(stream: &mut S, host: &str, port: u16) -> Result<(), String> {
+    let error = |e: std::io::Error| e.to_string();
+
+    write!(
+        stream,
+        "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\n\r\n",
+        host = host,
+        port = port
+    )
+    .map_err(error)?;
+
+    // read byte by byte, the tunnel starts right after the head
+    let mut reader = BufReader::with_capacity(1, stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).map_err(error)?;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(error)? == 0 || line == "\r\n" {
+            break;
+        }
+    }
+
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("Proxy refused the connection: {}", status.trim())),
+    }
+}
+
+#[cfg(test)]
+mod tunnel {
+    use super::*;
+    use std::io::Cursor;
+
+    /// A stream replaying `input` and recording what is written.
+    struct Mock {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Mock {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buffer)
+        }
+    }
+
+    impl Write for Mock {
+        fn write(&mut self, buffer: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buffer)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn mock(input: &[u8]) -> Mock {
+        Mock {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn socks5_remote_name() {
+        let mut stream = mock(&[5, 0, 5, 0, 0, 1, 0, 0, 0, 0, 0, 0, b'x']);
+        socks5(&mut stream, Address::Name("example.onion"), 1965).unwrap();
+
+        let mut expected = vec![5, 1, 0, 5, 1, 0, 3, 13];
+        expected.extend(b"example.onion");
+        expected.extend(&[7, 173]);
+        assert_eq!(stream.output, expected);
+        // the tunnel starts after the reply
+        assert_eq!(stream.input.position(), 12);
+    }
+
+    #[test]
+    fn socks5_refused() {
+        let mut stream = mock(&[5, 0, 5, 4, 0, 1, 0, 0, 0, 0, 0, 0]);
+        assert!(socks5(&mut stream, Address::Ip([10, 0, 0, 1].into()), 70).is_err());
+    }
+
+    #[test]
+    fn http() {
+        let mut stream = mock(b"HTTP/1.1 200 Connection established\r\nVia: proxy\r\n\r\niA");
+        http_connect(&mut stream, "gopher.example", 70).unwrap();
+
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "CONNECT gopher.example:70 HTTP/1.1\r\nHost: gopher.example:70\r\n\r\n"
+        );
+        assert_eq!(stream.input.position(), 51);
+
+        let mut stream = mock(b"HTTP/1.1 403 Forbidden\r\n\r\n");
+        assert!(http_connect(&mut stream, "gopher.example", 70).is_err());
+    }
+}
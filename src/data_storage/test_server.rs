@@ -0,0 +1,101 @@
+//! Minimal HTTP stand-in server on loopback, for tests
+
+use reqwest::Url;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Answers every request with the response `respond` returns for it.
+/// Request heads are recorded with lowercase header names.
+pub struct TestServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<String>>>,
+    stopped: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopping = stopped.clone();
+
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopping.load(Ordering::SeqCst) {
+                    break;
+                }
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    // header names are case insensitive
+                    let line = match line.find(':') {
+                        Some(colon) if !request.is_empty() => {
+                            format!("{}{}", line[..colon].to_ascii_lowercase(), &line[colon..])
+                        }
+                        _ => line,
+                    };
+                    if let Some(length) = line.strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap_or(0);
+                    }
+                    request.push_str(&line);
+                }
+                let mut body = vec![0; content_length];
+                let _ = reader.read_exact(&mut body);
+                request.push_str("\r\n");
+                request.push_str(&String::from_utf8_lossy(&body));
+
+                let mut response = respond(&request);
+                recorded.lock().unwrap().push(request);
+
+                // one request per connection
+                if let Some(end) = response.find("\r\n") {
+                    response.insert_str(end + 2, "Connection: close\r\n");
+                }
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        Self {
+            port,
+            requests,
+            stopped,
+            thread,
+        }
+    }
+
+    /// Closes the port, later connections are refused.
+    pub fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // wakes the server up
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+        let _ = self.thread.join();
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        Url::parse(&format!("http://127.0.0.1:{}{}", self.port, path)).unwrap()
+    }
+
+    /// recorded request heads (and bodies)
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
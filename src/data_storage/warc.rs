@@ -0,0 +1,770 @@
+//! Web ARChive files
+//! <https://iipc.github.io/warc-specifications/specifications/warc-format/warc-1.1/>
+//!
+//! While recording, http exchanges are appended to a `.warc.gz` file in the download directory,
+//! except files downloaded by navigations.
+//! While an archive is replayed, requests are answered with its captured responses
+//! and nothing is sent to the network.
+
+use super::date;
+use super::download::Redirect;
+use super::{downloads, escape, local};
+use crate::config;
+
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use once_cell::sync::Lazy;
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url, Version};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// start of the error of a request not sent while replaying, followed by its url
+const NOT_ARCHIVED: &str = "Not archived, not requesting ";
+
+/// The archive http exchanges are recorded to
+static RECORDING: Lazy<Mutex<Option<Recording>>> = Lazy::new(|| Mutex::new(None));
+/// The archive being browsed
+static REPLAYING: Lazy<Mutex<Option<Archive>>> = Lazy::new(|| Mutex::new(None));
+
+/// A record of a WARC file
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    /// named fields of the header, like `WARC-Type`, in file order
+    pub headers: Vec<(String, String)>,
+    pub block: Vec<u8>,
+}
+
+impl Record {
+    fn new(headers: Vec<(&str, String)>, block: Vec<u8>) -> Self {
+        Self {
+            headers: headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            block,
+        }
+    }
+
+    /// Value of a header field, names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The record as written to a file, its `Content-Length` is added.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = b"WARC/1.1\r\n".to_vec();
+        for (name, value) in &self.headers {
+            bytes.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        bytes.extend_from_slice(format!("Content-Length: {}\r\n\r\n", self.block.len()).as_bytes());
+        bytes.extend_from_slice(&self.block);
+        bytes.extend_from_slice(b"\r\n\r\n");
+        bytes
+    }
+}
+
+/// An archive being recorded to
+struct Recording {
+    path: PathBuf,
+    file: File,
+    /// records written, part of the seed of record ids
+    written: u64,
+}
+
+impl Recording {
+    /// Creates the archive `stem.warc.gz` in `dir`, starting with a `warcinfo` record.
+    /// `stem-1.warc.gz` and so on if the name is taken.
+    fn create(dir: &Path, stem: &str) -> Result<Self, String> {
+        let mut number = 0;
+        let (path, file) = loop {
+            let path = match number {
+                0 => dir.join(format!("{}.warc.gz", stem)),
+                _ => dir.join(format!("{}-{}.warc.gz", stem, number)),
+            };
+            number += 1;
+            match OpenOptions::new().append(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(error) => return Err(error.to_string()),
+            }
+        };
+        let mut recording = Self {
+            path,
+            file,
+            written: 0,
+        };
+
+        let name = recording
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let info = Record::new(
+            vec![
+                ("WARC-Type", String::from("warcinfo")),
+                ("WARC-Date", warc_date()),
+                ("WARC-Filename", name),
+                ("WARC-Record-ID", recording.record_id()),
+                ("Content-Type", String::from("application/warc-fields")),
+            ],
+            format!(
+                "software: prowser/{}\r\nformat: WARC File Format 1.1\r\n",
+                env!("CARGO_PKG_VERSION")
+            )
+            .into_bytes(),
+        );
+        recording.write(&info)?;
+        Ok(recording)
+    }
+
+    /// Unique id of the next record
+    fn record_id(&mut self) -> String {
+        self.written += 1;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos())
+            .unwrap_or(0);
+        let hash = Sha256::digest(format!("{:?} {} {}", self.path, self.written, nanos).as_bytes());
+
+        // a random (version 4) uuid
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&hash[..16]);
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        format!(
+            "<urn:uuid:{}-{}-{}-{}-{}>",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+
+    /// Appends a record as its own gzip member, like other tools write `.warc.gz` files.
+    fn write(&mut self, record: &Record) -> Result<(), String> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&record.to_bytes())
+            .map_err(|e| e.to_string())?;
+        let member = encoder.finish().map_err(|e| e.to_string())?;
+        self.file.write_all(&member).map_err(|e| e.to_string())
+    }
+
+    /// Appends a `request` record and the `response` record answering it.
+    fn write_exchange(
+        &mut self,
+        request: &reqwest::blocking::Request,
+        status: StatusCode,
+        version: Version,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Result<(), String> {
+        let url = request.url();
+        let date = warc_date();
+        let response_id = self.record_id();
+        let request_id = self.record_id();
+
+        let mut target = url.path().to_string();
+        if let Some(query) = url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let mut request_block =
+            format!("{} {} HTTP/1.1\r\n", request.method(), target).into_bytes();
+        // added by the http client when sending
+        if !request.headers().contains_key("host") {
+            let host = match url.port() {
+                Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+                None => url.host_str().unwrap_or_default().to_string(),
+            };
+            request_block.extend_from_slice(format!("Host: {}\r\n", host).as_bytes());
+        }
+        write_headers(&mut request_block, request.headers());
+        request_block.extend_from_slice(b"\r\n");
+        if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+            request_block.extend_from_slice(body);
+        }
+
+        let mut response_block = format!("{:?} {}\r\n", version, status).into_bytes();
+        write_headers(&mut response_block, headers);
+        response_block.extend_from_slice(b"\r\n");
+        response_block.extend_from_slice(body);
+
+        let request = Record::new(
+            vec![
+                ("WARC-Type", String::from("request")),
+                ("WARC-Target-URI", url.to_string()),
+                ("WARC-Date", date.clone()),
+                ("WARC-Record-ID", request_id),
+                ("WARC-Concurrent-To", response_id.clone()),
+                (
+                    "Content-Type",
+                    String::from("application/http;msgtype=request"),
+                ),
+            ],
+            request_block,
+        );
+        let response = Record::new(
+            vec![
+                ("WARC-Type", String::from("response")),
+                ("WARC-Target-URI", url.to_string()),
+                ("WARC-Date", date),
+                ("WARC-Record-ID", response_id),
+                (
+                    "Content-Type",
+                    String::from("application/http;msgtype=response"),
+                ),
+            ],
+            response_block,
+        );
+        self.write(&request)?;
+        self.write(&response)
+    }
+}
+
+/// Header lines of an http message.
+/// The body is recorded as received from the http client, without its transfer encoding.
+fn write_headers(block: &mut Vec<u8>, headers: &HeaderMap) {
+    for (name, value) in headers {
+        if name == "transfer-encoding" {
+            continue;
+        }
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.extend_from_slice(b": ");
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Starts recording http exchanges to a new archive in the download directory.
+pub fn start_recording() -> Result<PathBuf, String> {
+    let dir = downloads::download_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let stem = format!("prowser-{}", warc_date().replace(['-', ':', 'T', 'Z'], ""));
+    let recording = Recording::create(&dir, &stem)?;
+    let path = recording.path.clone();
+    *RECORDING.lock().unwrap() = Some(recording);
+    Ok(path)
+}
+
+/// Stops recording, returns the path of the archive if one was recorded to.
+pub fn stop_recording() -> Option<PathBuf> {
+    RECORDING
+        .lock()
+        .unwrap()
+        .take()
+        .map(|recording| recording.path)
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+/// Records an exchange, if recording.
+/// The body of the response is read to record it, a response with the same body is returned.
+/// `navigation` is whether the request is a top-level navigation.
+pub fn record(
+    request: &reqwest::blocking::Request,
+    response: reqwest::blocking::Response,
+    navigation: bool,
+) -> Result<reqwest::blocking::Response, String> {
+    if !is_recording() || !recorded(navigation, response.status(), response.headers()) {
+        return Ok(response);
+    }
+
+    // read before locking, other requests are recorded meanwhile
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let body = response.bytes().map_err(|e| e.to_string())?.to_vec();
+    if let Some(recording) = RECORDING.lock().unwrap().as_mut() {
+        if let Err(error) = recording.write_exchange(request, status, version, &headers, &body) {
+            eprintln!("Couldn't record {}: {}", request.url(), error);
+        }
+    }
+
+    let mut recorded = http::Response::new(body);
+    *recorded.status_mut() = status;
+    *recorded.version_mut() = version;
+    *recorded.headers_mut() = headers;
+    Ok(recorded.into())
+}
+
+/// Is an exchange recorded? Not if a navigation downloads a file,
+/// it streams to disk and isn't read into memory for the archive.
+fn recorded(navigation: bool, status: StatusCode, headers: &HeaderMap) -> bool {
+    !(navigation && status.is_success() && downloads::is_download(headers))
+}
+
+/// Records of a WARC file, gzip compressed or not.
+pub fn read(path: &Path) -> Result<Vec<Record>, String> {
+    let content = fs::read(path).map_err(|e| e.to_string())?;
+    if content.starts_with(&[0x1f, 0x8b]) {
+        // `.warc.gz` files are a gzip member per record
+        let mut decompressed = Vec::new();
+        MultiGzDecoder::new(&content[..])
+            .read_to_end(&mut decompressed)
+            .map_err(|e| e.to_string())?;
+        parse(&decompressed)
+    } else {
+        parse(&content)
+    }
+}
+
+fn parse(mut content: &[u8]) -> Result<Vec<Record>, String> {
+    let mut records = Vec::new();
+
+    loop {
+        // records end with two line breaks
+        while let Some(b'\r' | b'\n') = content.first() {
+            content = &content[1..];
+        }
+        if content.is_empty() {
+            return Ok(records);
+        }
+
+        let (head, rest) = split_head(content).ok_or("Truncated WARC record header")?;
+        let mut lines = head.lines();
+        if !lines.next().unwrap_or_default().starts_with("WARC/") {
+            return Err(String::from("Not a WARC record"));
+        }
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        let mut record = Record {
+            headers,
+            block: Vec::new(),
+        };
+
+        let length = record
+            .header("Content-Length")
+            .and_then(|length| length.parse::<usize>().ok())
+            .ok_or("WARC record without Content-Length")?;
+        let block = rest.get(..length).ok_or("Truncated WARC record")?;
+        record.block = block.to_vec();
+        content = &rest[length..];
+        records.push(record);
+    }
+}
+
+/// Splits a message at the empty line ending its header.
+fn split_head(message: &[u8]) -> Option<(String, &[u8])> {
+    let (end, separator) = match message.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => (end, 4),
+        None => (message.windows(2).position(|w| w == b"\n\n")?, 2),
+    };
+    let head = String::from_utf8_lossy(&message[..end]).into_owned();
+    Some((head, &message[end + separator..]))
+}
+
+/// A captured response, its body extracted to a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+    pub url: Url,
+    pub status: u16,
+    pub mime_type: String,
+    /// target of a redirect, resolved
+    pub location: Option<Url>,
+    /// path of the body
+    pub path: String,
+    /// `WARC-Date` of the record
+    pub date: String,
+}
+
+/// The responses captured in a WARC file, by url
+#[derive(Debug, Default)]
+pub struct Archive {
+    captures: HashMap<String, Capture>,
+    /// urls in the order they were first captured
+    urls: Vec<String>,
+}
+
+impl Archive {
+    /// Reads an archive and extracts the bodies of its responses to `dir`.
+    /// The last capture of an url is used.
+    pub fn open(file: &Path, dir: &Path) -> Result<Self, String> {
+        let records = read(file)?;
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+        let mut archive = Self::default();
+        for (index, record) in records.into_iter().enumerate() {
+            // WARC 1.0 shows them in angle brackets
+            let url = match record
+                .header("WARC-Target-URI")
+                .and_then(|url| Url::parse(url.trim_start_matches('<').trim_end_matches('>')).ok())
+            {
+                Some(url) => url,
+                None => continue,
+            };
+            let (status, headers, body) = match record.header("WARC-Type") {
+                Some("response") => match parse_response(&record.block) {
+                    Some(response) => response,
+                    None => continue,
+                },
+                // the block is the body, like files fetched without http
+                Some("resource") => {
+                    let mime_type = record.header("Content-Type").unwrap_or_default();
+                    let headers = vec![(String::from("content-type"), mime_type.to_string())];
+                    (200, headers, record.block.clone())
+                }
+                _ => continue,
+            };
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(field, _)| field.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.clone())
+            };
+
+            let path = dir.join(index.to_string());
+            fs::write(&path, body).map_err(|e| e.to_string())?;
+            let capture = Capture {
+                location: header("location").and_then(|location| url.join(&location).ok()),
+                mime_type: header("content-type").unwrap_or_else(|| local::mime_type(&path)),
+                path: path.to_string_lossy().into_owned(),
+                date: record.header("WARC-Date").unwrap_or_default().to_string(),
+                status,
+                url: url.clone(),
+            };
+
+            let key = key(&url);
+            if archive.captures.insert(key.clone(), capture).is_none() {
+                archive.urls.push(key);
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// Capture of an url, whatever its fragment.
+    pub fn get(&self, url: &Url) -> Option<&Capture> {
+        self.captures.get(&key(url))
+    }
+
+    /// Captured response of an url, following captured redirects.
+    /// Also returns the redirects followed.
+    pub fn resolve(
+        &self,
+        url: &Url,
+        max_redirects: usize,
+    ) -> Result<(Capture, Vec<Redirect>), String> {
+        let mut redirects = Vec::new();
+        let mut capture = self.get(url).ok_or_else(|| not_archived(url))?;
+
+        while let (300..=399, Some(location)) = (capture.status, &capture.location) {
+            if redirects.len() == max_redirects {
+                return Err(format!(
+                    "Redirect loop: more than {} redirects, the last to {}",
+                    max_redirects, location
+                ));
+            }
+            redirects.push(Redirect {
+                status: capture.status,
+                from: capture.url.clone(),
+                to: location.clone(),
+            });
+            capture = self.get(location).ok_or_else(|| not_archived(location))?;
+        }
+
+        Ok((capture.clone(), redirects))
+    }
+
+    /// Captured html pages, in archive order.
+    pub fn pages(&self) -> Vec<&Capture> {
+        self.urls
+            .iter()
+            .filter_map(|url| self.captures.get(url))
+            .filter(|capture| capture.status == 200 && capture.mime_type.starts_with("text/html"))
+            .collect()
+    }
+}
+
+/// status, header fields and body
+type HttpResponse = (u16, Vec<(String, String)>, Vec<u8>);
+
+/// Parses a captured http response.
+fn parse_response(block: &[u8]) -> Option<HttpResponse> {
+    let (head, body) = split_head(block)?;
+    let mut lines = head.lines();
+    // `HTTP/1.1 200 OK`
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect::<Vec<_>>();
+    let has = |name: &str, value: &str| {
+        headers.iter().any(|(field, content)| {
+            field.eq_ignore_ascii_case(name) && content.to_ascii_lowercase().contains(value)
+        })
+    };
+
+    // other tools record the body as sent
+    let mut body = body.to_vec();
+    if has("transfer-encoding", "chunked") {
+        body = dechunk(&body).unwrap_or(body);
+    }
+    if has("content-encoding", "gzip") {
+        let mut decoded = Vec::new();
+        if GzDecoder::new(&body[..]).read_to_end(&mut decoded).is_ok() {
+            body = decoded;
+        }
+    }
+
+    Some((status, headers, body))
+}
+
+/// Body of a `Transfer-Encoding: chunked` message, `None` if it's malformed.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        // chunk extensions follow a `;`
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+/// Opens an archive to browse and returns a document listing its pages.
+pub fn replay(file: &Path) -> Result<String, String> {
+    let archive = Archive::open(file, &env::temp_dir().join("prowser-archive"))?;
+    let pages = archive
+        .pages()
+        .iter()
+        .map(|capture| {
+            format!(
+                "<tr><td>{}</td><td><a href=\"{1}\">{1}</a></td></tr>",
+                escape(&capture.date),
+                escape(capture.url.as_str())
+            )
+        })
+        .collect::<String>();
+
+    let document = super::open_local_file("assets/archive.html")
+        .expect("'archive' asset to be present")
+        .replace("replace_path", &escape(&file.to_string_lossy()))
+        .replace("replace_pages", &pages);
+    *REPLAYING.lock().unwrap() = Some(archive);
+    Ok(document)
+}
+
+/// Stops browsing an archive, requests go to the network again.
+pub fn stop_replay() {
+    REPLAYING.lock().unwrap().take();
+}
+
+pub fn is_replaying() -> bool {
+    REPLAYING.lock().unwrap().is_some()
+}
+
+/// Captured response of an url from the archive being replayed, with the redirects followed.
+/// `None` if no archive is replayed.
+pub fn replayed(url: &Url) -> Option<Result<(Capture, Vec<Redirect>), String>> {
+    REPLAYING
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|archive| archive.resolve(url, config::get().max_redirects))
+}
+
+/// Is the file a WARC file, by its name?
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_ascii_lowercase();
+    name.ends_with(".warc") || name.ends_with(".warc.gz")
+}
+
+/// Error of a request that isn't sent while replaying an archive.
+pub fn not_archived(url: &Url) -> String {
+    format!("{}{}", NOT_ARCHIVED, url)
+}
+
+/// Url of a request that wasn't sent while replaying, `None` for other errors.
+pub fn not_archived_error(error: &str) -> Option<&str> {
+    error.strip_prefix(NOT_ARCHIVED)
+}
+
+/// Key of an url in an archive: the url without fragment
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.to_string()
+}
+
+/// Current time as `WARC-Date`
+fn warc_date() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    date::format_iso_date(now)
+}
+
+#[cfg(test)]
+mod archives {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn downloads_unrecorded() {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/zip".parse().unwrap());
+
+        assert!(!recorded(true, StatusCode::OK, &headers));
+        assert!(recorded(false, StatusCode::OK, &headers));
+        assert!(recorded(true, StatusCode::NOT_FOUND, &headers));
+        headers.insert("content-type", "text/html".parse().unwrap());
+        assert!(recorded(true, StatusCode::OK, &headers));
+    }
+
+    #[test]
+    fn recorded_and_replayed() {
+        let file = temp("prowser-warc-test.warc.gz");
+        let mut recording = Recording::create(&env::temp_dir(), "prowser-warc-test").unwrap();
+        assert_eq!(recording.path, file);
+        let client = reqwest::blocking::Client::new();
+
+        let redirect = client.get("http://example.com/").build().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("location", "/page#top".parse().unwrap());
+        recording
+            .write_exchange(
+                &redirect,
+                StatusCode::MOVED_PERMANENTLY,
+                Version::HTTP_11,
+                &headers,
+                b"",
+            )
+            .unwrap();
+
+        let page = client
+            .post("http://example.com/page")
+            .header("content-type", "text/plain")
+            .body("q=1")
+            .build()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "text/html".parse().unwrap());
+        headers.insert("transfer-encoding", "chunked".parse().unwrap());
+        recording
+            .write_exchange(
+                &page,
+                StatusCode::OK,
+                Version::HTTP_11,
+                &headers,
+                b"<p>a</p>",
+            )
+            .unwrap();
+
+        let records = read(&file).unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].header("WARC-Type"), Some("warcinfo"));
+        assert_eq!(records[3].header("warc-type"), Some("request"));
+        assert_eq!(
+            records[3].header("WARC-Concurrent-To"),
+            records[4].header("WARC-Record-ID")
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&records[3].block),
+            "POST /page HTTP/1.1\r\nHost: example.com\r\ncontent-type: text/plain\r\n\r\nq=1"
+        );
+
+        let url = |url| Url::parse(url).unwrap();
+        let dir = temp("prowser-warc-test");
+        let archive = Archive::open(&file, &dir).unwrap();
+        let (capture, redirects) = archive.resolve(&url("http://example.com/#a"), 10).unwrap();
+        assert_eq!(capture.url, url("http://example.com/page"));
+        assert_eq!(capture.mime_type, "text/html");
+        assert_eq!(fs::read(&capture.path).unwrap(), b"<p>a</p>");
+        assert_eq!(
+            redirects,
+            vec![Redirect {
+                status: 301,
+                from: url("http://example.com/"),
+                to: url("http://example.com/page#top"),
+            }]
+        );
+        assert_eq!(archive.pages(), vec![&capture]);
+        assert_eq!(
+            archive.resolve(&url("http://example.com/other"), 10),
+            Err(not_archived(&url("http://example.com/other")))
+        );
+        assert_eq!(
+            archive.resolve(&url("http://example.com/"), 0).unwrap_err(),
+            "Redirect loop: more than 0 redirects, the last to http://example.com/page#top"
+        );
+    }
+
+    #[test]
+    fn names_taken() {
+        let dir = temp("prowser-warc-names");
+        fs::create_dir(&dir).unwrap();
+
+        let first = Recording::create(&dir, "prowser-1").unwrap();
+        let second = Recording::create(&dir, "prowser-1").unwrap();
+        assert_eq!(first.path, dir.join("prowser-1.warc.gz"));
+        assert_eq!(second.path, dir.join("prowser-1-1.warc.gz"));
+        assert_eq!(read(&second.path).unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn captured_by_other_tools() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: text/css\r\nTransfer-Encoding: chunked\r\n\r\n4\r\np{}\n\r\n3;x=1\r\na{}\r\n0\r\n\r\n";
+        let warc = format!(
+            "WARC/1.0\r\nWARC-Type: response\r\nWARC-Target-URI: <http://example.com/a.css>\r\nContent-Length: {}\r\n\r\n{}\r\n\r\n\
+             WARC/1.0\r\nWARC-Type: resource\r\nWARC-Target-URI: file:///notes.txt\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nnotes\r\n\r\n",
+            response.len(),
+            response
+        );
+        let file = temp("prowser-warc-test.warc");
+        fs::write(&file, warc).unwrap();
+
+        let archive = Archive::open(&file, &temp("prowser-warc-test-other")).unwrap();
+        let url = |url| Url::parse(url).unwrap();
+        let css = archive.get(&url("http://example.com/a.css")).unwrap();
+        assert_eq!(fs::read(&css.path).unwrap(), b"p{}\na{}");
+        assert_eq!(css.mime_type, "text/css");
+        let notes = archive.get(&url("file:///notes.txt")).unwrap();
+        assert_eq!(
+            (notes.status, notes.mime_type.as_str()),
+            (200, "text/plain")
+        );
+        assert!(archive.pages().is_empty());
+
+        assert_eq!(
+            parse(b"WARC/1.1\r\nContent-Length: 10\r\n\r\nshort"),
+            Err(String::from("Truncated WARC record"))
+        );
+        assert!(is_archive(Path::new("/crawl/A.WARC.GZ")));
+        assert!(!is_archive(Path::new("/crawl/a.warc.txt")));
+    }
+}
@@ -4,6 +4,8 @@ use crate::dom;
 use crate::layout::{self, lbox, AnonymousBlock, BlockNode, InlineNode, Rect, TableRowNode};
 use crate::stylednode::StyledNode;
 
+use reqwest::Url;
+
 #[derive(Debug)]
 pub enum DisplayCommand {
     SolidColor(Color, Rect),
@@ -29,14 +31,22 @@ pub fn layout(style_root: StyledNode, width: f32, height: f32) -> lbox::LBox {
 }
 
 /// Converts a layout into a drawable `DisplayList`.
-pub fn build_display_list(layout_root: &lbox::LBox) -> DisplayList {
+/// Images are taken from the `resources` of the page.
+pub fn build_display_list(
+    layout_root: &lbox::LBox,
+    resources: &data_storage::Resources,
+) -> DisplayList {
     let mut list = Vec::new();
-    render_layout_box(&mut list, layout_root);
+    render_layout_box(&mut list, layout_root, resources);
     list
 }
 
 /// renders layout box and children
-fn render_layout_box(list: &mut DisplayList, layout_box: &lbox::LBox) {
+fn render_layout_box(
+    list: &mut DisplayList,
+    layout_box: &lbox::LBox,
+    resources: &data_storage::Resources,
+) {
     let mut visible = true;
     if let Some(Value::Keyword(keyword)) = get_value(layout_box, "visibility") {
         // FIXME: `collapse` eigentlich andere funktion wenn in Tabelle
@@ -46,7 +56,7 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &lbox::LBox) {
     }
 
     if visible {
-        render_background(list, layout_box);
+        render_background(list, layout_box, resources);
         render_borders(list, layout_box);
 
         if let InlineNode(ref node, _) | BlockNode(ref node) = layout_box.box_type {
@@ -55,11 +65,11 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &lbox::LBox) {
             } else if let dom::NodeType::Element(element) = &node.node.node_type {
                 if element.tag_name == "img" {
                     if let Some(url) = element.src() {
-                        render_image(list, layout_box, url);
+                        render_image(list, layout_box, url, resources);
                     }
                 } else if element.tag_name == "video" {
                     if let Some(url) = element.get_attribute("poster") {
-                        render_image(list, layout_box, url);
+                        render_image(list, layout_box, url, resources);
                     }
                 }
             }
@@ -103,11 +113,15 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &lbox::LBox) {
     layout_box
         .children
         .iter()
-        .for_each(|child| render_layout_box(list, child));
+        .for_each(|child| render_layout_box(list, child, resources));
 }
 
 /// adds display command for background
-fn render_background(list: &mut DisplayList, layout_box: &lbox::LBox) {
+fn render_background(
+    list: &mut DisplayList,
+    layout_box: &lbox::LBox,
+    resources: &data_storage::Resources,
+) {
     if let Some(Value::Color(color)) = get_value(layout_box, "background-color") {
         list.push(DisplayCommand::SolidColor(
             color,
@@ -116,7 +130,7 @@ fn render_background(list: &mut DisplayList, layout_box: &lbox::LBox) {
     }
 
     if let Some(Value::Url(url)) = get_value(layout_box, "background-image") {
-        render_image(list, layout_box, &url);
+        render_image(list, layout_box, &url, resources);
     } else if let Some(Value::Gradient(direction, colors)) =
         get_value(layout_box, "background-image")
     {
@@ -295,14 +309,24 @@ fn render_text(list: &mut DisplayList, layout_box: &lbox::LBox, text: &[String])
 }
 
 /// adds display command for images
-fn render_image(list: &mut DisplayList, layout_box: &lbox::LBox, url: &str) {
-    // TODO: painting should not download. at any pointer earlier.
-    // at best in/after layout, when it is known if the image is in the viewport
+fn render_image(
+    list: &mut DisplayList,
+    layout_box: &lbox::LBox,
+    url: &str,
+    resources: &data_storage::Resources,
+) {
+    // images are fetched ahead, before layout
+    // FIXME: only fetch images in or near the viewport
 
-    if let Ok(path) = data_storage::download_cache_path(
-        url,
-        vec!["image/jpeg", "image/gif", "image/png", "image/webp"],
-    ) {
+    let path = Url::parse(url).ok().and_then(|url| {
+        resources
+            .path(
+                &url,
+                vec!["image/jpeg", "image/gif", "image/png", "image/webp"],
+            )
+            .ok()
+    });
+    if let Some(path) = path {
         list.push(DisplayCommand::Image(path, layout_box.dimensions.content));
     } else if let layout::BoxType::InlineNode(node, _) = &layout_box.box_type {
         if let dom::NodeType::Element(element) = &node.node.node_type {
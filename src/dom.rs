@@ -46,6 +46,42 @@ impl Node {
             }),
         }
     }
+
+    /// Values of `attribute` of all `tag_name` elements, in document order.
+    pub fn attribute_values(&self, tag_name: &str, attribute: &str) -> Vec<String> {
+        let mut values = Vec::new();
+        if let NodeType::Element(element) = &self.node_type {
+            if element.tag_name == tag_name {
+                if let Some(value) = element.get_attribute(attribute) {
+                    values.push(value.clone());
+                }
+            }
+        }
+        for child in &self.children {
+            values.extend(child.attribute_values(tag_name, attribute));
+        }
+        values
+    }
+
+    /// `href` of the first `<link>` whose `rel` list contains `relationship`,
+    /// like `<link rel="shortcut icon" href="…">` for `icon`.
+    pub fn link_href(&self, relationship: &str) -> Option<&String> {
+        if let NodeType::Element(element) = &self.node_type {
+            if element.tag_name == "link"
+                && element.get_attribute("rel").is_some_and(|rel| {
+                    rel.split_whitespace()
+                        .any(|keyword| keyword.eq_ignore_ascii_case(relationship))
+                })
+            {
+                if let Some(href) = element.get_attribute("href") {
+                    return Some(href);
+                }
+            }
+        }
+        self.children
+            .iter()
+            .find_map(|child| child.link_href(relationship))
+    }
 }
 
 impl ElementData {
@@ -0,0 +1,499 @@
+//! Html forms: controls collected from the DOM, their values and submission
+//! <https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#form-submission-2>
+
+use crate::dom::{ElementData, Node, NodeType};
+use crate::logic;
+
+use reqwest::Url;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// attribute set on form controls in the DOM, `<form index>:<field index>`
+pub const FIELD_ATTRIBUTE: &str = "data-prowser-field";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+    /// url the form is submitted to
+    pub action: Url,
+    pub method: Method,
+    pub enctype: Enctype,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Enctype {
+    /// `application/x-www-form-urlencoded`
+    UrlEncoded,
+    /// `multipart/form-data`
+    Multipart,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub kind: Kind,
+    pub value: String,
+    /// checked checkboxes and radio buttons
+    pub checked: bool,
+    /// values of the options of a `select`
+    pub options: Vec<String>,
+    pub disabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// text, password, email, search and other typed in inputs
+    Text,
+    Hidden,
+    Checkbox,
+    Radio,
+    /// submit inputs and buttons, sent only if used to submit
+    Submit,
+    /// reset and plain buttons, file inputs, never sent
+    Button,
+    Select,
+    Textarea,
+}
+
+/// What a click on a form control does
+#[derive(Debug, PartialEq)]
+pub enum Click {
+    /// text to be typed in, starting with the current value
+    Edit(String),
+    Submit,
+    /// checkbox, radio button or selected option changed
+    Changed,
+    Nothing,
+}
+
+/// A request sending form data
+#[derive(Debug, PartialEq)]
+pub struct Submission {
+    pub url: Url,
+    pub post: Option<Post>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Post {
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+/// Collects the forms of a document, resolving actions against `url`.
+/// Their controls are marked with `FIELD_ATTRIBUTE`.
+pub fn collect(root: &mut Node, url: &Url) -> Vec<Form> {
+    let mut forms = Vec::new();
+    collect_node(root, url, &mut forms, None);
+    forms
+}
+
+fn collect_node(node: &mut Node, url: &Url, forms: &mut Vec<Form>, mut form: Option<usize>) {
+    if let NodeType::Element(element) = &mut node.node_type {
+        match element.tag_name.as_str() {
+            // nested forms are ignored by parsers
+            "form" if form.is_none() => {
+                forms.push(Form::new(element, url));
+                form = Some(forms.len() - 1);
+            }
+            "input" | "button" | "select" | "textarea" => {
+                if let Some(form) = form {
+                    let field = Field::new(element, &node.children);
+                    let fields = &mut forms[form].fields;
+                    element.attributes.insert(
+                        FIELD_ATTRIBUTE.to_string(),
+                        format!("{}:{}", form, fields.len()),
+                    );
+                    fields.push(field);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for child in &mut node.children {
+        collect_node(child, url, forms, form);
+    }
+}
+
+/// `(form, field)` indices of a control marked by `collect`
+pub fn field_index(element: &ElementData) -> Option<(usize, usize)> {
+    let (form, field) = element.get_attribute(FIELD_ATTRIBUTE)?.split_once(':')?;
+    Some((form.parse().ok()?, field.parse().ok()?))
+}
+
+impl Form {
+    fn new(element: &ElementData, url: &Url) -> Self {
+        let attribute = |name| {
+            element
+                .get_attribute(name)
+                .map(|value| value.trim().to_ascii_lowercase())
+                .unwrap_or_default()
+        };
+
+        let action = element
+            .get_attribute("action")
+            .and_then(|action| logic::resolve(Some(url), action))
+            .unwrap_or_else(|| url.clone());
+
+        Self {
+            action,
+            method: match attribute("method").as_str() {
+                "post" => Method::Post,
+                _ => Method::Get,
+            },
+            enctype: match attribute("enctype").as_str() {
+                "multipart/form-data" => Enctype::Multipart,
+                _ => Enctype::UrlEncoded,
+            },
+            fields: Vec::new(),
+        }
+    }
+
+    /// Handles a click on a field.
+    pub fn click(&mut self, index: usize) -> Click {
+        let field = match self.fields.get_mut(index) {
+            Some(field) if !field.disabled => field,
+            _ => return Click::Nothing,
+        };
+
+        match field.kind {
+            Kind::Text | Kind::Textarea => Click::Edit(field.value.clone()),
+            Kind::Submit => Click::Submit,
+            Kind::Checkbox => {
+                field.checked = !field.checked;
+                Click::Changed
+            }
+            Kind::Radio => {
+                let name = field.name.clone();
+                for (i, other) in self.fields.iter_mut().enumerate() {
+                    if other.kind == Kind::Radio && other.name == name {
+                        other.checked = i == index;
+                    }
+                }
+                Click::Changed
+            }
+            Kind::Select if !field.options.is_empty() => {
+                let selected = field
+                    .options
+                    .iter()
+                    .position(|option| *option == field.value);
+                let next = selected.map_or(0, |selected| (selected + 1) % field.options.len());
+                field.value = field.options[next].clone();
+                Click::Changed
+            }
+            Kind::Select | Kind::Hidden | Kind::Button => Click::Nothing,
+        }
+    }
+
+    /// Forms with a single text field are submitted when it is filled in, like on pressing enter.
+    pub fn submits_on_enter(&self) -> bool {
+        self.fields
+            .iter()
+            .filter(|field| field.kind == Kind::Text)
+            .count()
+            == 1
+    }
+
+    /// Name and value pairs sent, `submitter` is the button used to submit.
+    pub fn data(&self, submitter: Option<usize>) -> Vec<(String, String)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| !field.disabled && !field.name.is_empty())
+            .filter(|(i, field)| match field.kind {
+                Kind::Checkbox | Kind::Radio => field.checked,
+                Kind::Submit => submitter == Some(*i),
+                Kind::Button => false,
+                _ => true,
+            })
+            .map(|(_, field)| {
+                let value = match field.kind {
+                    Kind::Textarea => field.value.replace("\r\n", "\n").replace('\n', "\r\n"),
+                    _ => field.value.clone(),
+                };
+                (field.name.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Request for submitting the form with the button `submitter`.
+    pub fn submit(&self, submitter: Option<usize>) -> Submission {
+        let data = self.data(submitter);
+
+        match self.method {
+            Method::Get => {
+                let mut url = self.action.clone();
+                url.set_query(Some(&urlencoded(&data)));
+                Submission { url, post: None }
+            }
+            Method::Post => {
+                let post = match self.enctype {
+                    Enctype::UrlEncoded => Post {
+                        content_type: String::from("application/x-www-form-urlencoded"),
+                        body: urlencoded(&data).into_bytes(),
+                    },
+                    Enctype::Multipart => {
+                        let boundary = boundary();
+                        Post {
+                            content_type: format!("multipart/form-data; boundary={}", boundary),
+                            body: multipart(&data, &boundary),
+                        }
+                    }
+                };
+                Submission {
+                    url: self.action.clone(),
+                    post: Some(post),
+                }
+            }
+        }
+    }
+}
+
+impl Field {
+    fn new(element: &ElementData, children: &[Node]) -> Self {
+        let attribute = |name| element.get_attribute(name).cloned().unwrap_or_default();
+        let input_type = attribute("type").to_ascii_lowercase();
+
+        let mut field = Self {
+            name: attribute("name"),
+            kind: Kind::Text,
+            value: attribute("value"),
+            checked: element.get_attribute("checked").is_some(),
+            options: Vec::new(),
+            disabled: element.get_attribute("disabled").is_some(),
+        };
+
+        match element.tag_name.as_str() {
+            "button" => {
+                field.kind = match input_type.as_str() {
+                    "reset" | "button" => Kind::Button,
+                    _ => Kind::Submit,
+                };
+            }
+            "textarea" => {
+                field.kind = Kind::Textarea;
+                field.value = text_content(children);
+            }
+            "select" => {
+                field.kind = Kind::Select;
+                let mut selected = None;
+                options(children, &mut field.options, &mut selected);
+                field.value = selected
+                    .or_else(|| field.options.first().cloned())
+                    .unwrap_or_default();
+            }
+            _ => {
+                field.kind = match input_type.as_str() {
+                    "hidden" => Kind::Hidden,
+                    "checkbox" => Kind::Checkbox,
+                    "radio" => Kind::Radio,
+                    "submit" => Kind::Submit,
+                    "reset" | "button" | "image" | "file" => Kind::Button,
+                    _ => Kind::Text,
+                };
+                if matches!(field.kind, Kind::Checkbox | Kind::Radio) && field.value.is_empty() {
+                    field.value = String::from("on");
+                }
+            }
+        }
+
+        field
+    }
+}
+
+/// Values of `option`s, also inside `optgroup`s, and the last selected one.
+fn options(nodes: &[Node], options: &mut Vec<String>, selected: &mut Option<String>) {
+    for node in nodes {
+        if let NodeType::Element(element) = &node.node_type {
+            match element.tag_name.as_str() {
+                "option" => {
+                    let value = match element.get_attribute("value") {
+                        Some(value) => value.clone(),
+                        None => text_content(&node.children).trim().to_string(),
+                    };
+                    if element.get_attribute("selected").is_some() {
+                        *selected = Some(value.clone());
+                    }
+                    options.push(value);
+                }
+                "optgroup" => self::options(&node.children, options, selected),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn text_content(nodes: &[Node]) -> String {
+    nodes
+        .iter()
+        .map(|node| match &node.node_type {
+            NodeType::Text(text) => text.concat(),
+            NodeType::Element(_) => text_content(&node.children),
+        })
+        .collect()
+}
+
+/// `application/x-www-form-urlencoded` serialization
+/// <https://url.spec.whatwg.org/#urlencoded-serializing>
+pub fn urlencoded(data: &[(String, String)]) -> String {
+    data.iter()
+        .map(|(name, value)| format!("{}={}", form_encode(name), form_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn form_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => {
+                (byte as char).to_string()
+            }
+            b' ' => String::from("+"),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// `multipart/form-data` body, <https://tools.ietf.org/html/rfc7578>
+fn multipart(data: &[(String, String)], boundary: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in data {
+        let name = name
+            .replace('"', "%22")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                boundary, name
+            )
+            .into_bytes(),
+        );
+        body.extend(value.as_bytes());
+        body.extend(b"\r\n");
+    }
+    body.extend(format!("--{}--\r\n", boundary).into_bytes());
+    body
+}
+
+/// Boundary unlikely to be in the data
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos())
+        .unwrap_or_default();
+    format!("----prowser{:x}", nanos)
+}
+
+#[cfg(test)]
+mod controls {
+    use super::*;
+    use crate::html;
+
+    fn forms(source: &str) -> (Node, Vec<Form>) {
+        let url = Url::parse("https://a.example/b/c").unwrap();
+        let mut root = html::parse(source.to_string(), Some(url.clone())).root;
+        let forms = collect(&mut root, &url);
+        (root, forms)
+    }
+
+    const SEARCH: &str = "<form action='/search' method='get'>\
+        <input name='q' value='rust lang'>\
+        <input type='hidden' name='lang' value='en'>\
+        <input type='checkbox' name='safe' checked>\
+        <input type='checkbox' name='images' value='yes'>\
+        <input type='radio' name='size' value='s'><input type='radio' name='size' value='m' checked>\
+        <select name='sort'><option>relevance</option><optgroup><option value='date' selected>Newest</option></optgroup></select>\
+        <textarea name='notes'>a b</textarea>\
+        <input type='text' name='off' disabled>\
+        <input type='submit' name='go' value='Search'><button name='other'>Other</button>\
+        </form>";
+
+    #[test]
+    fn collected() {
+        let (_, forms) = forms(SEARCH);
+
+        assert_eq!(forms.len(), 1);
+        assert_eq!(forms[0].action.as_str(), "https://a.example/search");
+        assert_eq!(forms[0].method, Method::Get);
+        assert_eq!(forms[0].fields.len(), 11);
+        assert_eq!(forms[0].fields[6].options, vec!["relevance", "date"]);
+    }
+
+    #[test]
+    fn data() {
+        let (_, mut forms) = forms(SEARCH);
+        // line breaks are sent as CRLF
+        forms[0].fields[7].value = String::from("a\nb");
+
+        assert_eq!(
+            urlencoded(&forms[0].data(Some(9))),
+            "q=rust+lang&lang=en&safe=on&size=m&sort=date&notes=a%0D%0Ab&go=Search"
+        );
+        assert_eq!(
+            forms[0].submit(Some(10)).url.as_str(),
+            "https://a.example/search?q=rust+lang&lang=en&safe=on&size=m&sort=date&notes=a%0D%0Ab&other="
+        );
+    }
+
+    #[test]
+    fn clicks() {
+        let (_, mut forms) = forms(SEARCH);
+        let form = &mut forms[0];
+
+        assert_eq!(form.click(0), Click::Edit(String::from("rust lang")));
+        assert_eq!(form.click(3), Click::Changed);
+        assert_eq!(form.click(4), Click::Changed);
+        assert_eq!(form.click(6), Click::Changed);
+        assert_eq!(form.click(8), Click::Nothing);
+        assert_eq!(form.click(9), Click::Submit);
+
+        assert_eq!(
+            urlencoded(&form.data(None)),
+            "q=rust+lang&lang=en&safe=on&images=yes&size=s&sort=relevance&notes=a+b"
+        );
+    }
+
+    #[test]
+    fn marked() {
+        let (root, _) = forms("<p><input name='outside'></p><form><p><input name='a'></p><select name='b'></select></form>");
+
+        let marked = root.attribute_values("input", FIELD_ATTRIBUTE);
+        assert_eq!(marked, vec!["0:0"]);
+        assert_eq!(
+            root.attribute_values("select", FIELD_ATTRIBUTE),
+            vec!["0:1"]
+        );
+    }
+
+    #[test]
+    fn post() {
+        let (_, forms) = forms(
+            "<form method=POST action='https://b.example/login'><input name='user' value='a&b=c'></form>\
+             <form method=post enctype='multipart/form-data'><input name='x\"y' value='ü'></form>",
+        );
+
+        let urlencoded = forms[0].submit(None);
+        assert_eq!(urlencoded.url.as_str(), "https://b.example/login");
+        let post = urlencoded.post.unwrap();
+        assert_eq!(post.content_type, "application/x-www-form-urlencoded");
+        assert_eq!(post.body, b"user=a%26b%3Dc");
+
+        let multipart = forms[1].submit(None);
+        assert_eq!(multipart.url.as_str(), "https://a.example/b/c");
+        let post = multipart.post.unwrap();
+        let boundary = post.content_type.split("boundary=").nth(1).unwrap();
+        assert_eq!(
+            String::from_utf8(post.body).unwrap(),
+            format!(
+                "--{0}\r\nContent-Disposition: form-data; name=\"x%22y\"\r\n\r\nü\r\n--{0}--\r\n",
+                boundary
+            )
+        );
+    }
+}
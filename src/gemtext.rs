@@ -0,0 +1,232 @@
+//! Gemtext to DOM converter
+//! <https://gemini.circumlunar.space/docs/gemtext.gmi>
+
+use crate::dom::Node;
+
+use reqwest::Url;
+use std::collections::HashMap;
+
+/// Converts a gemtext document into a DOM.
+/// Links are resolved against `url`.
+pub fn parse(source: &str, url: &Url) -> Node {
+    let mut title = None;
+    let mut body = Vec::new();
+    let mut list = Vec::new();
+    let mut preformatted: Option<Vec<Node>> = None;
+
+    for line in source.lines() {
+        if line.starts_with("```") {
+            match preformatted.take() {
+                Some(lines) => body.push(element("pre", lines)),
+                None => preformatted = Some(Vec::new()),
+            }
+            continue;
+        }
+        if let Some(lines) = &mut preformatted {
+            lines.push(line_element(line));
+            continue;
+        }
+
+        // consecutive list items form one list
+        if let Some(item) = line.strip_prefix("* ") {
+            list.push(element("li", vec![Node::text(item.trim().to_string())]));
+            continue;
+        } else if !list.is_empty() {
+            body.push(element("ul", list));
+            list = Vec::new();
+        }
+
+        if let Some(link) = line.strip_prefix("=>") {
+            let link = link.trim();
+            let (target, label) = match link.find(char::is_whitespace) {
+                Some(space) => (&link[..space], link[space..].trim()),
+                None => (link, link),
+            };
+            if target.is_empty() {
+                continue;
+            }
+
+            let href = url
+                .join(target)
+                .map_or_else(|_| target.to_string(), |url| url.to_string());
+
+            let mut attributes = HashMap::new();
+            attributes.insert(String::from("href"), href);
+            body.push(element(
+                "p",
+                vec![Node::elem(
+                    String::from("a"),
+                    attributes,
+                    vec![Node::text(label.to_string())],
+                )],
+            ));
+        } else if let Some(heading) = heading(line) {
+            let (level, text) = heading;
+            if title.is_none() {
+                title = Some(text.to_string());
+            }
+            body.push(element(level, vec![Node::text(text.to_string())]));
+        } else if let Some(quote) = line.strip_prefix('>') {
+            body.push(element(
+                "blockquote",
+                vec![Node::text(quote.trim().to_string())],
+            ));
+        } else if !line.trim().is_empty() {
+            body.push(element("p", vec![Node::text(line.to_string())]));
+        }
+    }
+
+    if !list.is_empty() {
+        body.push(element("ul", list));
+    }
+    // unterminated preformatted block
+    if let Some(lines) = preformatted {
+        body.push(element("pre", lines));
+    }
+
+    document(&title.unwrap_or_else(|| url.to_string()), body)
+}
+
+/// Shows text as is, line by line.
+pub fn preformatted(source: &str, title: &str) -> Node {
+    document(
+        title,
+        vec![element("pre", source.lines().map(line_element).collect())],
+    )
+}
+
+/// A page with a heading and a message, for prompts and errors.
+pub fn message(title: &str, text: &str) -> Node {
+    document(
+        title,
+        vec![
+            element("h1", vec![Node::text(title.to_string())]),
+            element("p", vec![Node::text(text.to_string())]),
+        ],
+    )
+}
+
+/// `<html>` with `<head><title>` and `<body>`
+pub fn document(title: &str, body: Vec<Node>) -> Node {
+    element(
+        "html",
+        vec![
+            element(
+                "head",
+                vec![element("title", vec![Node::text(title.to_string())])],
+            ),
+            element("body", body),
+        ],
+    )
+}
+
+/// `#`, `##` or `###` heading
+fn heading(line: &str) -> Option<(&'static str, &str)> {
+    if let Some(text) = line.strip_prefix("###") {
+        Some(("h3", text.trim()))
+    } else if let Some(text) = line.strip_prefix("##") {
+        Some(("h2", text.trim()))
+    } else if let Some(text) = line.strip_prefix('#') {
+        Some(("h1", text.trim()))
+    } else {
+        None
+    }
+}
+
+/// A single preformatted line. Empty lines keep their height.
+fn line_element(line: &str) -> Node {
+    let mut attributes = HashMap::new();
+    attributes.insert(String::from("style"), String::from("display:block"));
+    let text = if line.is_empty() { " " } else { line };
+    Node::elem(
+        String::from("div"),
+        attributes,
+        vec![Node::text(text.replace('\t', "    "))],
+    )
+}
+
+fn element(tag_name: &str, children: Vec<Node>) -> Node {
+    Node::elem(tag_name.to_string(), HashMap::new(), children)
+}
+
+#[cfg(test)]
+mod convert {
+    use super::*;
+    use crate::dom::NodeType;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    /// tag names of the children of `<body>`
+    fn body_tags(document: &Node) -> Vec<String> {
+        document.children[1]
+            .children
+            .iter()
+            .map(|child| match &child.node_type {
+                NodeType::Element(element) => element.tag_name.clone(),
+                NodeType::Text(_) => String::from("#text"),
+            })
+            .collect()
+    }
+
+    fn title(document: &Node) -> String {
+        match &document.children[0].children[0].children[0].node_type {
+            NodeType::Text(text) => text[0].clone(),
+            _ => String::new(),
+        }
+    }
+
+    #[test]
+    fn line_types() {
+        let document = parse(
+            "# Title\n## Sub\n### Subsub\ntext\n\n> quote\n* a\n* b\n=> /x link",
+            &url("gemini://example.com/"),
+        );
+
+        assert_eq!(
+            body_tags(&document),
+            vec!["h1", "h2", "h3", "p", "blockquote", "ul", "p"]
+        );
+        assert_eq!(title(&document), "Title");
+    }
+
+    #[test]
+    fn link() {
+        let document = parse(
+            "=> docs/a.gmi  The docs",
+            &url("gemini://example.com/dir/index.gmi"),
+        );
+
+        let link = &document.children[1].children[0].children[0];
+        if let NodeType::Element(element) = &link.node_type {
+            assert_eq!(
+                element.get_attribute("href"),
+                Some(&String::from("gemini://example.com/dir/docs/a.gmi"))
+            );
+        } else {
+            panic!("expected a link");
+        }
+        assert!(
+            matches!(&link.children[0].node_type, NodeType::Text(text) if text[0] == "The docs")
+        );
+    }
+
+    #[test]
+    fn preformatted_block() {
+        let document = parse(
+            "```ascii art\n# not a heading\n=> not a link\n```\n",
+            &url("gemini://a/"),
+        );
+
+        assert_eq!(body_tags(&document), vec!["pre"]);
+        assert_eq!(document.children[1].children[0].children.len(), 2);
+    }
+
+    #[test]
+    fn no_heading_title() {
+        let document = parse("text", &url("gemini://example.com/"));
+
+        assert_eq!(title(&document), "gemini://example.com/");
+    }
+}
@@ -0,0 +1,79 @@
+//! Gophermap to DOM converter
+
+use crate::data_storage::gopher::Item;
+use crate::dom::Node;
+use crate::gemtext;
+
+use std::collections::HashMap;
+
+/// Converts menu items into a DOM, one line per item.
+/// Menus are often aligned for monospace fonts, so they are rendered as preformatted text.
+pub fn render(items: &[Item], title: &str) -> Node {
+    let lines = items
+        .iter()
+        .map(|item| {
+            let text = format!("{} {}", label(item.kind), item.display);
+            let content = match item.url() {
+                Some(href) => {
+                    let mut attributes = HashMap::new();
+                    attributes.insert(String::from("href"), href.to_string());
+                    Node::elem(String::from("a"), attributes, vec![Node::text(text)])
+                }
+                None => Node::text(text),
+            };
+
+            let mut style = HashMap::new();
+            style.insert(String::from("style"), String::from("display:block"));
+            Node::elem(String::from("div"), style, vec![content])
+        })
+        .collect();
+
+    gemtext::document(
+        title,
+        vec![Node::elem(String::from("pre"), HashMap::new(), lines)],
+    )
+}
+
+/// Fixed width marker of the item type
+fn label(kind: char) -> &'static str {
+    match kind {
+        'i' => "     ",
+        '0' => "[TXT]",
+        '1' => "[DIR]",
+        '3' => "[ERR]",
+        '7' => "[ ? ]",
+        '8' | 'T' => "[TEL]",
+        'g' | 'I' | 'p' => "[IMG]",
+        'h' => "[WWW]",
+        's' | ';' => "[MED]",
+        _ => "[BIN]",
+    }
+}
+
+#[cfg(test)]
+mod menu {
+    use super::*;
+    use crate::data_storage::gopher::parse_menu;
+    use crate::dom::NodeType;
+
+    #[test]
+    fn links() {
+        let items = parse_menu(
+            "iWelcome\t\t\t0\r\n\
+             1Docs\t/docs\texample.com\t70\r\n",
+        );
+
+        let document = render(&items, "gopher://example.com/");
+        let lines = &document.children[1].children[0].children;
+
+        assert_eq!(lines.len(), 2);
+        assert!(matches!(&lines[0].children[0].node_type, NodeType::Text(_)));
+        match &lines[1].children[0].node_type {
+            NodeType::Element(element) => assert_eq!(
+                element.get_attribute("href"),
+                Some(&String::from("gopher://example.com:70/1/docs"))
+            ),
+            NodeType::Text(_) => panic!("expected a link"),
+        }
+    }
+}
@@ -39,6 +39,22 @@ pub enum Command {
     Click(i32, i32, sdl2::mouse::MouseButton),
     Scroll(i32),
     Fullscreen,
+    /// all sites or only the current one
+    ClearCookies(bool),
+    /// add the search engine linked by the current page
+    AddSearchEngine,
+    /// show the url and redirects of the current page in a new tab
+    PageInfo,
+    /// show `about:downloads` in a new tab
+    Downloads,
+    /// go offline, or online again
+    ToggleOffline,
+    /// save the current page with its resources as one html file
+    SavePage,
+    /// start or stop recording http exchanges to a WARC file
+    ToggleRecording,
+    /// cancel loading, quit if nothing is loading
+    Stop,
 }
 
 /// Inits sdl2
@@ -105,12 +121,14 @@ pub fn handle_events(
 
     for event in event_pump.poll_iter() {
         match event {
-            Event::Quit { .. }
-            | Event::KeyDown {
+            Event::Quit { .. } => {
+                commands.push(Command::Quit);
+            }
+            Event::KeyDown {
                 keycode: Some(Keycode::Escape),
                 ..
             } => {
-                commands.push(Command::Quit);
+                commands.push(Command::Stop);
             }
             Event::Window {
                 win_event: WindowEvent::Resized(..),
@@ -150,6 +168,20 @@ pub fn handle_events(
                 Keycode::Up => commands.push(Command::ScrollUp),
                 Keycode::F5 => commands.push(Command::Reload(false)),
                 Keycode::F11 => commands.push(Command::Fullscreen),
+                Keycode::Delete => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    let flag_shift = mod_state & 0x0001;
+
+                    match (flag_ctrl, flag_shift) {
+                        (64, 0) => {
+                            commands.push(Command::ClearCookies(false));
+                        }
+                        (64, 1) => {
+                            commands.push(Command::ClearCookies(true));
+                        }
+                        _ => {}
+                    }
+                }
                 Keycode::Backspace => {
                     text_input.pop();
                 }
@@ -165,6 +197,44 @@ pub fn handle_events(
                         commands.push(Command::StartTextInput);
                     }
                 }
+                Keycode::K => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    if flag_ctrl == 64 {
+                        commands.push(Command::AddSearchEngine);
+                    }
+                }
+                Keycode::I => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    if flag_ctrl == 64 {
+                        commands.push(Command::PageInfo);
+                    }
+                }
+                Keycode::O => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    let flag_shift = mod_state & 0x0001;
+                    if (flag_ctrl, flag_shift) == (64, 1) {
+                        commands.push(Command::ToggleOffline);
+                    }
+                }
+                Keycode::R => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    let flag_shift = mod_state & 0x0001;
+                    if (flag_ctrl, flag_shift) == (64, 1) {
+                        commands.push(Command::ToggleRecording);
+                    }
+                }
+                Keycode::S => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    if flag_ctrl == 64 {
+                        commands.push(Command::SavePage);
+                    }
+                }
+                Keycode::J => {
+                    let flag_ctrl = mod_state & 0x0040;
+                    if flag_ctrl == 64 {
+                        commands.push(Command::Downloads);
+                    }
+                }
                 Keycode::W => {
                     let flag_ctrl = mod_state & 0x0040;
                     let flag_shift = mod_state & 0x0001;
@@ -361,17 +431,26 @@ pub fn display(
             ));
         }
 
-        let tab_title = if let Some(title) = &tab.title {
+        // show the url being loaded instead of the current title
+        let mut tab_title = if let Some(url) = tab.loading_url() {
+            url.chars().take(max_title_length).collect()
+        } else if let Some(title) = &tab.title {
             if title.len() < max_title_length {
                 title.clone()
             } else {
                 title[..max_title_length].to_string()
             }
-        } else if tab.url.len() < max_title_length {
-            tab.url.clone()
+        } else if tab.address().len() < max_title_length {
+            tab.address().to_string()
         } else {
-            tab.url[..max_title_length].to_string()
+            tab.address()[..max_title_length].to_string()
         };
+        if tab.offline && tab.loading_url().is_none() {
+            tab_title = format!("offline copy: {}", tab_title)
+                .chars()
+                .take(max_title_length)
+                .collect();
+        }
         ui_list.push(DisplayCommand::Text(
             Color {
                 r: 0,
@@ -409,6 +488,24 @@ pub fn display(
             String::new(),
         ));
 
+        // loading indicator
+        if tab.loading_url().is_some() {
+            ui_list.push(DisplayCommand::SolidColor(
+                Color {
+                    r: 52,
+                    g: 152,
+                    b: 219,
+                    a: 255,
+                },
+                Rect {
+                    x: i as f32 * (tab_width + 2.0),
+                    y: 20.0,
+                    width: tab_width,
+                    height: 2.0,
+                },
+            ));
+        }
+
         // theme color
         if let Some(theme_color) = &tab.color {
             ui_list.push(DisplayCommand::SolidColor(
@@ -492,7 +589,7 @@ pub fn display(
             height: 21.0,
         },
     ));
-    if !tabs.is_empty() && !tabs[current_tab].url.is_empty() {
+    if !tabs.is_empty() && tabs[current_tab].url.is_some() {
         ui_list.push(DisplayCommand::Text(
             Color {
                 r: 200,
@@ -500,7 +597,7 @@ pub fn display(
                 b: 200,
                 a: 255,
             },
-            tabs[current_tab].url.clone(),
+            tabs[current_tab].address().to_string(),
             Rect {
                 x: 110.0,
                 y: 24.0,
@@ -0,0 +1,102 @@
+//! Pages visited, kept in `data/history`,
+//! one visit per line with tab separated time (seconds since unix epoch), url and title
+
+use reqwest::Url;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "data/history";
+
+/// A visit of a page
+#[derive(Debug, PartialEq)]
+pub struct Visit {
+    /// seconds since unix epoch
+    pub time: u64,
+    pub url: Url,
+    pub title: String,
+}
+
+/// Adds a visit of `url` now.
+pub fn record(url: &Url, title: &str) -> Result<(), String> {
+    fs::create_dir_all("data").map_err(|e| e.to_string())?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    append(HISTORY_FILE, time, url, title)
+}
+
+/// Visited pages, the most recent first.
+pub fn visits() -> Vec<Visit> {
+    read(HISTORY_FILE)
+}
+
+/// Forgets all visits.
+pub fn clear() -> Result<(), String> {
+    match fs::remove_file(HISTORY_FILE) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.to_string()),
+        _ => Ok(()),
+    }
+}
+
+fn append(path: &str, time: u64, url: &Url, title: &str) -> Result<(), String> {
+    // tabs and line breaks would end the cell or line
+    let clean = |text: &str| text.replace(['\t', '\n', '\r'], " ");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}\t{}\t{}", time, url, clean(title)).map_err(|e| e.to_string())
+}
+
+fn read(path: &str) -> Vec<Visit> {
+    let mut visits = fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut cells = line.splitn(3, '\t');
+            Some(Visit {
+                time: cells.next()?.parse().ok()?,
+                url: Url::parse(cells.next()?).ok()?,
+                title: cells.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+    visits.reverse();
+    visits
+}
+
+#[cfg(test)]
+mod stored {
+    use super::*;
+
+    #[test]
+    fn recorded() {
+        let path = std::env::temp_dir().join("prowser-history-test");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+
+        let url = |url| Url::parse(url).unwrap();
+        append(path, 1, &url("https://example.com/"), "Example\tDomain").unwrap();
+        append(path, 2, &url("https://example.org/a"), "").unwrap();
+        fs::write(path, fs::read_to_string(path).unwrap() + "not a visit\n").unwrap();
+
+        assert_eq!(
+            read(path),
+            vec![
+                Visit {
+                    time: 2,
+                    url: url("https://example.org/a"),
+                    title: String::new(),
+                },
+                Visit {
+                    time: 1,
+                    url: url("https://example.com/"),
+                    title: String::from("Example Domain"),
+                },
+            ]
+        );
+    }
+}
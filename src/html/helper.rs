@@ -1,9 +1,10 @@
-use crate::data_storage;
 use crate::dom;
 use crate::html::Parser;
 use crate::logic;
 
+use reqwest::Url;
 use std::collections::HashMap;
+use std::time::Duration;
 
 impl Parser {
     /// Parse a single element, including its open tag, contents, and closing tag (if present).
@@ -19,6 +20,39 @@ impl Parser {
 
         let attributes = self.parse_attributes()?;
 
+        match tag_name.as_str() {
+            // only the first `<base href>` counts, its href is already resolved
+            "base" if !self.base => {
+                if let Some(base) = attributes
+                    .get("href")
+                    .and_then(|href| Url::parse(href).ok())
+                {
+                    self.url = Some(base);
+                    self.base = true;
+                }
+            }
+            "meta" if self.refresh.is_none() => {
+                let http_equiv = attributes.get("http-equiv").map(String::as_str);
+                if http_equiv.is_some_and(|value| value.eq_ignore_ascii_case("refresh")) {
+                    self.refresh = attributes
+                        .get("content")
+                        .and_then(|content| super::refresh(content))
+                        .and_then(|(delay, url)| {
+                            let url = logic::resolve(self.url.as_ref(), url)?;
+                            // no `about:` or browser actions without a click
+                            let page_scheme = self.url.as_ref().map(Url::scheme);
+                            if !matches!(url.scheme(), "http" | "https")
+                                && Some(url.scheme()) != page_scheme
+                            {
+                                return None;
+                            }
+                            Some((Duration::from_secs(delay), url))
+                        });
+                }
+            }
+            _ => {}
+        }
+
         if let Some('/') = self.next_char() {
             self.consume_char(); // /
         }
@@ -37,17 +71,14 @@ impl Parser {
             if tag_name == "link" {
                 if let Some(relationship) = attributes.get("rel") {
                     if relationship == "stylesheet" {
-                        if let Some(raw_url) = attributes.get("href") {
-                            let query;
-                            let url = logic::absolute_path(&self.url, raw_url);
-                            if let Some(media_query) = attributes.get("media") {
-                                query = media_query.clone();
-                            } else {
-                                let _ = data_storage::download(&url).is_ok();
-                                query = String::new();
-                            }
-
-                            self.style.push((url, Some(query)));
+                        if let Some(url) = attributes
+                            .get("href")
+                            .and_then(|href| logic::resolve(self.url.as_ref(), href))
+                        {
+                            // fetched after parsing, together with the other subresources
+                            let query = attributes.get("media").cloned().unwrap_or_default();
+
+                            self.style.push((url.to_string(), Some(query)));
                         }
                     }
                 }
@@ -115,8 +146,11 @@ impl Parser {
             String::new()
         };
 
-        if name == "src" {
-            value = logic::absolute_path(&self.url, &value);
+        // urls are left as they are in documents without one
+        if let ("src" | "href" | "action" | "poster", Some(base)) = (name.as_str(), &self.url) {
+            if let Some(url) = logic::resolve(Some(base), &value) {
+                value = url.to_string();
+            }
         }
 
         (name, value)
@@ -207,7 +241,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<style></style>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -220,14 +256,18 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("<style type=' text/css '></style>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("<style type='text/css; charset=utf-8'></style>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -240,14 +280,18 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("<style type=''></style>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("<style type='text/CSS'></style>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -261,7 +305,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<script>console.log('Test');</script>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -273,7 +319,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("href='https://example.com'"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -288,7 +336,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("href"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -300,14 +350,18 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("'test'"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("\"test\""),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -320,7 +374,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("test"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -333,7 +389,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("https://git.sr.ht/~sircmpwn/sr.ht-docs"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -348,7 +406,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("href='https://example.com' target='_blank'>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -365,7 +425,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("data-src='https://example.com' target='_blank'>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -381,7 +443,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("href='https://example.com' href='https://test.com'>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -397,7 +461,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<!DOCTYPE html>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -413,7 +479,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<![CDATA[some stuff]]>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -437,7 +505,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<!DOCTYPE html PUBLIC \"foo>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -454,7 +524,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<!-- comment -->"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -470,7 +542,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<div id=foo></div class=bar>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -483,7 +557,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<div></div/>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -500,7 +576,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -513,7 +591,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<![CDATA"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -528,7 +608,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<div id="),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -541,7 +623,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<!-- comment --!>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -556,7 +640,9 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("<!-->"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -568,7 +654,9 @@ mod parse_element {
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("<!--->"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -583,7 +671,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("<! treated as comment >"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -598,7 +688,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("id=>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -610,7 +702,9 @@ mod parse_element {
         let mut parser1 = Parser {
             pos: 0,
             input: String::from("foo<div"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -622,7 +716,9 @@ mod parse_element {
         let mut parser2 = Parser {
             pos: 0,
             input: String::from("id'bar'"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -637,7 +733,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("foo=b'ar'"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -655,7 +753,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("foo=\"bar\" =\"baz\""),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -672,7 +772,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("xml:lang='en-US'"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
@@ -687,7 +789,9 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("v-bind:crates_map='crates' v-bind:tag_filter='tag_filter'>"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
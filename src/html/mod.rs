@@ -2,16 +2,34 @@ mod helper;
 
 use crate::dom;
 
+use reqwest::Url;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug)]
 struct Parser {
     pos: usize,
     input: String,
-    url: String,
+    /// base url of the document, relative urls are resolved against it
+    url: Option<Url>,
+    /// whether `url` was set by a `<base href>`
+    base: bool,
+    /// delay and target of the first `<meta http-equiv="refresh">`
+    refresh: Option<(Duration, Url)>,
     style: Vec<(String, Option<String>)>,
 }
 
+/// A parsed HTML document
+pub struct Document {
+    pub root: dom::Node,
+    /// sources of `<style>` elements, and urls of linked stylesheets with their media query
+    pub stylesheets: Vec<(String, Option<String>)>,
+    /// base url from `<base href>`, the document's url otherwise
+    pub base: Option<Url>,
+    /// `<meta http-equiv="refresh" content="5; url=…">`
+    pub refresh: Option<(Duration, Url)>,
+}
+
 impl Parser {
     /// Read the current character without consuming it.
     fn next_char(&self) -> Option<char> {
@@ -134,26 +152,69 @@ impl Parser {
     }
 }
 
-/// Parse an HTML document and return the root element.
-pub fn parse(source: String, url: String) -> (dom::Node, Vec<(String, Option<String>)>) {
-    let (mut nodes, style) = Parser {
+/// Parse an HTML document with the url `url`.
+pub fn parse(source: String, url: Option<Url>) -> Document {
+    let mut parser = Parser {
         pos: 0,
         input: source,
         url,
+        base: false,
+        refresh: None,
         style: Vec::new(),
-    }
-    .parse_nodes();
+    };
+    let (mut nodes, stylesheets) = parser.parse_nodes();
     // If the document contains a root element, just return it. Otherwise, create one.
-    if nodes.len() == 1 {
-        (nodes.swap_remove(0), style)
+    let root = if nodes.len() == 1 {
+        nodes.swap_remove(0)
     } else {
-        (
-            dom::Node::elem("html".to_string(), HashMap::new(), nodes),
-            style,
-        )
+        dom::Node::elem("html".to_string(), HashMap::new(), nodes)
+    };
+
+    Document {
+        root,
+        stylesheets,
+        base: parser.url,
+        refresh: parser.refresh,
     }
 }
 
+/// Delay and url of a `refresh` meta element's `content`, the url is empty to reload.
+/// <https://html.spec.whatwg.org/multipage/semantics.html#shared-declarative-refresh-steps>
+fn refresh(content: &str) -> Option<(u64, &str)> {
+    let content = content.trim_start();
+    let digits = content
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(content.len());
+    if digits == 0 && !content.starts_with('.') {
+        return None;
+    }
+    let delay = content[..digits].parse().unwrap_or(0);
+
+    // fractions are ignored
+    let rest = content[digits..].trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if !rest.is_empty() && !rest.starts_with(|c: char| c.is_whitespace() || c == ';' || c == ',') {
+        return None;
+    }
+    let mut url = rest
+        .trim_start()
+        .trim_start_matches([';', ','])
+        .trim_start();
+
+    // `url=` is optional
+    if url.get(..3).is_some_and(|s| s.eq_ignore_ascii_case("url")) {
+        let after = url[3..].trim_start();
+        if let Some(after) = after.strip_prefix('=') {
+            url = after.trim_start();
+        }
+    }
+    if let Some(quote) = url.chars().next().filter(|c| *c == '"' || *c == '\'') {
+        url = &url[1..];
+        url = &url[..url.find(quote).unwrap_or(url.len())];
+    }
+
+    Some((delay, url.trim_end()))
+}
+
 #[cfg(test)]
 mod parse_element {
     use super::*;
@@ -163,10 +224,129 @@ mod parse_element {
         let mut parser = Parser {
             pos: 0,
             input: String::from("crates-list"),
-            url: String::new(),
+            url: None,
+            base: false,
+            refresh: None,
             style: Vec::new(),
         };
 
         assert_eq!(parser.parse_tag_name(), String::from("crates-list"));
     }
 }
+
+#[cfg(test)]
+mod document {
+    use super::*;
+
+    fn url(url: &str) -> Option<Url> {
+        Url::parse(url).ok()
+    }
+
+    #[test]
+    fn base_href() {
+        let document = parse(
+            String::from(
+                "<html><head><base href='/static/'><base href='https://other.example/'></head>\
+                <body><img src='a.png'><a href='b.html'>b</a><form action='?q=1'></form></body></html>",
+            ),
+            url("https://example.com/page/index.html"),
+        );
+
+        assert_eq!(document.base, url("https://example.com/static/"));
+        assert_eq!(
+            document.root.attribute_values("img", "src"),
+            vec!["https://example.com/static/a.png"]
+        );
+        assert_eq!(
+            document.root.attribute_values("a", "href"),
+            vec!["https://example.com/static/b.html"]
+        );
+        assert_eq!(
+            document.root.attribute_values("form", "action"),
+            vec!["https://example.com/static/?q=1"]
+        );
+    }
+
+    #[test]
+    fn without_base() {
+        let document = parse(
+            String::from("<link rel='stylesheet' href='../s.css'><a href='#top'>top</a>"),
+            url("https://example.com/a/b/c"),
+        );
+
+        assert_eq!(document.base, url("https://example.com/a/b/c"));
+        assert_eq!(
+            document.stylesheets,
+            vec![(
+                String::from("https://example.com/a/s.css"),
+                Some(String::new())
+            )]
+        );
+        assert_eq!(
+            document.root.attribute_values("a", "href"),
+            vec!["https://example.com/a/b/c#top"]
+        );
+    }
+
+    #[test]
+    fn meta_refresh() {
+        let document = parse(
+            String::from(
+                "<head><meta http-equiv='Refresh' content='3; URL=\"/next\"'>\
+                <meta http-equiv='refresh' content='0; url=/other'></head>",
+            ),
+            url("https://example.com/a"),
+        );
+
+        assert_eq!(
+            document.refresh,
+            Some((
+                Duration::from_secs(3),
+                url("https://example.com/next").unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn meta_refresh_scheme() {
+        let refresh = |content: &str, page: &str| {
+            let source = format!("<meta http-equiv='refresh' content='{}'>", content);
+            parse(source, url(page))
+                .refresh
+                .map(|(_, url)| url.to_string())
+        };
+
+        assert_eq!(
+            refresh("0; url=about:cache?clear", "https://example.com/"),
+            None
+        );
+        assert_eq!(
+            refresh("0; url=javascript:alert(1)", "https://example.com/"),
+            None
+        );
+        assert_eq!(
+            refresh("0; url=http://example.org/", "https://example.com/"),
+            Some(String::from("http://example.org/"))
+        );
+        assert_eq!(
+            refresh("0; url=b.html", "file:///tmp/a.html"),
+            Some(String::from("file:///tmp/b.html"))
+        );
+    }
+
+    #[test]
+    fn refresh_content() {
+        assert_eq!(refresh("5"), Some((5, "")));
+        assert_eq!(
+            refresh("0;url=https://example.com/"),
+            Some((0, "https://example.com/"))
+        );
+        assert_eq!(refresh(" 1.5 , URL = 'next page' "), Some((1, "next page")));
+        assert_eq!(refresh("2; next.html"), Some((2, "next.html")));
+        assert_eq!(refresh(".5"), Some((0, "")));
+        assert_eq!(refresh("soon"), None);
+        assert_eq!(refresh("5x; url=a"), None);
+        assert_eq!(refresh("0; ab日"), Some((0, "ab日")));
+        assert_eq!(refresh("0; u日"), Some((0, "u日")));
+    }
+}
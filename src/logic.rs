@@ -1,38 +1,13 @@
-/// Returns the absolute path of a given `relative_url`.
-pub fn absolute_path(current_page: &str, relative_url: &str) -> String {
-    let parts: Vec<_> = current_page.split('/').collect();
-
-    if relative_url.starts_with("http") {
-        relative_url.to_string()
-    } else if relative_url.starts_with('/') {
-        format!("{}//{}{}", parts[0], parts[2], relative_url)
-    } else if relative_url.starts_with("../") {
-        let count = relative_url.split("../").count();
-
-        let src = relative_url.replace("../", "");
-        let mut url = format!("{}//{}", parts[0], parts[2]);
-
-        if parts.len() != 3 {
-            for (i, item) in parts[3..].iter().enumerate() {
-                if parts.len() == i + 3 + count {
-                    break;
-                }
-                url = format!("{}/{}", url, item);
-            }
-        }
-        format!("{}/{}", url, src)
-    } else {
-        let mut url = format!("{}//{}", parts[0], parts[2]);
-
-        if parts.len() != 3 {
-            for (i, item) in parts[3..].iter().enumerate() {
-                if parts.len() == i + 4 {
-                    break;
-                }
-                url = format!("{}/{}", url, item);
-            }
-        }
-        format!("{}/{}", url, relative_url)
+use reqwest::Url;
+
+/// Resolves a `reference` found on a page against the page's url `base`,
+/// following the WHATWG URL standard (RFC 3986 reference resolution).
+/// Dot segments are removed, hosts IDNA-encoded and invalid characters percent-encoded.
+/// Without a `base`, only absolute urls are resolved.
+pub fn resolve(base: Option<&Url>, reference: &str) -> Option<Url> {
+    match base {
+        Some(base) => base.join(reference).ok(),
+        None => Url::parse(reference).ok(),
     }
 }
 
@@ -40,6 +15,12 @@ pub fn absolute_path(current_page: &str, relative_url: &str) -> String {
 mod get_url {
     use super::*;
 
+    fn resolved(base: &str, reference: &str) -> String {
+        resolve(Some(&Url::parse(base).unwrap()), reference)
+            .unwrap()
+            .to_string()
+    }
+
     #[test]
     fn absolute() {
         let current_page = "https://example.com/test/index.php";
@@ -47,7 +28,18 @@ mod get_url {
 
         assert_eq!(
             String::from("https://example.com/test/image.jpg"),
-            absolute_path(current_page, absolute)
+            resolved(current_page, absolute)
+        );
+    }
+
+    #[test]
+    fn absolute_other_scheme() {
+        let current_page = "gemini://example.com/test/index.gmi";
+        let absolute = "gopher://example.com/1/";
+
+        assert_eq!(
+            String::from("gopher://example.com/1/"),
+            resolved(current_page, absolute)
         );
     }
 
@@ -58,7 +50,7 @@ mod get_url {
 
         assert_eq!(
             String::from("https://example.com/image.jpg"),
-            absolute_path(current_page, relative_up)
+            resolved(current_page, relative_up)
         );
     }
 
@@ -69,7 +61,7 @@ mod get_url {
 
         assert_eq!(
             String::from("https://example.com/image.jpg"),
-            absolute_path(current_page, relative_up)
+            resolved(current_page, relative_up)
         );
     }
 
@@ -80,7 +72,7 @@ mod get_url {
 
         assert_eq!(
             String::from("https://example.com/test/image.jpg"),
-            absolute_path(current_page, relative_same)
+            resolved(current_page, relative_same)
         );
     }
 
@@ -91,7 +83,106 @@ mod get_url {
 
         assert_eq!(
             String::from("https://example.com/lol/image.jpg"),
-            absolute_path(current_page, root)
+            resolved(current_page, root)
+        );
+    }
+
+    #[test]
+    fn query_and_fragment() {
+        let current_page = "https://example.com/a/b?page=1#top";
+
+        assert_eq!(
+            resolved(current_page, "?page=2"),
+            "https://example.com/a/b?page=2"
+        );
+        assert_eq!(
+            resolved(current_page, "#end"),
+            "https://example.com/a/b?page=1#end"
+        );
+        assert_eq!(
+            resolved(current_page, "c?x=/../y#z"),
+            "https://example.com/a/c?x=/../y#z"
+        );
+    }
+
+    #[test]
+    fn protocol_relative() {
+        assert_eq!(
+            resolved("https://example.com/a/b", "//cdn.example.org/x.js"),
+            "https://cdn.example.org/x.js"
+        );
+        assert_eq!(
+            resolved("http://example.com/", "//cdn.example.org/x.js"),
+            "http://cdn.example.org/x.js"
+        );
+    }
+
+    #[test]
+    fn dot_segments() {
+        let current_page = "https://example.com/a/b/c";
+
+        assert_eq!(resolved(current_page, "./d"), "https://example.com/a/b/d");
+        assert_eq!(resolved(current_page, "."), "https://example.com/a/b/");
+        assert_eq!(
+            resolved(current_page, "../../../../d"),
+            "https://example.com/d"
+        );
+        assert_eq!(
+            resolved(current_page, "d/./e/../f"),
+            "https://example.com/a/b/d/f"
+        );
+    }
+
+    #[test]
+    fn other_schemes() {
+        let current_page = "https://example.com/a/b";
+
+        assert_eq!(
+            resolved(current_page, "mailto:someone@example.com"),
+            "mailto:someone@example.com"
+        );
+        assert_eq!(
+            resolved(current_page, "data:text/plain,a/../b"),
+            "data:text/plain,a/../b"
+        );
+        assert_eq!(
+            resolved("gemini://example.com/a/b.gmi", "../c.gmi"),
+            "gemini://example.com/c.gmi"
+        );
+        assert_eq!(
+            resolved("gopher://example.com/1/a/", "b"),
+            "gopher://example.com/1/a/b"
+        );
+    }
+
+    #[test]
+    fn normalised() {
+        assert_eq!(
+            resolved("https://example.com/", "HTTPS://BÜCHER.example/ä b"),
+            "https://xn--bcher-kva.example/%C3%A4%20b"
+        );
+        assert_eq!(
+            resolved("https://example.com:443/", "  x\ty.png\n"),
+            "https://example.com/xy.png"
+        );
+    }
+
+    #[test]
+    fn short_inputs() {
+        assert_eq!(resolve(None, "image.jpg"), None);
+        assert_eq!(resolve(None, ""), None);
+        assert_eq!(
+            resolved("https://example.com/a", ""),
+            "https://example.com/a"
+        );
+        assert_eq!(
+            resolved("https://example.com/a/", "b"),
+            "https://example.com/a/b"
+        );
+        // a page that can't be a base
+        assert_eq!(
+            resolve(Some(&Url::parse("mailto:a@example.com").unwrap()), "b"),
+            None
         );
     }
 }
@@ -1,13 +1,22 @@
+mod about;
+mod clean_url;
+mod config;
 mod css;
 mod data_storage;
 mod display;
 mod dom;
+mod form;
+mod gemtext;
+mod gophermap;
 mod gui;
+mod history;
 mod html;
 mod layout;
 mod logic;
 mod markdown;
 mod resource_manager;
+mod save;
+mod search;
 mod style;
 mod stylednode;
 mod tab;
@@ -52,7 +61,9 @@ view-source:<URL>       View source code of website"
 
     if !Path::new("cache/").exists() {
         fs::create_dir("cache").expect("to create cache directory");
-        fs::File::create("cache/cache.csv").expect("to create cache index file");
+    }
+    if !Path::new("data/").exists() {
+        fs::create_dir("data").expect("to create data directory");
     }
 
     let (ttf_context, mut canvas, mut event_pump, sdl_context, text_util) =
@@ -74,11 +85,11 @@ view-source:<URL>       View source code of website"
     // holds current cursor, as it apparently needs to stay in scope to be effective
     let mut cursor;
 
-    cursor = sdl2::mouse::Cursor::from_system(SystemCursor::WaitArrow).unwrap();
-    cursor.set();
     tabs[current].browse(url, dimensions);
 
-    cursor = sdl2::mouse::Cursor::from_system(SystemCursor::Arrow).unwrap();
+    // busy cursor while the current tab is loading
+    let mut loading = true;
+    cursor = sdl2::mouse::Cursor::from_system(SystemCursor::WaitArrow).unwrap();
     cursor.set();
 
     let mut window = canvas.window_mut();
@@ -99,7 +110,11 @@ view-source:<URL>       View source code of website"
         // FIXME: to get smooth scrolling, maybe save a offset to scroll and each frame update it by 1px until the offset is gone
         for command in &commands {
             match command {
-                Command::Quit => {
+                Command::Stop if tabs[current].loading_url().is_some() => {
+                    tabs[current].cancel();
+                    gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
+                }
+                Command::Quit | Command::Stop => {
                     if tabs.len() > 1 {
                         // Maybe add checkbox: Warn me when I attempt to close multiple tabs
                         let buttons: Vec<_> = vec![
@@ -147,7 +162,8 @@ view-source:<URL>       View source code of website"
 
                             layout_height = layout.dimensions.margin_box().height;
 
-                            tabs[current].display_list = display::build_display_list(&layout);
+                            tabs[current].display_list =
+                                display::build_display_list(&layout, &tabs[current].resources);
                         }
                     }
 
@@ -259,24 +275,23 @@ view-source:<URL>       View source code of website"
                 Command::OpenUrl(url) => {
                     let dimensions = viewport.size();
 
-                    cursor = sdl2::mouse::Cursor::from_system(SystemCursor::WaitArrow).unwrap();
-                    cursor.set();
                     tabs[current].browse(url.to_string(), dimensions);
-                    cursor = sdl2::mouse::Cursor::from_system(SystemCursor::Arrow).unwrap();
-                    cursor.set();
 
                     gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
                 }
                 Command::Reload(new_tab) => {
                     let dimensions = viewport.size();
-                    let new_url = tabs[current].url.clone();
+                    // documents shown by the browser, like page info, can't be loaded again
+                    let new_url = tabs[current].history_url().map(str::to_string);
 
-                    if *new_tab {
-                        tabs.push(tab::Tab::new());
-                        current = tabs.len() - 1;
-                        tabs[current].browse(new_url, dimensions);
-                    } else {
-                        tabs[current].open(new_url, dimensions);
+                    match new_url {
+                        Some(new_url) if *new_tab => {
+                            tabs.push(tab::Tab::new());
+                            current = tabs.len() - 1;
+                            tabs[current].browse(new_url, dimensions);
+                        }
+                        Some(new_url) => tabs[current].load(new_url, dimensions),
+                        None => {}
                     }
 
                     gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
@@ -303,14 +318,11 @@ view-source:<URL>       View source code of website"
                     cursor.set();
                 }
                 Command::OpenUrlbar => {
-                    if !text_input.is_empty() {
+                    // form fields can be emptied
+                    if !text_input.is_empty() || tabs[current].editing() {
                         let dimensions = viewport.size();
 
-                        cursor = sdl2::mouse::Cursor::from_system(SystemCursor::WaitArrow).unwrap();
-                        cursor.set();
                         tabs[current].browse(text_input, dimensions);
-                        cursor = sdl2::mouse::Cursor::from_system(SystemCursor::Arrow).unwrap();
-                        cursor.set();
 
                         gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
 
@@ -331,6 +343,64 @@ view-source:<URL>       View source code of website"
                     tabs[current].go_back(dimensions);
                     gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
                 }
+                Command::ClearCookies(all) => {
+                    let result = if *all {
+                        data_storage::clear_cookies()
+                    } else {
+                        match &tabs[current].url {
+                            Some(url) => data_storage::clear_site_cookies(url),
+                            None => Ok(()),
+                        }
+                    };
+
+                    if let Err(error) = result {
+                        eprintln!("Couldn't clear cookies: {}", error);
+                    }
+                }
+                Command::PageInfo => {
+                    let url = format!("page-info:{}", tabs[current].address());
+                    let page_info = tabs[current].page_info();
+
+                    tabs.push(tab::Tab::new());
+                    current = tabs.len() - 1;
+                    tabs[current].show_document(url, page_info, viewport.size());
+
+                    gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
+                    window = canvas.window_mut();
+                    set_title(window, &tabs[current].title);
+                }
+                Command::Downloads => {
+                    tabs.push(tab::Tab::new());
+                    current = tabs.len() - 1;
+                    tabs[current].browse(String::from("about:downloads"), viewport.size());
+
+                    gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
+                }
+                Command::ToggleOffline => {
+                    let offline = !data_storage::offline();
+                    data_storage::set_offline(offline);
+                    println!("{}", if offline { "Offline" } else { "Online" });
+                }
+                Command::ToggleRecording => match data_storage::warc::stop_recording() {
+                    Some(path) => println!("Stopped recording to {}", path.display()),
+                    None => match data_storage::warc::start_recording() {
+                        Ok(path) => println!("Recording to {}", path.display()),
+                        Err(error) => eprintln!("Couldn't start recording: {}", error),
+                    },
+                },
+                Command::SavePage => match tabs[current].save_page() {
+                    Ok(path) => println!("Saved to {}", path.display()),
+                    Err(error) => eprintln!("Couldn't save page: {}", error),
+                },
+                Command::AddSearchEngine => match tabs[current].search_engine.clone() {
+                    Some(description) => {
+                        std::thread::spawn(move || match search::import(&description) {
+                            Ok(name) => println!("Added search engine {}", name),
+                            Err(error) => eprintln!("Couldn't add search engine: {}", error),
+                        });
+                    }
+                    None => println!("No search engine on this page"),
+                },
                 Command::Fullscreen => {
                     window = canvas.window_mut();
                     let state = window.fullscreen_state();
@@ -438,11 +508,14 @@ view-source:<URL>       View source code of website"
                                             if let Some(href) = &element.get_attribute("href") {
                                                 let dimensions = viewport.size();
 
-                                                let url = (*logic::absolute_path(
-                                                    &tabs[current].url,
+                                                let url = logic::resolve(
+                                                    tabs[current].url.as_ref(),
                                                     href,
-                                                ))
-                                                .to_string();
+                                                )
+                                                .map_or_else(
+                                                    || href.to_string(),
+                                                    |url| url.to_string(),
+                                                );
 
                                                 if btn == &sdl2::mouse::MouseButton::Middle {
                                                     // always open in new tab on middle click
@@ -457,17 +530,7 @@ view-source:<URL>       View source code of website"
                                                     }
                                                 }
 
-                                                cursor = sdl2::mouse::Cursor::from_system(
-                                                    SystemCursor::WaitArrow,
-                                                )
-                                                .unwrap();
-                                                cursor.set();
                                                 tabs[current].browse(url, dimensions);
-                                                cursor = sdl2::mouse::Cursor::from_system(
-                                                    SystemCursor::Arrow,
-                                                )
-                                                .unwrap();
-                                                cursor.set();
                                                 gui::display(
                                                     (&mut canvas, &texture_creator),
                                                     managers,
@@ -475,6 +538,30 @@ view-source:<URL>       View source code of website"
                                                     current,
                                                 );
                                             }
+                                        } else if element
+                                            .attributes
+                                            .contains_key(form::FIELD_ATTRIBUTE)
+                                        {
+                                            let element = element.clone();
+                                            let dimensions = viewport.size();
+                                            if let Some(value) =
+                                                tabs[current].click_field(&element, dimensions)
+                                            {
+                                                // typed in like an url, entered with return
+                                                text_input = value;
+                                                text_util.start();
+                                                cursor = sdl2::mouse::Cursor::from_system(
+                                                    SystemCursor::IBeam,
+                                                )
+                                                .unwrap();
+                                                cursor.set();
+                                            }
+                                            gui::display(
+                                                (&mut canvas, &texture_creator),
+                                                managers,
+                                                &tabs,
+                                                current,
+                                            );
                                         }
                                     }
                                 }
@@ -485,6 +572,36 @@ view-source:<URL>       View source code of website"
             }
         }
 
+        // swap in pages loaded in the background
+        let mut loaded = false;
+        for (i, tab) in tabs.iter_mut().enumerate() {
+            if tab.poll() && i == current {
+                loaded = true;
+            }
+        }
+        // `<meta http-equiv="refresh">` redirects
+        let dimensions = canvas.viewport().size();
+        for tab in tabs.iter_mut() {
+            tab.follow_refresh(dimensions);
+        }
+
+        if loaded {
+            gui::display((&mut canvas, &texture_creator), managers, &tabs, current);
+            window = canvas.window_mut();
+            set_title(window, &tabs[current].title);
+        }
+
+        if tabs[current].loading_url().is_some() != loading {
+            loading = !loading;
+            let system_cursor = if loading {
+                SystemCursor::WaitArrow
+            } else {
+                SystemCursor::Arrow
+            };
+            cursor = sdl2::mouse::Cursor::from_system(system_cursor).unwrap();
+            cursor.set();
+        }
+
         // display text input of search bar
         if !text_input.is_empty() {
             use crate::css::Color;
@@ -0,0 +1,267 @@
+//! Saving a page as one html file, its stylesheets and images inlined as `data:` urls,
+//! so it looks the same when opened again without network access
+
+use crate::data_storage::{self, Resources};
+use crate::dom::{Node, NodeType};
+use crate::html;
+use crate::logic;
+
+use reqwest::Url;
+use std::collections::HashMap;
+
+const IMAGE_TYPES: [&str; 4] = ["image/jpeg", "image/gif", "image/png", "image/webp"];
+const ICON_TYPES: [&str; 5] = [
+    "image/x-icon",
+    "image/vnd.microsoft.icon",
+    "image/png",
+    "image/gif",
+    "image/jpeg",
+];
+/// elements without content and end tag
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Self-contained html of the page at `url` parsed from `source`.
+/// Resources fetched for showing the page are reused, others are downloaded now.
+pub fn page(source: &str, url: &Url, resources: &Resources) -> String {
+    let document = html::parse(source.to_string(), Some(url.clone()));
+    let base = document.base.as_ref();
+    let mut root = document.root;
+
+    // stylesheets in document order, linked ones keep their media query
+    let mut head = String::from("<meta charset=\"utf-8\">");
+    for (sheet, media) in &document.stylesheets {
+        match media {
+            None => head.push_str(&format!(
+                "<style>{}</style>",
+                inline_css(sheet, base, resources)
+            )),
+            Some(media) => {
+                let sheet = Url::parse(sheet).ok();
+                if let Some(css) = sheet
+                    .as_ref()
+                    .and_then(|sheet| resources.content(sheet, vec!["text/css"]).ok())
+                {
+                    let css = inline_css(&css, sheet.as_ref(), resources);
+                    let href = data_storage::to_data_url("text/css;charset=utf-8", css.as_bytes());
+                    head.push_str(&format!(
+                        "<link rel=\"stylesheet\"{}{}>",
+                        attribute("media", media),
+                        attribute("href", &href)
+                    ));
+                }
+            }
+        }
+    }
+    let hiding = data_storage::hiding_css(url);
+    if !hiding.is_empty() {
+        head.push_str(&format!("<style>{}</style>", hiding));
+    }
+
+    let icon = root.link_href("icon").map_or_else(
+        || logic::resolve(Some(url), "/favicon.ico"),
+        |href| Url::parse(href).ok(),
+    );
+    if let Some(icon) = icon.and_then(|icon| resources.data_url(&icon, ICON_TYPES.to_vec()).ok()) {
+        head.push_str(&format!("<link rel=\"icon\"{}>", attribute("href", &icon)));
+    }
+
+    inline(&mut root, base, resources);
+
+    if !is_element(&root, "html") {
+        root = Node::elem(String::from("html"), HashMap::new(), vec![root]);
+    }
+    if !root.children.iter().any(|child| is_element(child, "head")) {
+        let empty_head = Node::elem(String::from("head"), HashMap::new(), Vec::new());
+        root.children.insert(0, empty_head);
+    }
+
+    let mut saved = String::from("<!DOCTYPE html>");
+    serialize(&root, &mut Some(head), &mut saved);
+    saved
+}
+
+/// Inlines images and drops what is replaced by the head of the saved page,
+/// or would load something from the network.
+fn inline(node: &mut Node, base: Option<&Url>, resources: &Resources) {
+    node.children.retain(|child| {
+        let element = match &child.node_type {
+            NodeType::Element(element) => element,
+            NodeType::Text(_) => return true,
+        };
+        let rel = element
+            .get_attribute("rel")
+            .map(|rel| rel.to_ascii_lowercase());
+        let http_equiv = element.get_attribute("http-equiv");
+
+        match element.tag_name.as_str() {
+            "style" | "base" => false,
+            "link" => !rel.is_some_and(|rel| {
+                rel.split_whitespace()
+                    .any(|keyword| keyword == "stylesheet" || keyword == "icon")
+            }),
+            "meta" => !http_equiv.is_some_and(|value| value.eq_ignore_ascii_case("refresh")),
+            _ => true,
+        }
+    });
+
+    if let NodeType::Element(element) = &mut node.node_type {
+        let image = match element.tag_name.as_str() {
+            "img" => Some("src"),
+            "video" => Some("poster"),
+            _ => None,
+        };
+        if let Some(name) = image {
+            match element
+                .get_attribute(name)
+                .and_then(|src| Url::parse(src).ok())
+                .and_then(|src| resources.data_url(&src, IMAGE_TYPES.to_vec()).ok())
+            {
+                Some(data_url) => element.attributes.insert(name.to_string(), data_url),
+                // the alt text is shown instead
+                None => element.attributes.remove(name),
+            };
+        }
+
+        if let Some(style) = element.attributes.get_mut("style") {
+            *style = inline_css(style, base, resources);
+        }
+
+        // links to a part of the page stay on the saved page
+        if let (Some(href), Some(base)) = (element.attributes.get_mut("href"), base) {
+            if let Ok(mut link) = Url::parse(href) {
+                let fragment = link.fragment().map(str::to_string);
+                link.set_fragment(None);
+                let mut page = base.clone();
+                page.set_fragment(None);
+                if let (true, Some(fragment)) = (link == page, fragment) {
+                    *href = format!("#{}", fragment);
+                }
+            }
+        }
+    }
+
+    for child in &mut node.children {
+        inline(child, base, resources);
+    }
+}
+
+/// Css with the images of `url()`s inlined, resolved against the url of the stylesheet.
+/// Urls that can't be loaded are emptied, the saved page never requests anything.
+fn inline_css(css: &str, base: Option<&Url>, resources: &Resources) -> String {
+    let mut inlined = String::new();
+    let mut rest = css;
+
+    while let Some(start) = rest.to_ascii_lowercase().find("url(") {
+        inlined.push_str(&rest[..start + 4]);
+        rest = &rest[start + 4..];
+        let end = match rest.find(')') {
+            Some(end) => end,
+            None => break,
+        };
+
+        let reference = rest[..end].trim().trim_matches(['"', '\'']);
+        // base64 has neither quotes nor parentheses
+        let data_url = logic::resolve(base, reference)
+            .and_then(|url| resources.data_url(&url, IMAGE_TYPES.to_vec()).ok())
+            .unwrap_or_default();
+        inlined.push_str(&data_url);
+        inlined.push(')');
+        rest = &rest[end + 1..];
+    }
+
+    inlined.push_str(rest);
+    inlined
+}
+
+/// Appends the html of `node`, `head` goes to the start of the first `<head>`.
+fn serialize(node: &Node, head: &mut Option<String>, html: &mut String) {
+    let element = match &node.node_type {
+        NodeType::Text(text) => {
+            html.push_str(&data_storage::escape(&text.concat()));
+            return;
+        }
+        NodeType::Element(element) => element,
+    };
+
+    let mut attributes = element.attributes.iter().collect::<Vec<_>>();
+    attributes.sort();
+    html.push('<');
+    html.push_str(&element.tag_name);
+    for (name, value) in attributes {
+        html.push_str(&attribute(name, value));
+    }
+    html.push('>');
+
+    if element.tag_name == "head" {
+        html.push_str(&head.take().unwrap_or_default());
+    }
+    if VOID_ELEMENTS.contains(&element.tag_name.as_str()) {
+        return;
+    }
+    for child in &node.children {
+        serialize(child, head, html);
+    }
+    html.push_str(&format!("</{}>", element.tag_name));
+}
+
+/// ` name="value"`, attribute values aren't unescaped when parsed, so the quotes are chosen
+fn attribute(name: &str, value: &str) -> String {
+    if value.contains('"') {
+        format!(" {}='{}'", name, value)
+    } else {
+        format!(" {}=\"{}\"", name, value)
+    }
+}
+
+fn is_element(node: &Node, tag_name: &str) -> bool {
+    matches!(&node.node_type, NodeType::Element(element) if element.tag_name == tag_name)
+}
+
+#[cfg(test)]
+mod single_file {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn inlined() {
+        let dir = std::env::temp_dir().join("prowser-save-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("dot.png"), "png").unwrap();
+        fs::write(
+            dir.join("style.css"),
+            "body { background-image: url('dot.png') }",
+        )
+        .unwrap();
+        let url = Url::from_file_path(dir.join("page.html")).unwrap();
+
+        let saved = page(
+            "<html><head><title>A &amp; B</title><link rel='stylesheet' href='style.css'>\
+             <style>p { color: red }</style>\
+             <meta http-equiv='refresh' content='0; url=https://example.com/'></head>\
+             <body><a href='#end'>end</a><img src='dot.png'><img src='missing.png' alt='gone'>\
+             <p style=\"background: url(dot.png)\">a</p></body></html>",
+            &url,
+            &Resources::new(Some(&url), Default::default()),
+        );
+
+        let sheet = data_storage::to_data_url(
+            "text/css;charset=utf-8",
+            b"body { background-image: url(data:image/png;base64,cG5n) }",
+        );
+        assert!(saved.starts_with(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><link rel=\"stylesheet\" media=\"\" href=\""
+        ));
+        assert!(saved.contains(&sheet));
+        assert!(saved.contains("<style>p { color: red }</style>"));
+        assert!(saved.contains("<title>A &amp; B</title>"));
+        assert!(saved.contains("<a href=\"#end\">end</a>"));
+        assert!(saved.contains("<img src=\"data:image/png;base64,cG5n\"><img alt=\"gone\">"));
+        assert!(saved.contains("<p style=\"background: url(data:image/png;base64,cG5n)\">a</p>"));
+        assert!(!saved.contains("refresh"));
+        assert!(!saved.contains("file://"));
+    }
+}
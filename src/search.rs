@@ -0,0 +1,232 @@
+//! Searching from the url bar with the configured search engines,
+//! and adding engines from OpenSearch descriptions
+//! <https://github.com/dewitt/opensearch/blob/master/opensearch-1-1-draft-6.md>
+
+use crate::config::{self, SearchEngine};
+use crate::data_storage;
+use crate::form;
+use crate::logic;
+
+use reqwest::Url;
+
+/// Request searching for text typed into the url bar.
+pub fn request(input: &str) -> Result<form::Submission, String> {
+    let config = config::get();
+    let (engine, terms) = config.search_engine(input);
+    let url = Url::parse(&expand(&engine.url, terms)).map_err(|e| e.to_string())?;
+
+    Ok(match &engine.post {
+        Some(post) => form::Submission {
+            url,
+            post: Some(form::Post {
+                content_type: String::from("application/x-www-form-urlencoded"),
+                body: expand(post, terms).into_bytes(),
+            }),
+        },
+        None => form::Submission { url, post: None },
+    })
+}
+
+/// Fills in an OpenSearch template.
+/// Unknown and optional parameters like `{count?}` are left empty.
+fn expand(template: &str, terms: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        expanded.push_str(&rest[..start]);
+        match &rest[start + 1..end] {
+            "searchTerms" => {
+                expanded.push_str(&form::urlencoded(&[(String::new(), terms.to_string())])[1..])
+            }
+            "inputEncoding" | "outputEncoding" => expanded.push_str("UTF-8"),
+            "startPage" | "startPage?" => expanded.push('1'),
+            _ => {}
+        }
+        rest = &rest[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Downloads an OpenSearch description and adds its engine to the config.
+/// Returns the name of the engine.
+pub fn import(description_url: &Url) -> Result<String, String> {
+    let xml = data_storage::download_and_get(description_url, None, vec!["xml"])
+        .map_err(|_| format!("Not an OpenSearch description: {}", description_url))?;
+    let engine = parse_description(&xml, description_url)
+        .ok_or_else(|| format!("No html search in {}", description_url))?;
+
+    if config::get()
+        .engines
+        .iter()
+        .any(|known| known.name == engine.name)
+    {
+        return Err(format!("Search engine {} already exists", engine.name));
+    }
+    config::append(&engine.to_config())?;
+    Ok(engine.name)
+}
+
+/// Search engine of an OpenSearch description with a `text/html` url.
+fn parse_description(xml: &str, description_url: &Url) -> Option<SearchEngine> {
+    let name = element_text(xml, "ShortName")?
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // `<Url type="text/html" method="post" template="…"><Param name="q" value="{searchTerms}"/></Url>`
+    let mut rest = xml;
+    while let Some(start) = rest.find("<Url") {
+        let tag_end = start + rest[start..].find('>')?;
+        let tag = &rest[start..tag_end];
+        let content_end = if tag.ends_with('/') {
+            tag_end
+        } else {
+            tag_end + rest[tag_end..].find("</Url>").unwrap_or(0)
+        };
+        let content = &rest[tag_end..content_end];
+        rest = &rest[content_end..];
+
+        if attribute(tag, "type").as_deref() != Some("text/html") {
+            continue;
+        }
+        let template =
+            logic::resolve(Some(description_url), &attribute(tag, "template")?)?.to_string();
+        let params = content
+            .match_indices("<Param")
+            .filter_map(|(start, _)| {
+                let tag = &content[start..start + content[start..].find('>')?];
+                Some(format!(
+                    "{}={}",
+                    attribute(tag, "name")?,
+                    attribute(tag, "value")?
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let post =
+            attribute(tag, "method").is_some_and(|method| method.eq_ignore_ascii_case("post"));
+        let (url, post) = match (post, params.is_empty()) {
+            (true, _) => (template, Some(params)),
+            (false, true) => (template, None),
+            (false, false) if template.contains('?') => (format!("{}&{}", template, params), None),
+            (false, false) => (format!("{}?{}", template, params), None),
+        };
+
+        return Some(SearchEngine {
+            name,
+            url,
+            post,
+            keyword: None,
+        });
+    }
+
+    None
+}
+
+/// Text of the first `<name>` element.
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}", name))?;
+    let start = start + xml[start..].find('>')? + 1;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    Some(unescape(&xml[start..end]))
+}
+
+/// Value of an attribute of a start tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+    while let Some(position) = rest.find(name) {
+        let before = rest[..position].chars().last();
+        let after = rest[position + name.len()..].trim_start();
+        rest = &rest[position + name.len()..];
+
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        if let Some(value) = after.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &value[1..];
+            return Some(unescape(&value[..value.find(quote)?]));
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod engines {
+    use super::*;
+
+    #[test]
+    fn templates() {
+        assert_eq!(
+            expand(
+                "https://example.com/?q={searchTerms}&n={count?}&ie={inputEncoding}",
+                "a+b ü"
+            ),
+            "https://example.com/?q=a%2Bb+%C3%BC&n=&ie=UTF-8"
+        );
+    }
+
+    #[test]
+    fn default_engine() {
+        let search = request("rust lang").unwrap();
+
+        assert_eq!(search.url.as_str(), "https://duckduckgo.com/lite/");
+        assert_eq!(search.post.unwrap().body, b"q=rust+lang&kl=us-en");
+    }
+
+    #[test]
+    fn description() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/">
+  <ShortName>Example &amp; Co</ShortName>
+  <Url type="application/x-suggestions+json" template="https://example.com/suggest?q={searchTerms}"/>
+  <Url type="text/html" template="/search?q={searchTerms}&amp;src=os"/>
+</OpenSearchDescription>"#;
+
+        assert_eq!(
+            parse_description(
+                xml,
+                &Url::parse("https://example.com/opensearch.xml").unwrap()
+            ),
+            Some(SearchEngine {
+                name: String::from("Example & Co"),
+                url: String::from("https://example.com/search?q={searchTerms}&src=os"),
+                post: None,
+                keyword: None,
+            })
+        );
+    }
+
+    #[test]
+    fn description_post() {
+        let xml = r#"<OpenSearchDescription>
+  <ShortName>Posted</ShortName>
+  <Url type='text/html' method='POST' template='https://example.org/find'>
+    <Param name="query" value="{searchTerms}"/>
+    <Param name="lang" value="en"/>
+  </Url>
+</OpenSearchDescription>"#;
+
+        let url = Url::parse("https://example.org/os.xml").unwrap();
+        let engine = parse_description(xml, &url).unwrap();
+        assert_eq!(engine.url, "https://example.org/find");
+        assert_eq!(engine.post.as_deref(), Some("query={searchTerms}&lang=en"));
+    }
+}
@@ -2,6 +2,7 @@ use crate::css::{self, ChainedSelector, Rule, SimpleSelector, Specificity, Style
 use crate::dom::{ElementData, Node, NodeType};
 use crate::stylednode::StyledNode;
 
+use reqwest::Url;
 use std::collections::HashMap;
 
 /// Map from CSS property names to values.
@@ -11,19 +12,27 @@ pub type PropertyMap = HashMap<String, Value>;
 type MatchedRule<'a> = (Specificity, &'a Rule);
 
 /// Apply a stylesheet to an entire DOM tree, returning a `StyledNode` tree.
+/// Elements matched by a rule of `hidden` are not displayed, whatever their style.
 pub fn style_tree(
     root: Node,
     stylesheets: &[Stylesheet],
+    hidden: Option<&Stylesheet>,
     parent_style: &PropertyMap,
     combinators: Vec<Vec<&ElementData>>,
-    url: &str,
+    url: Option<&Url>,
 ) -> StyledNode {
     let mut combinators = combinators;
 
     let specified_values = match root.node_type {
         NodeType::Element(ref elem) => {
             combinators.last_mut().unwrap().push(elem);
-            let mut values = specified_values(elem, stylesheets, &combinators, url.to_string());
+            let mut values = specified_values(elem, stylesheets, &combinators, url);
+            if hidden.is_some_and(|hidden| !matching_rules(hidden, &combinators).is_empty()) {
+                values.insert(
+                    String::from("display"),
+                    Value::Keyword(String::from("none")),
+                );
+            }
             values = inherit_values(parent_style, values);
             values
         }
@@ -40,6 +49,7 @@ pub fn style_tree(
                 let child_node = style_tree(
                     child.to_owned(),
                     stylesheets,
+                    hidden,
                     &specified_values,
                     combinators.clone(),
                     url,
@@ -60,7 +70,7 @@ fn specified_values(
     elem: &ElementData,
     stylesheets: &[Stylesheet],
     combinators: &[Vec<&ElementData>],
-    url: String,
+    url: Option<&Url>,
 ) -> PropertyMap {
     let mut values = HashMap::new();
     let mut rules = Vec::new();
@@ -84,7 +94,7 @@ fn specified_values(
         let mut parser = css::parser::Parser {
             pos: 0,
             input: style.to_string(),
-            url,
+            url: url.cloned(),
         };
 
         for declaration in parser.parse_declarations() {
@@ -365,3 +375,41 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
     // We didn't find any non-matching selector components.
     true
 }
+
+#[cfg(test)]
+mod cascade {
+    use super::*;
+
+    #[test]
+    fn hidden_elements() {
+        let mut ad = HashMap::new();
+        ad.insert(String::from("class"), String::from("ad"));
+        let root = Node::elem(
+            String::from("body"),
+            HashMap::new(),
+            vec![
+                Node::elem(String::from("div"), ad, Vec::new()),
+                Node::elem(String::from("p"), HashMap::new(), Vec::new()),
+            ],
+        );
+        let stylesheets = [css::parse(
+            String::from("div, p { display: block !important }"),
+            None,
+            (800, 600),
+        )];
+        let hidden = css::parse(String::from(".ad { display: none }"), None, (800, 600));
+
+        let styled = style_tree(
+            root,
+            &stylesheets,
+            Some(&hidden),
+            &HashMap::new(),
+            vec![Vec::new()],
+            Url::parse("https://example.com/").ok().as_ref(),
+        );
+
+        let none = Value::Keyword(String::from("none"));
+        assert_eq!(styled.children[0].value("display"), Some(none.clone()));
+        assert_ne!(styled.children[1].value("display"), Some(none));
+    }
+}
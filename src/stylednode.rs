@@ -49,6 +49,18 @@ impl StyledNode {
         None
     }
 
+    /// Urls used as value of property `name` anywhere in the tree.
+    pub fn urls(&self, name: &str) -> Vec<String> {
+        let mut urls = Vec::new();
+        if let Some(Value::Url(url)) = self.value(name) {
+            urls.push(url);
+        }
+        for child in &self.children {
+            urls.extend(child.urls(name));
+        }
+        urls
+    }
+
     /// Return the specified value of a property if it exists, otherwise `None`.
     pub fn value(&self, name: &str) -> Option<Value> {
         self.specified_values.get(name).cloned()
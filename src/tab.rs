@@ -1,17 +1,33 @@
+use crate::about;
+use crate::clean_url;
+use crate::config;
 use crate::css;
 use crate::data_storage;
 use crate::display;
 use crate::dom;
+use crate::form;
+use crate::gemtext;
+use crate::gophermap;
+use crate::history;
 use crate::html;
 use crate::layout::lbox::LBox;
 use crate::logic;
+use crate::save;
+use crate::search;
 use crate::style;
 use crate::stylednode;
 
+use reqwest::Url;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 pub struct Tab {
-    pub url: String,
+    /// url of the page, `None` on a new tab
+    pub url: Option<Url>,
     pub display_list: Vec<display::DisplayCommand>,
     pub style_root: Option<stylednode::StyledNode>,
     pub layout_height: f32,
@@ -24,12 +40,56 @@ pub struct Tab {
     pub color: Option<css::Color>,
     /// path of favicon icon
     pub favicon: Option<String>,
+    /// url of a gemini prompt or gopher search waiting for user input, the next entry is sent as its query
+    pending_input: Option<Url>,
+    /// fetched stylesheets and images of the page
+    pub resources: data_storage::Resources,
+    /// forms of the page with the values entered
+    forms: Vec<form::Form>,
+    /// `(form, field)` of the text field being typed into
+    editing: Option<(usize, usize)>,
+    /// OpenSearch description linked by the page
+    pub search_engine: Option<Url>,
+    /// when and where a `<meta http-equiv="refresh">` of the page redirects
+    refresh: Option<(Instant, Url)>,
+    /// http redirects followed to the page
+    pub redirects: Vec<data_storage::Redirect>,
+    /// the page is a cached copy, shown in offline mode
+    pub offline: bool,
+    /// html the page was built from, for saving it
+    source: String,
+    /// page of the browser shown instead of the one navigated to, whose links may act on the browser
+    interstitial: Option<data_storage::Interstitial>,
+    /// page being loaded in the background
+    loading: Option<Loading>,
+    /// set when loading the page is cancelled
+    cancelled: Arc<AtomicBool>,
+}
+
+/// A page loaded on a worker thread
+struct Loading {
+    url: String,
+    page: Receiver<Tab>,
+    /// change of the history once the page is shown
+    history: Option<HistoryChange>,
+    /// tells the worker to stop fetching
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Change of the tab history made by a load
+enum HistoryChange {
+    /// a new entry for a page navigated to
+    Push(String),
+    /// back or forward, to the cursor
+    Move(usize),
+    /// the current entry is replaced, by a meta refresh
+    Replace(String),
 }
 
 impl Tab {
     pub fn new() -> Self {
         Self {
-            url: String::new(),
+            url: None,
             display_list: Vec::new(),
             style_root: None,
             layout_height: 0.0,
@@ -40,15 +100,248 @@ impl Tab {
             layout: None,
             color: None,
             favicon: Some(String::from("assets/icon.png")),
+            pending_input: None,
+            resources: data_storage::Resources::default(),
+            forms: Vec::new(),
+            editing: None,
+            search_engine: None,
+            refresh: None,
+            redirects: Vec::new(),
+            offline: false,
+            source: String::new(),
+            interstitial: None,
+            loading: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Loads an url on a worker thread, replacing a load in progress.
+    /// The current page stays visible until `poll` swaps in the new one.
+    /// no history
+    pub fn load(&mut self, url_to_open: String, dimensions: (u32, u32)) {
+        self.load_request(url_to_open, None, None, None, dimensions);
+    }
+
+    /// Loads an url, posting `post` to it if given.
+    /// `referrer` is the page it was navigated to from,
+    /// `history` is changed once the page is shown.
+    fn load_request(
+        &mut self,
+        url_to_open: String,
+        post: Option<form::Post>,
+        referrer: Option<Url>,
+        history: Option<HistoryChange>,
+        dimensions: (u32, u32),
+    ) {
+        self.cancel();
+        let (sender, receiver) = mpsc::channel();
+        let url = url_to_open.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+
+        thread::spawn(move || {
+            let mut page = Self::new();
+            page.cancelled = worker_cancelled;
+            match post {
+                Some(post) => page.open_post(url_to_open, post, dimensions),
+                None => page.open(url_to_open, referrer.as_ref(), dimensions),
+            }
+            // the receiver is gone if the load was cancelled
+            let _ = sender.send(page);
+        });
+
+        self.loading = Some(Loading {
+            url,
+            page: receiver,
+            history,
+            cancelled,
+        });
+    }
+
+    /// Swaps in the page once it finished loading.
+    /// Returns `true` if the tab changed.
+    pub fn poll(&mut self) -> bool {
+        let result = match &self.loading {
+            Some(loading) => loading.page.try_recv(),
+            None => return false,
+        };
+
+        match result {
+            Ok(page) => {
+                let loading = self.loading.take().expect("load to be in progress");
+                match loading.history {
+                    Some(HistoryChange::Push(url)) => {
+                        self.history.push(url);
+                        self.his_cursor += 1;
+                    }
+                    Some(HistoryChange::Move(cursor)) => self.his_cursor = cursor,
+                    Some(HistoryChange::Replace(url)) => {
+                        if let Some(entry) = self
+                            .his_cursor
+                            .checked_sub(1)
+                            .and_then(|current| self.history.get_mut(current))
+                        {
+                            *entry = url;
+                        }
+                    }
+                    None => {}
+                }
+                // history keeps the url redirects led to
+                if let Some(entry) = self
+                    .his_cursor
+                    .checked_sub(1)
+                    .and_then(|current| self.history.get_mut(current))
+                {
+                    if *entry == loading.url && !page.redirects.is_empty() {
+                        if let Some(url) = &page.url {
+                            *entry = url.to_string();
+                        }
+                    }
+                }
+                self.url = page.url;
+                self.display_list = page.display_list;
+                self.style_root = page.style_root;
+                self.layout_height = page.layout_height;
+                self.scrolled = page.scrolled;
+                self.title = page.title;
+                self.layout = page.layout;
+                self.color = page.color;
+                self.favicon = page.favicon;
+                self.pending_input = page.pending_input;
+                self.resources = page.resources;
+                self.forms = page.forms;
+                self.editing = None;
+                self.search_engine = page.search_engine;
+                self.refresh = page.refresh;
+                self.redirects = page.redirects;
+                self.offline = page.offline;
+                self.source = page.source;
+                self.interstitial = page.interstitial;
+
+                if let Some(url) = self.url.as_ref().filter(|url| url.scheme() != "about") {
+                    let title = self.title.as_deref().unwrap_or_default();
+                    if let Err(error) = history::record(url, title) {
+                        eprintln!("Couldn't record visit: {}", error);
+                    }
+                }
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            // the worker panicked
+            Err(TryRecvError::Disconnected) => {
+                let loading = self.loading.take().expect("load to be in progress");
+                self.title = Some(format!("Failed to load {}", loading.url));
+                true
+            }
+        }
+    }
+
+    /// Loads the target of the page's meta refresh once its delay has passed,
+    /// it replaces the page in history. Returns `true` if loading started.
+    pub fn follow_refresh(&mut self, dimensions: (u32, u32)) -> bool {
+        match &self.refresh {
+            Some((due, _)) if self.loading.is_none() && Instant::now() >= *due => {}
+            _ => return false,
+        }
+        let (_, url) = self.refresh.take().expect("refresh to be due");
+
+        self.load_request(
+            url.to_string(),
+            None,
+            None,
+            Some(HistoryChange::Replace(url.to_string())),
+            dimensions,
+        );
+        true
+    }
+
+    /// Html document about the page: its url and the redirects followed to it.
+    pub fn page_info(&self) -> String {
+        let redirects = if self.redirects.is_empty() {
+            String::from("<p>None</p>")
+        } else {
+            let items = self
+                .redirects
+                .iter()
+                .map(|redirect| {
+                    format!(
+                        "<li>{} {} to {}</li>",
+                        redirect.status,
+                        data_storage::escape(redirect.from.as_str()),
+                        data_storage::escape(redirect.to.as_str())
+                    )
+                })
+                .collect::<String>();
+            format!("<ol>{}</ol>", items)
+        };
+
+        let title = self.title.as_deref().unwrap_or(self.address());
+        data_storage::open_local_file("assets/page-info.html")
+            .expect("'page info' asset to be present")
+            .replace("replace_title", &data_storage::escape(title))
+            .replace("replace_url", &data_storage::escape(self.address()))
+            .replace("replace_redirects", &redirects)
+    }
+
+    /// Saves the page with its stylesheets and images as one html file to the download directory.
+    pub fn save_page(&self) -> Result<std::path::PathBuf, String> {
+        let url = match &self.url {
+            Some(url) if !self.source.is_empty() => url,
+            _ => return Err(format!("Nothing to save on {}", self.address())),
+        };
+        let html = save::page(&self.source, url, &self.resources);
+        let name = format!("{}.html", self.title.as_deref().unwrap_or("page"));
+        data_storage::downloads::save(url, Some(&name), html.as_bytes())
+    }
+
+    /// url of the page as shown in the url bar, empty on a new tab
+    pub fn address(&self) -> &str {
+        self.url.as_ref().map_or("", Url::as_str)
+    }
+
+    /// Shows a document generated by the browser, like `page_info` of another tab.
+    /// It can't be loaded again, so it gets no history entry.
+    pub fn show_document(&mut self, url: String, html_source: String, dimensions: (u32, u32)) {
+        self.interstitial = None;
+        self.open_html(url, html_source, Vec::new(), dimensions);
+    }
+
+    /// url of the current history entry, `None` for a document shown without one
+    pub fn history_url(&self) -> Option<&str> {
+        self.his_cursor
+            .checked_sub(1)
+            .and_then(|current| self.history.get(current))
+            .map(String::as_str)
+    }
+
+    /// Stops loading, keeping the current page.
+    /// Returns `false` if nothing was loading.
+    pub fn cancel(&mut self) -> bool {
+        match self.loading.take() {
+            Some(loading) => {
+                loading.cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
         }
     }
 
+    /// url of the page being loaded
+    pub fn loading_url(&self) -> Option<&str> {
+        self.loading.as_ref().map(|loading| loading.url.as_str())
+    }
+
     /// go 1 forward in tab history
     pub fn go_forward(&mut self, dimensions: (u32, u32)) {
         let hist_len = self.history.len();
         if hist_len > 1 && hist_len - self.his_cursor > 0 {
-            self.open(self.history[self.his_cursor].clone(), dimensions);
-            self.his_cursor += 1;
+            self.load_request(
+                self.history[self.his_cursor].clone(),
+                None,
+                None,
+                Some(HistoryChange::Move(self.his_cursor + 1)),
+                dimensions,
+            );
         }
     }
 
@@ -56,15 +349,20 @@ impl Tab {
     pub fn go_back(&mut self, dimensions: (u32, u32)) {
         let hist_len = self.history.len();
         if hist_len > 1 && hist_len - self.his_cursor + 1 > 0 && self.his_cursor != 1 {
-            self.his_cursor -= 1;
-            self.open(self.history[self.his_cursor - 1].clone(), dimensions);
+            self.load_request(
+                self.history[self.his_cursor - 2].clone(),
+                None,
+                None,
+                Some(HistoryChange::Move(self.his_cursor - 1)),
+                dimensions,
+            );
         }
     }
 
     /// open an url
     /// no history
-    pub fn open(&mut self, mut url_to_open: String, dimensions: (u32, u32)) {
-        let mut html_source;
+    fn open(&mut self, mut url_to_open: String, referrer: Option<&Url>, dimensions: (u32, u32)) {
+        let document;
         // FIXME: don't reload resource if only `#bookmark_id` changes
 
         if url_to_open.is_empty() {
@@ -74,9 +372,11 @@ impl Tab {
             let mut title = String::from("Source: ");
             title.push_str(&url_to_open[12..]);
             self.title = Some(title);
-            self.url = url_to_open.clone();
+            self.url = Url::parse(&url_to_open).ok();
 
-            let mut source = data_storage::download_and_get(&url_to_open[12..], vec!["text/html"])
+            let mut source = Url::parse(&url_to_open[12..])
+                .map_err(|e| e.to_string())
+                .and_then(|url| data_storage::download_and_get(&url, None, vec!["text/html"]))
                 .expect("download to work");
             source = source.replace("\t", "    ");
 
@@ -107,31 +407,44 @@ impl Tab {
             let style_root = style::style_tree(
                 root_node,
                 &Vec::new(),
+                None,
                 &HashMap::new(),
                 vec![Vec::new()],
-                &url_to_open,
+                None,
             );
 
             let layout =
                 display::layout(style_root.clone(), dimensions.0 as f32, dimensions.1 as f32);
             self.layout_height = layout.dimensions.margin_box().height;
-            self.display_list = display::build_display_list(&layout);
+            self.display_list = display::build_display_list(&layout, &self.resources);
 
             self.layout = Some(layout);
             self.style_root = Some(style_root);
             return;
-        } else if url_to_open.starts_with("gopher://") {
-            dbg!("maybe TODO: gopher");
-            return;
-        } else if url_to_open.starts_with("gemini://") {
-            dbg!("maybe TODO: gemini");
+        } else if data_storage::warc::is_replaying()
+            && (url_to_open.starts_with("gopher://") || url_to_open.starts_with("gemini://"))
+        {
+            // answered from the archive, which only holds http responses
+            document = for_tab(&url_to_open, &self.cancelled);
+        } else if url_to_open.starts_with("gopher://") || url_to_open.starts_with("gemini://") {
+            match Url::parse(&url_to_open) {
+                Ok(url) if url.scheme() == "gopher" => self.open_gopher(url, dimensions),
+                Ok(url) => self.open_gemini(url, dimensions),
+                Err(error) => {
+                    self.render_plain(gemtext::message("Error", &error.to_string()), dimensions)
+                }
+            }
             return;
         } else if url_to_open.starts_with("finger://") {
             dbg!("maybe TODO: finger");
             return;
-        } else if url_to_open.starts_with("file://") {
-            dbg!("TODO: open local file");
-            return;
+        } else if url_to_open.starts_with("about:") {
+            document = data_storage::TabDocument::new(match Url::parse(&url_to_open) {
+                Ok(url) => about::page(&url, referrer),
+                Err(error) => error.to_string(),
+            });
+        } else if url_to_open.starts_with("file://") || data_storage::is_data_url(&url_to_open) {
+            document = for_tab(&url_to_open, &self.cancelled);
         } else if !url_to_open.contains(' ')
             && url_to_open.contains('.')
             && !url_to_open.starts_with('.')
@@ -142,19 +455,59 @@ impl Tab {
             }
             if url_to_open.starts_with("https://dev.dev") {
                 println!("loading dev resource");
-                html_source = data_storage::open_local_file("assets/dev.html")
-                    .expect("dev' asset to be present");
+                document = data_storage::TabDocument::new(
+                    data_storage::open_local_file("assets/dev.html")
+                        .expect("dev' asset to be present"),
+                );
             } else {
-                html_source = data_storage::for_tab(&url_to_open);
+                document = for_tab(&url_to_open, &self.cancelled);
             }
         } else {
-            let params = [("q", &*url_to_open), ("kl", "us-en")];
-            html_source =
-                data_storage::download_and_get_post("https://duckduckgo.com/lite/", &params);
-            url_to_open = String::from("https://duckduckgo.com/lite/");
+            document = match search::request(&url_to_open) {
+                Ok(search) => {
+                    url_to_open = search.url.to_string();
+                    match search.post {
+                        Some(post) => data_storage::download_and_get_post(
+                            &search.url,
+                            &post.content_type,
+                            &post.body,
+                        ),
+                        None => data_storage::for_tab(&search.url, &self.cancelled),
+                    }
+                }
+                Err(error) => data_storage::TabDocument::new(error),
+            };
         }
 
-        self.url = url_to_open.clone();
+        self.offline = url_to_open.starts_with("http") && data_storage::offline();
+        self.interstitial = document.interstitial;
+        self.open_html(url_to_open, document.html, document.redirects, dimensions);
+    }
+
+    /// Posts form data and opens the response.
+    fn open_post(&mut self, url_to_open: String, post: form::Post, dimensions: (u32, u32)) {
+        let document = match Url::parse(&url_to_open) {
+            Ok(url) => data_storage::download_and_get_post(&url, &post.content_type, &post.body),
+            Err(error) => data_storage::TabDocument::new(error.to_string()),
+        };
+        self.interstitial = document.interstitial;
+        self.open_html(url_to_open, document.html, document.redirects, dimensions);
+    }
+
+    /// Parses, styles and renders an html document.
+    /// The page gets the url the `redirects` followed for it lead to.
+    fn open_html(
+        &mut self,
+        url_to_open: String,
+        mut html_source: String,
+        redirects: Vec<data_storage::Redirect>,
+        dimensions: (u32, u32),
+    ) {
+        self.url = match redirects.last() {
+            Some(redirect) => Some(redirect.to.clone()),
+            None => Url::parse(&url_to_open).ok(),
+        };
+        self.redirects = redirects;
 
         /* response_body = response_body
         .replace("\x0D\x0A", "\n")
@@ -162,53 +515,103 @@ impl Tab {
         .replace("\x0C", "\n")
         .replace("\x00", "\n");*/
         html_source = html_source.replace("\t", " ").replace("\n", "");
+        self.source = html_source.clone();
+
+        let document = html::parse(html_source, self.url.clone());
+        let mut root_node = document.root;
+        let mut raw_stylesheets = document.stylesheets;
+        let base = document.base;
+        self.forms = match &base {
+            Some(base) => form::collect(&mut root_node, base),
+            None => Vec::new(),
+        };
+
+        if let Some((delay, url)) = document.refresh {
+            if config::get().refresh_banner {
+                add_refresh_banner(&mut root_node, url.as_str());
+            } else {
+                self.refresh = Some((Instant::now() + delay, url));
+            }
+        }
+
+        // skip linked stylesheets of other media
+        raw_stylesheets.retain(|sheet| match sheet {
+            (_, None) => true,
+            (_, Some(query)) => {
+                let mut parser = css::media_query::parser::Parser {
+                    pos: 0,
+                    input: query.clone(),
+                };
+                parser.matches(dimensions)
+            }
+        });
+
+        // fetch stylesheets, images and the favicon at once
+        // `<link rel="icon">`, which may be a `data:` url, or `/favicon.ico`
+        let favicon_url = logic::resolve(
+            self.url.as_ref(),
+            root_node
+                .link_href("icon")
+                .map_or("/favicon.ico", String::as_str),
+        );
+        let mut resources = data_storage::Resources::new(self.url.as_ref(), self.cancelled.clone());
+        let sheet_urls = raw_stylesheets
+            .iter()
+            .filter(|(_, query)| query.is_some())
+            .filter_map(|(sheet_url, _)| Url::parse(sheet_url).ok())
+            .collect();
+        let mut image_urls = root_node.attribute_values("img", "src");
+        image_urls.extend(root_node.attribute_values("video", "poster"));
+        let mut image_urls = image_urls
+            .iter()
+            .filter_map(|url| Url::parse(url).ok())
+            .collect::<Vec<_>>();
+        image_urls.extend(favicon_url.clone());
+        resources.fetch(sheet_urls, data_storage::Kind::Stylesheet);
+        resources.fetch(image_urls, data_storage::Kind::Image);
 
-        let (root_node, raw_stylesheets) = html::parse(html_source, url_to_open.clone());
         let default_css = data_storage::open_local_file("assets/default-style.css")
             .expect("'default-style' asset to be present");
-        let mut stylesheets = vec![css::parse(default_css, String::new(), dimensions)];
+        let mut stylesheets = vec![css::parse(default_css, None, dimensions)];
 
         for sheet in raw_stylesheets {
             match sheet {
                 (style, None) => {
-                    stylesheets.push(css::parse(style, url_to_open.clone(), dimensions));
+                    stylesheets.push(css::parse(style, base.clone(), dimensions));
                 }
-                (sheet_url, Some(query)) => {
-                    let mut parser = css::media_query::parser::Parser {
-                        pos: 0,
-                        input: query,
-                    };
-
-                    if parser.matches(dimensions) {
-                        if let Ok(style) =
-                            data_storage::download_and_get(&sheet_url, vec!["text/css"])
-                        {
-                            stylesheets.push(css::parse(style, sheet_url, dimensions));
+                (sheet_url, Some(_)) => {
+                    if let Ok(sheet_url) = Url::parse(&sheet_url) {
+                        if let Ok(style) = resources.content(&sheet_url, vec!["text/css"]) {
+                            stylesheets.push(css::parse(style, Some(sheet_url), dimensions));
                         }
                     }
                 }
             }
         }
 
-        let style_root = style::style_tree(
+        self.favicon = favicon_url.and_then(|favicon_url| {
+            resources
+                .path(
+                    &favicon_url,
+                    vec![
+                        "image/x-icon",
+                        "image/vnd.microsoft.icon",
+                        "image/png",
+                        "image/gif",
+                        "image/jpeg",
+                    ],
+                )
+                .ok()
+        });
+
+        self.render(
             root_node,
             &stylesheets,
-            &HashMap::new(),
-            vec![Vec::new()],
-            &url_to_open,
+            base.as_ref(),
+            resources,
+            dimensions,
         );
-
-        let possible_title_node = style_root.finde_node("title", None);
-        if let Some(title_node) = possible_title_node {
-            // FIXME: assuming `<title>` has a text node as only child. Might break if no child or wont work if title is wrapped in other element
-            if let dom::NodeType::Text(title) = &title_node.children[0].node_type {
-                self.title = Some(title[0].clone());
-            } else {
-                self.title = None;
-            }
-        } else {
-            self.title = None;
-        }
+        let style_root = self.style_root.as_ref().expect("page to be rendered");
 
         {
             // FIXME: move this somewhere else. Don't block rendering
@@ -220,7 +623,7 @@ impl Tab {
                         let mut parser = css::parser::Parser {
                             pos: 0,
                             input: value.clone(),
-                            url: String::new(),
+                            url: None,
                         };
 
                         if let Some(css::Value::Color(color)) = parser.parse_value() {
@@ -231,11 +634,7 @@ impl Tab {
             }
         }
 
-        // favicon
-        let favicon_url = logic::absolute_path(&self.url, "/favicon.ico");
-        // FIXME: add possible other favicon positons https://en.wikipedia.org/wiki/Favicon#How_to_use
-        self.favicon = data_storage::download_cache_path(&favicon_url, vec!["image/x-icon"]).ok();
-
+        let mut search_engine = None;
         {
             // FIXME: move this somewhere else. Don't block rendering
             // FIXME: there can be multiple feed for different things
@@ -267,17 +666,78 @@ impl Tab {
                     }
                 }
             }
+
+            let search = style_root.finde_node(
+                "link",
+                Some(("type", "application/opensearchdescription+xml")),
+            );
+            if let Some(search_node) = search {
+                if let dom::NodeType::Element(element_data) = &search_node.node_type {
+                    if let Some(value) = element_data.attributes.get("href") {
+                        if let Some(description) = logic::resolve(self.url.as_ref(), value) {
+                            println!("Search engine found at {}", description);
+                            search_engine = Some(description);
+                        }
+                    }
+                }
+            }
+        }
+        self.search_engine = search_engine;
+    }
+
+    /// Styles, lays out and paints a DOM for the current url.
+    /// Urls in `style` attributes are resolved against `base`.
+    /// Background images are fetched into `resources` once styles are known.
+    fn render(
+        &mut self,
+        root_node: dom::Node,
+        stylesheets: &[css::Stylesheet],
+        base: Option<&Url>,
+        mut resources: data_storage::Resources,
+        dimensions: (u32, u32),
+    ) {
+        let hiding_css = self
+            .url
+            .as_ref()
+            .map(data_storage::hiding_css)
+            .unwrap_or_default();
+        let hidden = css::parse(hiding_css, None, dimensions);
+        let style_root = style::style_tree(
+            root_node,
+            stylesheets,
+            Some(&hidden),
+            &HashMap::new(),
+            vec![Vec::new()],
+            base,
+        );
+        resources.fetch(
+            style_root
+                .urls("background-image")
+                .iter()
+                .filter_map(|url| Url::parse(url).ok())
+                .collect(),
+            data_storage::Kind::Image,
+        );
+
+        let possible_title_node = style_root.finde_node("title", None);
+        if let Some(title_node) = possible_title_node {
+            // FIXME: assuming `<title>` has a text node as only child. Might break if no child or wont work if title is wrapped in other element
+            if let dom::NodeType::Text(title) = &title_node.children[0].node_type {
+                self.title = Some(title[0].clone());
+            } else {
+                self.title = None;
+            }
+        } else {
+            self.title = None;
         }
 
         let layout = display::layout(style_root.clone(), dimensions.0 as f32, dimensions.1 as f32);
         self.layout_height = layout.dimensions.margin_box().height;
-        self.display_list = display::build_display_list(&layout);
+        self.display_list = display::build_display_list(&layout, &resources);
 
         // scroll to bookmark link
         {
-            if let Some(pos) = self.url.find('#') {
-                let id = &self.url[pos + 1..];
-
+            if let Some(id) = self.url.as_ref().and_then(Url::fragment) {
                 if !id.is_empty() {
                     let y = if id == "top" {
                         Some(0.0)
@@ -300,13 +760,394 @@ impl Tab {
 
         self.layout = Some(layout);
         self.style_root = Some(style_root);
+        self.resources = resources;
+    }
+
+    /// Opens a gemini url, rendering gemtext, plain text, prompts and errors.
+    fn open_gemini(&mut self, url: Url, dimensions: (u32, u32)) {
+        self.url = Some(url.clone());
+        self.color = None;
+        self.favicon = None;
+
+        let root_node = match data_storage::gemini::request(&url) {
+            Ok(responce) => {
+                self.url = Some(responce.url.clone());
+                match responce.status / 10 {
+                    // input, the next entry is sent as query
+                    1 => {
+                        self.pending_input = Some(responce.url);
+                        gemtext::message(&responce.meta, "Enter your answer in the url bar.")
+                    }
+                    2 => {
+                        let mime_type = if responce.meta.is_empty() {
+                            "text/gemini"
+                        } else {
+                            &responce.meta
+                        };
+                        let body = data_storage::decode(&responce.body, mime_type);
+
+                        if mime_type.starts_with("text/gemini") {
+                            gemtext::parse(&body, &responce.url)
+                        } else if mime_type.starts_with("text/") {
+                            gemtext::preformatted(&body, responce.url.as_str())
+                        } else {
+                            gemtext::message(
+                                "Unsupported Mime Type",
+                                &format!("{} can not be displayed.", mime_type),
+                            )
+                        }
+                    }
+                    4 => gemtext::message("Temporary Failure", &responce.meta),
+                    5 => gemtext::message("Permanent Failure", &responce.meta),
+                    6 => gemtext::message("Client Certificate Required", &responce.meta),
+                    _ => gemtext::message("Invalid Response", &responce.meta),
+                }
+            }
+            Err(error) => gemtext::message("Error", &error),
+        };
+
+        self.render_plain(root_node, dimensions);
+    }
+
+    /// Opens a gopher url: menus, search prompts and text are rendered, other items downloaded.
+    fn open_gopher(&mut self, url: Url, dimensions: (u32, u32)) {
+        self.url = Some(url.clone());
+        self.color = None;
+        self.favicon = None;
+
+        let root_node = match data_storage::gopher::Target::parse(&url) {
+            Ok(target) if target.kind == '7' && target.query.is_none() => {
+                self.pending_input = Some(url);
+                gemtext::message("Search", "Enter your search in the url bar.")
+            }
+            Ok(target) => match data_storage::gopher::fetch(&target) {
+                Ok(body) => match target.kind {
+                    '1' | '7' => gophermap::render(
+                        &data_storage::gopher::parse_menu(&data_storage::decode(&body, "")),
+                        url.as_str(),
+                    ),
+                    '0' => gemtext::preformatted(&data_storage::decode(&body, ""), url.as_str()),
+                    _ => match data_storage::downloads::save(&url, None, &body) {
+                        Ok(path) => {
+                            gemtext::message("Download", &format!("Saved to {}", path.display()))
+                        }
+                        Err(error) => gemtext::message("Error", &error),
+                    },
+                },
+                Err(error) => gemtext::message("Error", &error),
+            },
+            Err(error) => gemtext::message("Error", &error),
+        };
+
+        self.render_plain(root_node, dimensions);
+    }
+
+    /// Renders a DOM generated by the browser itself with the default and the plain stylesheet.
+    fn render_plain(&mut self, root_node: dom::Node, dimensions: (u32, u32)) {
+        let default_css = data_storage::open_local_file("assets/default-style.css")
+            .expect("'default-style' asset to be present");
+        let plain_css =
+            data_storage::open_local_file("assets/plain.css").expect("'plain' asset to be present");
+        let stylesheets = vec![
+            css::parse(default_css, None, dimensions),
+            css::parse(plain_css, None, dimensions),
+        ];
+
+        let resources = data_storage::Resources::new(self.url.as_ref(), self.cancelled.clone());
+        self.render(root_node, &stylesheets, None, resources, dimensions);
     }
 
     /// browse to an url, appending url to tab history
-    pub fn browse(&mut self, url_to_open: String, dimensions: (u32, u32)) {
-        self.open(url_to_open.clone(), dimensions);
+    pub fn browse(&mut self, mut url_to_open: String, dimensions: (u32, u32)) {
+        // text typed into a form field
+        if let Some((form, field)) = self.editing.take() {
+            self.forms[form].fields[field].value = url_to_open;
+            if self.forms[form].submits_on_enter() {
+                self.submit(form, None, dimensions);
+            }
+            return;
+        }
+
+        // answer to a gemini prompt or gopher search
+        if let Some(prompt_url) = self.pending_input.take() {
+            if prompt_url.scheme() == "gopher" && !url_to_open.contains("://") {
+                url_to_open =
+                    data_storage::gopher::with_query(&prompt_url, &url_to_open).to_string();
+            } else if !url_to_open.contains("://") {
+                let mut url = prompt_url;
+                url.set_query(Some(&url_to_open));
+                url_to_open = url.to_string();
+            }
+        }
+
+        // "continue over http" of the https-only interstitial
+        if let Some(url) = url_to_open.strip_prefix("allow-http:") {
+            if self.interstitial != Some(data_storage::Interstitial::HttpsOnly) {
+                eprintln!("Ignored {}, not on the https-only page", url_to_open);
+                return;
+            }
+            url_to_open = url.to_string();
+            match Url::parse(&url_to_open) {
+                Ok(url) => {
+                    if let Err(error) = data_storage::allow_http(&url) {
+                        eprintln!("Couldn't remember http site: {}", error);
+                    }
+                }
+                Err(error) => eprintln!("Couldn't remember http site: {}", error),
+            }
+        }
+
+        // "go online and try again" of the offline page
+        if let Some(url) = url_to_open.strip_prefix("go-online:") {
+            if self.interstitial != Some(data_storage::Interstitial::Offline) {
+                eprintln!("Ignored {}, not on the offline page", url_to_open);
+                return;
+            }
+            url_to_open = url.to_string();
+            data_storage::set_offline(false);
+        }
+
+        // "stop replaying" of an archive
+        if let Some(url) = url_to_open.strip_prefix("stop-replay:") {
+            if self.interstitial != Some(data_storage::Interstitial::Archive) {
+                eprintln!("Ignored {}, not on a page of the archive", url_to_open);
+                return;
+            }
+            url_to_open = url.to_string();
+            data_storage::warc::stop_replay();
+        }
 
-        self.history.push(url_to_open);
-        self.his_cursor += 1;
+        // tracking parameters neither reach the site nor the history
+        url_to_open = clean_url::clean(&config::get(), &url_to_open);
+
+        let referrer = self.url.clone();
+        self.load_request(
+            url_to_open.clone(),
+            None,
+            referrer,
+            Some(HistoryChange::Push(url_to_open)),
+            dimensions,
+        );
+    }
+
+    /// Handles a click on a form control of the page.
+    /// Returns the current value of a text field to be typed into, the next `browse` sets it.
+    pub fn click_field(
+        &mut self,
+        element: &dom::ElementData,
+        dimensions: (u32, u32),
+    ) -> Option<String> {
+        self.editing = None;
+        let (form, field) = form::field_index(element)?;
+
+        match self.forms.get_mut(form)?.click(field) {
+            form::Click::Edit(value) => {
+                self.editing = Some((form, field));
+                Some(value)
+            }
+            form::Click::Submit => {
+                self.submit(form, Some(field), dimensions);
+                None
+            }
+            form::Click::Changed | form::Click::Nothing => None,
+        }
+    }
+
+    /// Is a form field being typed into?
+    pub fn editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Submits a form, the response is loaded like a followed link.
+    fn submit(&mut self, form: usize, submitter: Option<usize>, dimensions: (u32, u32)) {
+        let submission = self.forms[form].submit(submitter);
+
+        let referrer = self.url.clone();
+        self.load_request(
+            submission.url.to_string(),
+            submission.post,
+            referrer,
+            Some(HistoryChange::Push(submission.url.to_string())),
+            dimensions,
+        );
+    }
+}
+
+/// Document of an url typed or followed, the error if it isn't a valid url.
+fn for_tab(url: &str, cancelled: &AtomicBool) -> data_storage::TabDocument {
+    match Url::parse(url) {
+        Ok(url) => data_storage::for_tab(&url, cancelled),
+        Err(error) => data_storage::TabDocument::new(format!("Invalid url {}: {}", url, error)),
+    }
+}
+
+/// Puts a banner with a link to the meta refresh target at the top of the page.
+fn add_refresh_banner(root: &mut dom::Node, url: &str) {
+    let mut style = HashMap::new();
+    style.insert(
+        String::from("style"),
+        String::from("display: block; padding: 8px; background-color: #fff3bf; color: #222"),
+    );
+    let mut link = HashMap::new();
+    link.insert(String::from("href"), url.to_string());
+
+    let banner = dom::Node::elem(
+        String::from("div"),
+        style,
+        vec![
+            dom::Node::text(String::from("This page wants to redirect to ")),
+            dom::Node::elem(
+                String::from("a"),
+                link,
+                vec![dom::Node::text(url.to_string())],
+            ),
+        ],
+    );
+
+    // first thing in `<body>`, or in the root if there is none
+    let body = root.children.iter().position(|child| {
+        matches!(&child.node_type, dom::NodeType::Element(element) if element.tag_name == "body")
+    });
+    match body {
+        Some(body) => root.children[body].children.insert(0, banner),
+        None => root.children.insert(0, banner),
+    }
+}
+
+#[cfg(test)]
+mod loading {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait(tab: &mut Tab) {
+        for _ in 0..500 {
+            if tab.poll() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("page didn't load");
+    }
+
+    #[test]
+    fn swapped_in() {
+        let mut tab = Tab::new();
+        tab.title = None;
+
+        tab.browse(String::new(), (800, 600));
+        assert_eq!(tab.loading_url(), Some(""));
+        wait(&mut tab);
+
+        assert_eq!(tab.loading_url(), None);
+        assert_eq!(tab.title, Some(String::from("New Tab")));
+        assert_eq!(tab.history, vec![String::new()]);
+    }
+
+    #[test]
+    fn cancelled() {
+        let mut tab = Tab::new();
+        tab.title = None;
+
+        tab.load(String::new(), (800, 600));
+        assert!(tab.cancel());
+        assert!(!tab.cancel());
+        thread::sleep(Duration::from_millis(50));
+
+        assert!(!tab.poll());
+        assert_eq!(tab.title, None);
+    }
+
+    #[test]
+    fn cancelled_history() {
+        let mut tab = Tab::new();
+        tab.history = vec![String::from("about:"), String::from("about:blank")];
+        tab.his_cursor = 2;
+
+        tab.browse(String::from("about:"), (800, 600));
+        assert!(tab.cancel());
+        tab.go_back((800, 600));
+        assert!(tab.cancel());
+
+        assert_eq!(
+            tab.history,
+            vec![String::from("about:"), String::from("about:blank")]
+        );
+        assert_eq!(tab.his_cursor, 2);
+
+        tab.go_back((800, 600));
+        wait(&mut tab);
+        assert_eq!(tab.his_cursor, 1);
+        assert_eq!(tab.history_url(), Some("about:"));
+    }
+
+    #[test]
+    fn browser_links() {
+        let mut tab = Tab::new();
+        tab.url = Url::parse("https://example.com/").ok();
+
+        tab.browse(String::from("allow-http:http://example.com/"), (800, 600));
+        tab.interstitial = Some(data_storage::Interstitial::HttpsOnly);
+        tab.browse(String::from("go-online:https://example.com/"), (800, 600));
+        tab.browse(String::from("stop-replay:about:blank"), (800, 600));
+
+        assert_eq!(tab.loading_url(), None);
+        assert!(tab.history.is_empty());
+    }
+
+    #[test]
+    fn meta_refresh() {
+        let mut tab = Tab::new();
+        tab.history = vec![String::from("https://example.com/old")];
+        tab.his_cursor = 1;
+        let blank = Url::parse("about:blank").unwrap();
+        tab.refresh = Some((Instant::now() + Duration::from_secs(60), blank.clone()));
+        assert!(!tab.follow_refresh((800, 600)));
+
+        tab.refresh = Some((Instant::now(), blank));
+        assert!(tab.follow_refresh((800, 600)));
+        assert_eq!(tab.loading_url(), Some("about:blank"));
+        wait(&mut tab);
+        assert_eq!(tab.history, vec![String::from("about:blank")]);
+        assert_eq!(tab.refresh, None);
+    }
+
+    #[test]
+    fn refresh_banner() {
+        let mut root = html::parse(
+            String::from("<html><head></head><body><p>a</p></body></html>"),
+            None,
+        )
+        .root;
+        add_refresh_banner(&mut root, "https://example.com/next");
+
+        assert_eq!(
+            root.children[1].children[0].attribute_values("a", "href"),
+            vec!["https://example.com/next"]
+        );
+    }
+
+    #[test]
+    fn page_info() {
+        let mut tab = Tab::new();
+        let url = |url| Url::parse(url).unwrap();
+        tab.url = Some(url("https://example.com/docs/"));
+        tab.redirects = vec![data_storage::Redirect {
+            status: 302,
+            from: url("https://example.com/start?a&b"),
+            to: url("https://example.com/docs/"),
+        }];
+
+        assert!(tab.page_info().contains(
+            "<li>302 https://example.com/start?a&amp;b to https://example.com/docs/</li>"
+        ));
+
+        let mut info = Tab::new();
+        info.show_document(
+            format!("page-info:{}", tab.address()),
+            tab.page_info(),
+            (800, 600),
+        );
+        assert_eq!(info.address(), "page-info:https://example.com/docs/");
+        assert_eq!(info.history_url(), None);
     }
 }